@@ -14,6 +14,75 @@ export function generateGraphDot(e: Extension): Command {
   }
 }
 
+export function rebuildGraph(e: Extension): Command {
+  return async () => {
+    await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'rebuildGraph',
+      arguments: [],
+    })
+  }
+}
+
+export function lintAll(e: Extension): Command {
+  return async () => {
+    await e.lspClient.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'lintAll',
+      arguments: [],
+    })
+  }
+}
+
+export function glInfo(e: Extension): Command {
+  return async () => {
+    const info = await e.lspClient.sendRequest<{ vendor: string, renderer: string, version: string, glslVersion: string }>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'glInfo',
+      arguments: [],
+    })
+    vscode.window.showInformationMessage(`${info.vendor} ${info.renderer}, OpenGL ${info.version}, GLSL ${info.glslVersion}`)
+  }
+}
+
+export function graphStats(e: Extension): Command {
+  return async () => {
+    const stats = await e.lspClient.sendRequest<{ nodes: number, edges: number, roots: number, maxDepth: number }>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'graphStats',
+      arguments: [],
+    })
+    vscode.window.showInformationMessage(`${stats.nodes} files, ${stats.edges} includes, ${stats.roots} top-level files, includes nested up to ${stats.maxDepth} deep`)
+  }
+}
+
+export function gotoMergedDefinition(e: Extension): Command {
+  return async () => {
+    const editor = vscode.window.activeTextEditor
+    const definition = await e.lspClient.sendRequest<{ root: string, line: number } | null>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'mergedDefinition',
+      arguments: [editor.document.uri.path, editor.selection.active],
+    })
+    if (definition === null) {
+      vscode.window.showInformationMessage('No definition found in the flattened file')
+      return
+    }
+
+    const doc = await vscode.workspace.openTextDocument(vscode.Uri.parse('mcglsl:' + vscode.Uri.file(definition.root).path))
+    const position = new vscode.Position(definition.line, 0)
+    await vscode.window.showTextDocument(doc, { preview: true, selection: new vscode.Range(position, position) })
+  }
+}
+
+export function insertIncludeExtension(e: Extension): Command {
+  return async (path: string) => {
+    const edit = await e.lspClient.sendRequest<lsp.WorkspaceEdit | null>(lsp.ExecuteCommandRequest.type.method, {
+      command: 'includeExtensionEdit',
+      arguments: [path],
+    })
+    if (edit === null) {
+      return
+    }
+    await vscode.workspace.applyEdit(e.lspClient.protocol2CodeConverter.asWorkspaceEdit(edit))
+  }
+}
+
 export function restartExtension(e: Extension): Command {
   return async () => {
     vscode.window.showInformationMessage('Reloading Minecraft GLSL language server...')
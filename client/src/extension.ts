@@ -6,6 +6,7 @@ import { log } from './log'
 import { LanguageClient } from './lspClient'
 import { download, getReleaseInfo } from './net'
 import { PersistentState } from './persistent_state'
+import { registerProviders } from './providers'
 import * as path from 'path' 
 
 const platforms: { [key: string]: string } = {
@@ -43,6 +44,12 @@ export class Extension {
     this.registerCommand('graphDot', commands.generateGraphDot)
     this.registerCommand('restart', commands.restartExtension)
     this.registerCommand('virtualMerge', commands.virtualMergedDocument)
+    this.registerCommand('rebuildGraph', commands.rebuildGraph)
+    this.registerCommand('lintAll', commands.lintAll)
+    this.registerCommand('glInfo', commands.glInfo)
+    this.registerCommand('graphStats', commands.graphStats)
+    this.registerCommand('gotoMergedDefinition', commands.gotoMergedDefinition)
+    this.registerCommand('insertIncludeExtension', commands.insertIncludeExtension)
 
     log.info('starting language server...')
 
@@ -54,6 +61,8 @@ export class Extension {
     const filewatcherGlob = this.fileAssociationsToGlob(this.getGLSLFileAssociations())
   
     this.client = await new LanguageClient(this, lspBinary, filewatcherGlob).startServer()
+
+    registerProviders(this)
   
     log.info('language server started!')
   }
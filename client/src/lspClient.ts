@@ -1,8 +1,8 @@
-import { ConfigurationTarget, workspace } from 'vscode'
+import { ConfigurationTarget, workspace, WorkspaceFolder, WorkspaceFoldersChangeEvent } from 'vscode'
 import * as lsp from 'vscode-languageclient'
 import { Extension } from './extension'
 import { log, lspOutputChannel } from './log'
-import { ConfigUpdateParams, statusMethod, StatusParams, updateConfigMethod } from './lspExt'
+import { ConfigUpdateParams, saveLintDueMethod, SaveLintDueParams, statusMethod, StatusParams, updateConfigMethod } from './lspExt'
 
 export class LanguageClient extends lsp.LanguageClient {
   private extension: Extension
@@ -31,6 +31,8 @@ export class LanguageClient extends lsp.LanguageClient {
     
     this.onNotification(updateConfigMethod, this.onUpdateConfig)
     this.onNotification(statusMethod, this.onStatusChange)
+    this.onNotification(saveLintDueMethod, this.onSaveLintDue)
+    this.extension.context.subscriptions.push(workspace.onDidChangeWorkspaceFolders(this.onWorkspaceFoldersChange))
     
     return this
   }
@@ -48,6 +50,23 @@ export class LanguageClient extends lsp.LanguageClient {
     }
   }
 
+  // the server's debounce timer can't lint by itself, so it has the lint run as a command
+  onSaveLintDue = async (params: SaveLintDueParams) => {
+    await this.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'lintDueSave',
+      arguments: [params.path],
+    })
+  }
+
+  // the server doesn't receive workspace/didChangeWorkspaceFolders, so the change is sent as a command
+  onWorkspaceFoldersChange = async (event: WorkspaceFoldersChangeEvent) => {
+    const asFolder = (folder: WorkspaceFolder) => ({ uri: folder.uri.toString(), name: folder.name })
+    await this.sendRequest(lsp.ExecuteCommandRequest.type.method, {
+      command: 'workspaceFoldersChanged',
+      arguments: [{ event: { added: event.added.map(asFolder), removed: event.removed.map(asFolder) } }],
+    })
+  }
+
   onUpdateConfig = (params: ConfigUpdateParams) => {
     for (const kv of params.kv) {
       workspace.getConfiguration().update('mcglsl.' + kv.key, kv.value, ConfigurationTarget.Global)
@@ -13,4 +13,9 @@ export const updateConfigMethod = 'mc-glsl/updateConfig'
 
 export type ConfigUpdateParams = {
   kv: {key: string, value: string}[]
-}
\ No newline at end of file
+}
+export type SaveLintDueParams = {
+  path: string
+}
+
+export const saveLintDueMethod = 'mc-glsl/saveLintDue'
@@ -0,0 +1,86 @@
+import * as vscode from 'vscode'
+import * as lsp from 'vscode-languageclient'
+import { Extension } from './extension'
+
+// vscode-languageclient would send the standard requests for these, which the server can't
+// route, so they're provided here by the server's commands of the same names instead. The
+// server advertises each of them in its experimental capabilities.
+
+const selector: vscode.DocumentSelector = { scheme: 'file', language: 'glsl' }
+
+export function registerProviders(e: Extension) {
+  const experimental = e.lspClient.initializeResult?.capabilities.experimental ?? {}
+
+  if (experimental.semanticTokensLegend) {
+    const legend = new vscode.SemanticTokensLegend(
+      experimental.semanticTokensLegend.tokenTypes,
+      experimental.semanticTokensLegend.tokenModifiers,
+    )
+    e.context.subscriptions.push(vscode.languages.registerDocumentSemanticTokensProvider(selector, {
+      provideDocumentSemanticTokens: async (document: vscode.TextDocument) => {
+        const tokens = await e.lspClient.sendRequest<{ data: number[] }>(lsp.ExecuteCommandRequest.type.method, {
+          command: 'semanticTokens',
+          arguments: [document.uri.path],
+        })
+        return new vscode.SemanticTokens(new Uint32Array(tokens.data))
+      }
+    }, legend))
+  }
+
+  if (experimental.foldingRangeProvider) {
+    e.context.subscriptions.push(vscode.languages.registerFoldingRangeProvider(selector, {
+      provideFoldingRanges: async (document: vscode.TextDocument) => {
+        const ranges = await e.lspClient.sendRequest<lsp.FoldingRange[]>(lsp.ExecuteCommandRequest.type.method, {
+          command: 'foldingRanges',
+          arguments: [document.uri.path],
+        })
+        return e.lspClient.protocol2CodeConverter.asFoldingRanges(ranges)
+      }
+    }))
+  }
+
+  if (experimental.colorProvider) {
+    e.context.subscriptions.push(vscode.languages.registerColorProvider(selector, {
+      provideDocumentColors: async (document: vscode.TextDocument) => {
+        const colors = await e.lspClient.sendRequest<lsp.ColorInformation[]>(lsp.ExecuteCommandRequest.type.method, {
+          command: 'documentColors',
+          arguments: [document.uri.path],
+        })
+        return e.lspClient.protocol2CodeConverter.asColorInformations(colors)
+      },
+      provideColorPresentations: async (color: vscode.Color, context: { document: vscode.TextDocument, range: vscode.Range }) => {
+        const presentations = await e.lspClient.sendRequest<lsp.ColorPresentation[]>(lsp.ExecuteCommandRequest.type.method, {
+          command: 'colorPresentations',
+          arguments: [
+            context.document.uri.path,
+            { red: color.red, green: color.green, blue: color.blue, alpha: color.alpha },
+            e.lspClient.code2ProtocolConverter.asRange(context.range),
+          ],
+        })
+        return e.lspClient.protocol2CodeConverter.asColorPresentations(presentations)
+      }
+    }))
+  }
+
+  if (experimental.prepareRenameProvider) {
+    // registered after vscode-languageclient's rename provider so it's asked first, and sends the
+    // rename itself as the standard request, which the server does route
+    e.context.subscriptions.push(vscode.languages.registerRenameProvider(selector, {
+      prepareRename: async (document: vscode.TextDocument, position: vscode.Position) => {
+        const response = await e.lspClient.sendRequest<{ range: lsp.Range, placeholder: string }>(lsp.ExecuteCommandRequest.type.method, {
+          command: 'prepareRename',
+          arguments: [document.uri.path, e.lspClient.code2ProtocolConverter.asPosition(position)],
+        })
+        return { range: e.lspClient.protocol2CodeConverter.asRange(response.range), placeholder: response.placeholder }
+      },
+      provideRenameEdits: async (document: vscode.TextDocument, position: vscode.Position, newName: string) => {
+        const edit = await e.lspClient.sendRequest(lsp.RenameRequest.type, {
+          textDocument: e.lspClient.code2ProtocolConverter.asTextDocumentIdentifier(document),
+          position: e.lspClient.code2ProtocolConverter.asPosition(position),
+          newName,
+        })
+        return e.lspClient.protocol2CodeConverter.asWorkspaceEdit(edit)
+      }
+    }))
+  }
+}
@@ -0,0 +1,63 @@
+/// Signatures of commonly used GLSL built-in functions, keyed by function name. Overloaded
+/// functions have one entry per overload. `genType` stands for `float`, `vec2`, `vec3` or `vec4`.
+static BUILTIN_SIGNATURES: &[(&str, &str, &[&str])] = &[
+    ("abs", "genType", &["genType x"]),
+    ("acos", "genType", &["genType x"]),
+    ("asin", "genType", &["genType x"]),
+    ("atan", "genType", &["genType y", "genType x"]),
+    ("atan", "genType", &["genType y_over_x"]),
+    ("ceil", "genType", &["genType x"]),
+    ("clamp", "genType", &["genType x", "genType minVal", "genType maxVal"]),
+    ("clamp", "genType", &["genType x", "float minVal", "float maxVal"]),
+    ("cos", "genType", &["genType angle"]),
+    ("cross", "vec3", &["vec3 x", "vec3 y"]),
+    ("degrees", "genType", &["genType radians"]),
+    ("distance", "float", &["genType p0", "genType p1"]),
+    ("dot", "float", &["genType x", "genType y"]),
+    ("exp", "genType", &["genType x"]),
+    ("exp2", "genType", &["genType x"]),
+    ("faceforward", "genType", &["genType N", "genType I", "genType Nref"]),
+    ("floor", "genType", &["genType x"]),
+    ("fract", "genType", &["genType x"]),
+    ("inversesqrt", "genType", &["genType x"]),
+    ("length", "float", &["genType x"]),
+    ("log", "genType", &["genType x"]),
+    ("log2", "genType", &["genType x"]),
+    ("max", "genType", &["genType x", "genType y"]),
+    ("max", "genType", &["genType x", "float y"]),
+    ("min", "genType", &["genType x", "genType y"]),
+    ("min", "genType", &["genType x", "float y"]),
+    ("mix", "genType", &["genType x", "genType y", "genType a"]),
+    ("mix", "genType", &["genType x", "genType y", "float a"]),
+    ("mod", "genType", &["genType x", "genType y"]),
+    ("mod", "genType", &["genType x", "float y"]),
+    ("normalize", "genType", &["genType x"]),
+    ("pow", "genType", &["genType x", "genType y"]),
+    ("radians", "genType", &["genType degrees"]),
+    ("reflect", "genType", &["genType I", "genType N"]),
+    ("refract", "genType", &["genType I", "genType N", "float eta"]),
+    ("sign", "genType", &["genType x"]),
+    ("sin", "genType", &["genType angle"]),
+    ("smoothstep", "genType", &["genType edge0", "genType edge1", "genType x"]),
+    ("smoothstep", "genType", &["float edge0", "float edge1", "genType x"]),
+    ("sqrt", "genType", &["genType x"]),
+    ("step", "genType", &["genType edge", "genType x"]),
+    ("step", "genType", &["float edge", "genType x"]),
+    ("tan", "genType", &["genType angle"]),
+    ("texelFetch", "gvec4", &["gsampler2D sampler", "ivec2 P", "int lod"]),
+    ("texture", "gvec4", &["gsampler2D sampler", "vec2 P"]),
+    ("texture", "gvec4", &["gsampler2D sampler", "vec2 P", "float bias"]),
+    ("texture2D", "vec4", &["sampler2D sampler", "vec2 coord"]),
+    ("texture2D", "vec4", &["sampler2D sampler", "vec2 coord", "float bias"]),
+    ("texture2DLod", "vec4", &["sampler2D sampler", "vec2 coord", "float lod"]),
+    ("textureLod", "gvec4", &["gsampler2D sampler", "vec2 P", "float lod"]),
+    ("textureSize", "ivec2", &["gsampler2D sampler", "int lod"]),
+];
+
+/// Returns the return type and parameters of every overload of the built-in function `name`.
+pub fn builtin_signatures(name: &str) -> Vec<(&'static str, &'static [&'static str])> {
+    BUILTIN_SIGNATURES.iter()
+        .filter(|(builtin, _, _)| *builtin == name)
+        .map(|(_, return_type, parameters)| (*return_type, *parameters))
+        .collect()
+}
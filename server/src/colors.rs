@@ -0,0 +1,98 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+
+use rust_lsp::lsp_types::{Color, ColorInformation, ColorPresentation, Position, Range, TextEdit};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RE_COLOR_LITERAL: Regex = Regex::new(
+        r#"\b(vec[34])\s*\(\s*([0-9.]+f?)\s*,\s*([0-9.]+f?)\s*,\s*([0-9.]+f?)\s*(?:,\s*([0-9.]+f?)\s*)?\)"#
+    ).unwrap();
+}
+
+/// Parses a float literal, such as `0.5`, `.5`, `1.` or `1.0f`, if it is within 0 to 1.
+fn component(literal: &str) -> Option<f32> {
+    let value: f32 = literal.trim_end_matches('f').parse().ok()?;
+    if (0.0..=1.0).contains(&value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn position(line: usize, character: usize) -> Position {
+    Position::new(u32::try_from(line).unwrap(), u32::try_from(character).unwrap())
+}
+
+/// Returns the colors of `source`, being every `vec3(r, g, b)` and `vec4(r, g, b, a)` whose
+/// components are all numeric literals from 0 to 1. A `vec3` is opaque.
+pub fn document_colors(source: &str) -> Vec<ColorInformation> {
+    let mut colors = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        for cap in RE_COLOR_LITERAL.captures_iter(line) {
+            let is_vec4 = &cap[1] == "vec4";
+            // a vec3 with a fourth component or a vec4 without one isn't valid GLSL
+            if is_vec4 != cap.get(5).is_some() {
+                continue;
+            }
+
+            let components: Option<Vec<f32>> = (2..=5)
+                .filter_map(|i| cap.get(i))
+                .map(|m| component(m.as_str()))
+                .collect();
+            let components = match components {
+                Some(components) => components,
+                None => continue,
+            };
+
+            let literal = cap.get(0).unwrap();
+            colors.push(ColorInformation {
+                range: Range::new(position(n, literal.start()), position(n, literal.end())),
+                color: Color {
+                    red: components[0],
+                    green: components[1],
+                    blue: components[2],
+                    alpha: components.get(3).copied().unwrap_or(1.0),
+                },
+            });
+        }
+    }
+
+    colors
+}
+
+/// Formats a color component as a float literal with up to 3 decimals, such as `0.5` or `1.0`.
+fn format_component(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let formatted = formatted.trim_end_matches('0');
+    if formatted.ends_with('.') {
+        format!("{}0", formatted)
+    } else {
+        formatted.to_string()
+    }
+}
+
+/// Returns the presentation of `color` replacing the literal at `range` of `source`, keeping
+/// the literal a `vec3` if it was one and the color is opaque.
+pub fn color_presentations(source: &str, color: Color, range: Range) -> Vec<ColorPresentation> {
+    let is_vec3 = source.lines()
+        .nth(range.start.line as usize)
+        .and_then(|line| line.get(range.start.character as usize..))
+        .map_or(false, |literal| literal.starts_with("vec3"));
+
+    let label = if is_vec3 && color.alpha >= 1.0 {
+        format!("vec3({}, {}, {})", format_component(color.red), format_component(color.green), format_component(color.blue))
+    } else {
+        format!("vec4({}, {}, {}, {})", format_component(color.red), format_component(color.green),
+            format_component(color.blue), format_component(color.alpha))
+    };
+
+    vec![ColorPresentation {
+        text_edit: Some(TextEdit::new(range, label.clone())),
+        label,
+        additional_text_edits: None,
+    }]
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::{HashMap, HashSet}, path::PathBuf};
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::fs::OpenOptions;
@@ -6,14 +6,18 @@ use std::io::prelude::*;
 
 use serde_json::Value;
 
-use petgraph::{dot, graph::NodeIndex};
+use path_slash::PathBufExt;
+
+use petgraph::graph::NodeIndex;
 
 use anyhow::{Result, format_err};
 
-use std::fs;
+use log::debug;
 
-use crate::{graph::CachedStableGraph, merge_views, url_norm::FromJSON};
-use crate::dfs;
+use crate::{graph::CachedStableGraph, merge_views, symbols, url_norm::FromJSON};
+use crate::{dfs, MinecraftShaderLanguageServer, TreeType};
+use crate::files::FileProvider;
+use crate::opengl::ShaderValidator;
 
 pub struct CustomCommandProvider {
     commands: HashMap<String, Box<dyn Invokeable>>
@@ -47,7 +51,7 @@ pub struct GraphDotCommand {
 impl Invokeable for GraphDotCommand {
     fn run_command(&self, root: &PathBuf, _: Vec<Value>) -> Result<Value> {
         let filepath = root.join("graph.dot");
-        eprintln!("generating dot file at {:?}", filepath);
+        debug!("generating dot file at {:?}", filepath);
         let mut file = OpenOptions::new()
             .truncate(true)
             .write(true)
@@ -56,10 +60,19 @@ impl Invokeable for GraphDotCommand {
             .unwrap();
 
         let mut write_data_closure = || -> Result<(), std::io::Error> {
-            let graph = self.graph.as_ref();
+            let graph = self.graph.borrow();
+
+            let mut dot = String::from("digraph {\n");
+            for node in graph.nodes() {
+                dot.push_str(&format!("    {:?}\n", node.to_string_lossy()));
+            }
+            for (parent, child, position) in graph.edges() {
+                dot.push_str(&format!("    {:?} -> {:?} [ label = \"line {}\" ]\n", parent.to_string_lossy(), child.to_string_lossy(), position.line + 1));
+            }
+            dot.push_str("}\n");
 
             file.seek(std::io::SeekFrom::Start(0))?;
-            file.write_all(dot::Dot::new(&graph.borrow().graph).to_string().as_bytes())?;
+            file.write_all(dot.as_bytes())?;
             file.flush()?;
             file.seek(std::io::SeekFrom::Start(0))?;
             Ok(())
@@ -72,91 +85,543 @@ impl Invokeable for GraphDotCommand {
     }
 }
 
-pub struct VirtualMergedDocument {
-    pub graph: Rc<RefCell<CachedStableGraph>>
+pub(crate) struct VirtualMergedDocument<'a> {
+    // merges the same way as linting does, with `mcglsl.mergeDedup` and `mcglsl.maxIncludeDepth`
+    pub(crate) server: &'a MinecraftShaderLanguageServer,
+    // the server's files, with the unsaved contents of the documents open in the client
+    pub(crate) files: &'a dyn FileProvider,
 }
 
-impl VirtualMergedDocument {
-    // TODO: DUPLICATE CODE
-    fn get_file_toplevel_ancestors(&self, uri: &PathBuf) -> Result<Option<Vec<petgraph::stable_graph::NodeIndex>>> {
-        let curr_node = match self.graph.borrow_mut().find_node(uri) {
-            Some(n) => n,
-            None => return Err(format_err!("node not found {:?}", uri)),
+impl<'a> VirtualMergedDocument<'a> {
+    /// Returns the merged source of the tree rooted at `path`, erroring if `path` isn't top-level.
+    fn merged_source(&self, root: &PathBuf, path: &PathBuf) -> Result<String> {
+        let file_ancestors = match self.server.get_file_toplevel_ancestors(path) {
+            Ok(opt) => match opt {
+                Some(ancestors) => ancestors,
+                None => vec![],
+            },
+            Err(e) => return Err(e),
         };
-        let roots = self.graph.borrow().collect_root_ancestors(curr_node);
-        if roots.is_empty() {
-            return Ok(None);
+        
+        //eprintln!("ancestors for {}:\n\t{:?}", path, file_ancestors.iter().map(|e| self.graph.borrow().graph.node_weight(*e).unwrap().clone()).collect::<Vec<String>>());
+
+        // the set of all filepath->content. TODO: change to Url?
+        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
+
+        // if we are a top-level file (this has to be one of the set defined by Optifine, right?)
+        if file_ancestors.is_empty() {
+            // gather the list of all descendants 
+            let root = self.server.graph.borrow_mut().find_node(path).unwrap();
+            let tree = match self.server.get_dfs_for_node(root) {
+                Ok(tree) => tree,
+                Err(e) => return Err(e.into()),
+            };
+
+            // missing includes are merged in as empty, as when linting
+            let sources = match self.server.load_sources_from(self.files, &tree, &mut HashMap::new()) {
+                Ok(s) => s,
+                Err(e) => return Err(e)
+            };
+            all_sources.extend(sources);
+
+            let graph = self.server.graph.borrow();
+            let view = merge_views::generate_merge_list(&tree, &all_sources, &graph, self.server.config.merge_dedup);
+            return Ok(view);
         }
-        Ok(Some(roots))
+        return Err(format_err!("{:?} is not a top-level file aka has ancestors", path.strip_prefix(root).unwrap()))
     }
+}
 
-    pub fn get_dfs_for_node(&self, root: NodeIndex) -> Result<Vec<(NodeIndex, Option<NodeIndex>)>, dfs::error::CycleError> {
-        let graph_ref = self.graph.borrow();
+impl<'a> Invokeable for VirtualMergedDocument<'a> {
+    fn run_command(&self, root: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+
+        Ok(Value::String(self.merged_source(root, &path)?))
+    }
+}
 
-        let dfs = dfs::Dfs::new(&graph_ref, root);
+/// Returns `{ source, lines }` for the top-level file given as the first argument, where
+/// `lines[n]` is the `{ file, line }` (0-indexed) that line `n` of the merged source came
+/// from, or `null` for the `#line` directives inserted while merging.
+pub(crate) struct MergedViewCommand<'a> {
+    pub(crate) server: &'a MinecraftShaderLanguageServer,
+    pub(crate) files: &'a dyn FileProvider,
+}
 
-        dfs.collect::<Result<Vec<_>, _>>()
+impl<'a> Invokeable for MergedViewCommand<'a> {
+    fn run_command(&self, root: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let merger = VirtualMergedDocument{
+            server: self.server,
+            files: self.files,
+        };
+        let source = merger.merged_source(root, &path)?;
+
+        let lines: Vec<Value> = merge_views::line_map(&source, &path).into_iter().map(|mapping| match mapping {
+            Some((file, line)) => serde_json::json!({
+                "file": file,
+                "line": line,
+            }),
+            None => Value::Null,
+        }).collect();
+
+        Ok(serde_json::json!({
+            "source": source,
+            "lines": lines,
+        }))
     }
+}
 
-    pub fn load_sources(&self, nodes: &[(NodeIndex, Option<NodeIndex>)]) -> Result<HashMap<PathBuf, String>> {
-        let mut sources = HashMap::new();
+/// Finds where the definition of the identifier at `{ line, character }` (the second argument) in
+/// the file given as the first argument ends up in the merged source of its top-level files.
+/// Returns `{ root, line }` for the first top-level file, in path order, whose merged source
+/// `#define`s or declares the identifier, where `line` is 0-indexed into that merged source,
+/// or `null` if none do.
+pub(crate) struct MergedDefinitionCommand<'a> {
+    pub(crate) server: &'a MinecraftShaderLanguageServer,
+    pub(crate) files: &'a dyn FileProvider,
+}
 
-        for node in nodes {
-            let graph = self.graph.borrow();
-            let path = graph.get_node(node.0);
+impl<'a> Invokeable for MergedDefinitionCommand<'a> {
+    fn run_command(&self, root: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+        let (line, character) = match arguments.get(1) {
+            Some(Value::Object(position)) => match (position.get("line").and_then(Value::as_u64), position.get("character").and_then(Value::as_u64)) {
+                (Some(line), Some(character)) => (line as usize, character as usize),
+                _ => return Err(format_err!("expected a position argument of the form {{ line, character }}")),
+            },
+            _ => return Err(format_err!("expected a position argument of the form {{ line, character }}")),
+        };
 
-            if sources.contains_key(&path) {
-                continue;
+        let (source, _) = crate::read_source_from(self.files, &path)?;
+        let token = match symbols::token_at_position(&source, line, character) {
+            Some(token) => token,
+            None => return Ok(Value::Null),
+        };
+
+        let merger = VirtualMergedDocument{
+            server: self.server,
+            files: self.files,
+        };
+        let mut roots: Vec<PathBuf> = match self.server.get_file_toplevel_ancestors(&path)? {
+            Some(ancestors) => ancestors.into_iter().map(|node| self.server.graph.borrow().get_node(node)).collect(),
+            None => vec![path],
+        };
+        roots.sort();
+
+        for top_level in roots {
+            let merged = merger.merged_source(root, &top_level)?;
+            let definition = symbols::find_defines(&merged, &token.text).into_iter()
+                .chain(symbols::find_declarations(&merged, &token.text))
+                .min();
+            if let Some(line) = definition {
+                return Ok(serde_json::json!({
+                    "root": top_level,
+                    "line": line,
+                }));
             }
+        }
 
-            let source = match fs::read_to_string(&path) {
-                Ok(s) => s,
-                Err(e) => return Err(format_err!("error reading {:?}: {}", path, e))
-            };
-            let source = crate::RE_CRLF.replace_all(&source, "\n").to_string();
-            sources.insert(path.clone(), source);
+        Ok(Value::Null)
+    }
+}
+
+/// Returns the DFS traversal that drives merging, starting from the file given as the first
+/// argument, as `{ order: [{ file, parent }], cycle }`. If an include cycle is found, `order`
+/// holds the traversal up until that point and `cycle` holds its details, else it is `null`.
+pub struct DfsDumpCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for DfsDumpCommand {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
+        };
+
+        let graph = self.graph.borrow();
+        let mut order = Vec::new();
+        let mut cycle = Value::Null;
+
+        for step in dfs::Dfs::new(&graph, node) {
+            match step {
+                Ok((child, parent)) => order.push(serde_json::json!({
+                    "file": graph.get_node(child),
+                    "parent": parent.map(|parent| graph.get_node(parent)),
+                })),
+                Err(e) => {
+                    cycle = serde_json::json!({
+                        "message": e.to_string(),
+                        "files": e.files(),
+                    });
+                    break;
+                }
+            }
         }
 
-        Ok(sources)
+        Ok(serde_json::json!({
+            "order": order,
+            "cycle": cycle,
+        }))
     }
 }
 
-impl Invokeable for VirtualMergedDocument {
+/// Returns the include tree of a file as an indented outline, with every file on its own line
+/// under the file that includes it along with the 1-indexed line it is included on, such as
+/// `shaders/final.fsh` followed by `  shaders/common.glsl (line 3)`. Paths are relative to the
+/// workspace root. A cycle ends the outline with a line describing it.
+pub struct TreeOutlineCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for TreeOutlineCommand {
     fn run_command(&self, root: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
-        let path = PathBuf::from_json(arguments.get(0).unwrap())?;
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
 
-        let file_ancestors = match self.get_file_toplevel_ancestors(&path) {
-            Ok(opt) => match opt {
-                Some(ancestors) => ancestors,
-                None => vec![],
-            },
-            Err(e) => return Err(e),
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
         };
-        
-        //eprintln!("ancestors for {}:\n\t{:?}", path, file_ancestors.iter().map(|e| self.graph.borrow().graph.node_weight(*e).unwrap().clone()).collect::<Vec<String>>());
 
-        // the set of all filepath->content. TODO: change to Url?
-        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
+        let graph = self.graph.borrow();
+        let display = |node: NodeIndex| {
+            let path = graph.get_node(node);
+            match path.strip_prefix(root) {
+                Ok(relative) => relative.to_path_buf().to_slash_lossy(),
+                Err(_) => path.to_slash_lossy(),
+            }
+        };
 
-        // if we are a top-level file (this has to be one of the set defined by Optifine, right?)
-        if file_ancestors.is_empty() {
-            // gather the list of all descendants 
-            let root = self.graph.borrow_mut().find_node(&path).unwrap();
-            let tree = match self.get_dfs_for_node(root) {
-                Ok(tree) => tree,
-                Err(e) => return Err(e.into()),
+        let mut outline = String::new();
+        // the files from the root down to the current one
+        let mut ancestry: Vec<NodeIndex> = Vec::new();
+
+        for step in dfs::Dfs::new(&graph, node) {
+            match step {
+                Ok((child, Some(parent))) => {
+                    while !ancestry.is_empty() && ancestry.last() != Some(&parent) {
+                        ancestry.pop();
+                    }
+                    outline.push_str(&format!("{}{} (line {})\n", "  ".repeat(ancestry.len()), display(child), graph.get_edge_meta(parent, child).line + 1));
+                    ancestry.push(child);
+                },
+                Ok((child, None)) => {
+                    outline.push_str(&format!("{}\n", display(child)));
+                    ancestry.push(child);
+                },
+                Err(e) => {
+                    outline.push_str(&format!("{}\n", e));
+                    break;
+                }
+            }
+        }
+
+        Ok(Value::String(outline))
+    }
+}
+
+pub struct WhyIncludedCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for WhyIncludedCommand {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
+        };
+
+        let mut includers = self.graph.borrow().parent_node_meta(node);
+        includers.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.line.cmp(&b.1.line)));
+
+        Ok(Value::Array(includers.into_iter().map(|(file, position)| {
+            serde_json::json!({
+                "file": file,
+                "line": position.line,
+            })
+        }).collect()))
+    }
+}
+
+/// Returns every include that `file` is pulled in through, directly or by the files that include
+/// it, as `[{ file, includes, line }]`, where `file` includes `includes` on 0-indexed `line`.
+/// Goes breadth first from the direct includers of `file` up to the top-level files.
+pub struct AncestorsCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for AncestorsCommand {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
+        };
+
+        let graph = self.graph.borrow();
+        Ok(Value::Array(graph.collect_ancestors(node).into_iter().map(|(includer, included, position)| {
+            serde_json::json!({
+                "file": graph.get_node(includer),
+                "includes": graph.get_node(included),
+                "line": position.line,
+            })
+        }).collect()))
+    }
+}
+
+/// Returns the top-level files of every program that `file` is part of, as `[file]`, so that the
+/// diagnostics of a shared file can be grouped by the programs they break.
+pub struct AffectedProgramsCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for AffectedProgramsCommand {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
+        };
+
+        Ok(serde_json::to_value(self.graph.borrow().affected_programs(node))?)
+    }
+}
+
+/// Returns the includes of `file` that look unused, as `[{ file, line }]`, where `file` is the
+/// included file and `line` the 0-indexed line of the include. An include is unused if nothing
+/// that it or the files it includes `#define` or declare appears in the text of `file`. Includes
+/// of files that declare nothing, such as snippets of a function body, are never reported.
+pub struct UnusedIncludesCommand<'a> {
+    pub graph: Rc<RefCell<CachedStableGraph>>,
+    pub files: &'a dyn FileProvider,
+}
+
+impl<'a> UnusedIncludesCommand<'a> {
+    /// Returns the names defined or declared in the file `node` and everything it includes.
+    fn provided_names(&self, graph: &CachedStableGraph, node: NodeIndex) -> HashSet<String> {
+        let mut names = HashSet::new();
+        for step in dfs::Dfs::new(graph, node) {
+            // the names up to a cycle are still provided
+            let (file, _) = match step {
+                Ok(step) => step,
+                Err(_) => break,
             };
+            if let Ok((source, _)) = crate::read_source_from(self.files, &graph.get_node(file)) {
+                names.extend(symbols::defined_names(&source));
+                names.extend(symbols::declared_names(&source));
+            }
+        }
+        names.retain(|name| !symbols::is_reserved(name));
+        names
+    }
+}
 
-            let sources = match self.load_sources(&tree) {
-                Ok(s) => s,
-                Err(e) => return Err(e)
+impl<'a> Invokeable for UnusedIncludesCommand<'a> {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(arg) => PathBuf::from_json(arg)?,
+            None => return Err(format_err!("expected a file path argument")),
+        };
+
+        let node = match self.graph.borrow_mut().find_node(&path) {
+            Some(n) => n,
+            None => return Err(format_err!("node not found {:?}", path)),
+        };
+
+        let (source, _) = crate::read_source_from(self.files, &path)?;
+        // the paths of the includes themselves don't count as using anything
+        let source: String = source.lines()
+            .filter(|line| !crate::RE_INCLUDE.is_match(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let graph = self.graph.borrow();
+        let unused: Vec<Value> = graph.child_edges(node).into_iter()
+            .filter(|(child, _)| {
+                let names = self.provided_names(&graph, *child);
+                !names.is_empty() && names.iter().all(|name| symbols::find_token_occurrences(&source, name).is_empty())
+            })
+            .map(|(child, position)| serde_json::json!({
+                "file": graph.get_node(child),
+                "line": position.line,
+            }))
+            .collect();
+
+        Ok(Value::Array(unused))
+    }
+}
+
+/// Returns every file that isn't included by any other file, grouped by the shader stage of its
+/// extension as `{ fragment: [file], vertex: [file], ..., unrecognized: [file] }`. The files under
+/// `unrecognized` have an extension that isn't one of a shader stage, such as an unused `.glsl` file.
+pub struct ListRootsCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl Invokeable for ListRootsCommand {
+    fn run_command(&self, _: &PathBuf, _: Vec<Value>) -> Result<Value> {
+        let graph = self.graph.borrow();
+
+        let mut roots: HashMap<Option<TreeType>, Vec<PathBuf>> = HashMap::new();
+        for node in graph.graph.node_indices() {
+            if !graph.parent_node_indexes(node).is_empty() {
+                continue;
+            }
+            let path = graph.get_node(node);
+            let tree_type = path.extension().and_then(TreeType::from_extension);
+            roots.entry(tree_type).or_default().push(path);
+        }
+
+        let mut result = serde_json::Map::new();
+        for (tree_type, mut files) in roots {
+            files.sort();
+            let key = match tree_type {
+                Some(tree_type) => serde_json::to_value(tree_type)?.as_str().unwrap().to_string(),
+                None => "unrecognized".to_string(),
             };
-            all_sources.extend(sources);
+            result.insert(key, serde_json::to_value(files)?);
+        }
 
-            let graph = self.graph.borrow();
-            let view = merge_views::generate_merge_list(&tree, &all_sources, &graph);
-            return Ok(serde_json::value::Value::String(view));
+        Ok(Value::Object(result))
+    }
+}
+
+/// Returns the size of the include graph as `{ nodes, edges, roots, maxDepth }`, where `maxDepth`
+/// is the number of includes in the longest chain down from a top-level file. Includes that
+/// close a cycle aren't followed.
+pub struct GraphStatsCommand {
+    pub graph: Rc<RefCell<CachedStableGraph>>
+}
+
+impl GraphStatsCommand {
+    /// Returns the number of includes in the longest chain down from `node`, memoized in `depths`.
+    fn depth(graph: &CachedStableGraph, node: NodeIndex, path: &mut HashSet<NodeIndex>, depths: &mut HashMap<NodeIndex, usize>) -> usize {
+        if let Some(depth) = depths.get(&node) {
+            return *depth;
         }
-        return Err(format_err!("{:?} is not a top-level file aka has ancestors", path.strip_prefix(root).unwrap()))
+
+        path.insert(node);
+        let children: Vec<NodeIndex> = graph.child_node_indexes(node).into_iter()
+            .filter(|child| !path.contains(child))
+            .collect();
+        let depth = children.into_iter()
+            .map(|child| 1 + GraphStatsCommand::depth(graph, child, path, depths))
+            .max()
+            .unwrap_or(0);
+        path.remove(&node);
+
+        depths.insert(node, depth);
+        depth
+    }
+}
+
+impl Invokeable for GraphStatsCommand {
+    fn run_command(&self, _: &PathBuf, _: Vec<Value>) -> Result<Value> {
+        let graph = self.graph.borrow();
+
+        let roots: Vec<NodeIndex> = graph.graph.node_indices()
+            .filter(|node| graph.parent_node_indexes(*node).is_empty())
+            .collect();
+
+        let mut depths = HashMap::new();
+        let max_depth = roots.iter()
+            .map(|root| GraphStatsCommand::depth(&graph, *root, &mut HashSet::new(), &mut depths))
+            .max()
+            .unwrap_or(0);
+
+        Ok(serde_json::json!({
+            "nodes": graph.graph.node_count(),
+            "edges": graph.graph.edge_count(),
+            "roots": roots.len(),
+            "maxDepth": max_depth,
+        }))
     }
-}
\ No newline at end of file
+}
+
+/// Returns the vendor, renderer and versions that shaders are validated against, so that users
+/// can check that they match the environment their shaderpack targets. Unlike the other
+/// commands it is created for each invocation, as the validator can be swapped out.
+pub struct GlInfoCommand<'a> {
+    pub validator: &'a dyn ShaderValidator,
+}
+
+impl<'a> Invokeable for GlInfoCommand<'a> {
+    fn run_command(&self, _: &PathBuf, _: Vec<Value>) -> Result<Value> {
+        Ok(serde_json::to_value(self.validator.info())?)
+    }
+}
+
+/// Validates a snippet of source as a stage, taking `{ source, stage }` and returning the
+/// diagnostics of the snippet, for trying out minimal repros. The snippet is merged as a file of
+/// its own, so it isn't added to the graph or written to disk. Like `glInfo` it is created for
+/// each invocation, as it validates with the server's current validator.
+// the server itself isn't public, so neither is a command holding it
+pub(crate) struct ValidateSnippetCommand<'a> {
+    pub(crate) server: &'a MinecraftShaderLanguageServer,
+}
+
+impl<'a> Invokeable for ValidateSnippetCommand<'a> {
+    fn run_command(&self, _: &PathBuf, arguments: Vec<Value>) -> Result<Value> {
+        let args = match arguments.get(0) {
+            Some(Value::Object(args)) => args,
+            _ => return Err(format_err!("expected an object argument of the form {{ source, stage }}")),
+        };
+
+        let source = match args.get("source") {
+            Some(Value::String(source)) => source.clone(),
+            _ => return Err(format_err!("missing source argument")),
+        };
+        let stage: TreeType = match args.get("stage") {
+            Some(stage) => serde_json::from_value(stage.clone())
+                .map_err(|_| format_err!("unknown stage {}, expected one of fragment, vertex, geometry, compute, tessControl or tessEval", stage))?,
+            None => return Err(format_err!("missing stage argument")),
+        };
+
+        // diagnostics need an absolute file to be reported on, which doesn't have to exist. There
+        // may not be a workspace folder to put it in
+        let path = std::env::temp_dir().join("snippet");
+        let mut graph = CachedStableGraph::new();
+        let node = graph.add_node(&path);
+        let mut sources = HashMap::new();
+        sources.insert(path.clone(), source);
+        let view = merge_views::generate_merge_list(&[(node, None)], &sources, &graph, self.server.config.merge_dedup);
+
+        let diagnostics = match self.server.validate(stage, view) {
+            // the snippet isn't in the server's graph, so there is no include chain to point at
+            Some(stdout) => self.server.parse_validator_lines(&path, stdout.lines(), &[]),
+            None => HashMap::new(),
+        };
+
+        let diagnostics: Vec<_> = diagnostics.into_iter().flat_map(|(_, diagnostics)| diagnostics).collect();
+        Ok(serde_json::to_value(diagnostics)?)
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use log::LevelFilter;
+
+use serde::{Deserialize, Serialize};
+
+use rust_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::TreeType;
+
+/// Server-side view of the `mcglsl` configuration section, as sent by the client
+/// in `workspace/didChangeConfiguration` on top of any `.mcglslrc` of the shaderpack.
+/// Any missing keys fall back to their defaults.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Configuration {
+    /// Maps a file extension (without the leading `.`) to how a top-level file
+    /// with that extension should be handled when it isn't a known shader stage.
+    pub unrecognized_roots: HashMap<String, UnrecognizedRootBehaviour>,
+    /// Glob patterns, relative to the workspace root, of files and directories to leave out of the include graph.
+    pub ignore: Vec<String>,
+    /// Also leave out anything matched by the workspace root's `.gitignore`.
+    pub use_gitignore: bool,
+    /// Which validator to compile shaders with.
+    pub validator: ValidatorKind,
+    /// Replaces the known Optifine program names that top-level files are checked against.
+    pub program_names: Option<Vec<String>>,
+    /// Extra arguments that shaders of each stage are validated with.
+    pub validator_args: HashMap<TreeType, Vec<String>>,
+    /// Only merge each file into a tree once, dropping any further includes of it like Optifine does.
+    pub merge_dedup: bool,
+    /// Report the validator's warnings as errors.
+    pub warnings_as_errors: bool,
+    /// The most verbose level of messages that the server logs to stderr.
+    pub log_level: LogLevel,
+    /// How long, in milliseconds, validating a single program may take before it is given up on.
+    pub validate_timeout_ms: Option<u64>,
+    /// Glob patterns, relative to the workspace root, of files that are kept in the include graph but never validated.
+    pub exclude_lint: Vec<String>,
+    /// The least severe diagnostics that are published. Everything is published when unset.
+    pub min_severity: Option<MinSeverity>,
+    /// Link each `#include` to the file it includes.
+    pub document_links: bool,
+    /// Which of the top-level files that include a linted file are validated along with it.
+    pub lint_scope: LintScope,
+    /// How many files deep includes may be nested before a tree is no longer followed.
+    pub max_include_depth: usize,
+    /// How long, in milliseconds, after the last of a burst of saves of a file that it is linted. The
+    /// first save of the burst is linted straight away.
+    pub validate_debounce_ms: Option<u64>,
+    /// Replaces the pattern that validator output is parsed with, for validators with their own format.
+    pub diagnostic_regex: Option<String>,
+    /// Log the files that would be indexed instead of building the include graph.
+    pub index_dry_run: bool,
+    /// Directories, relative to the workspace root, that includes are looked up in when they don't
+    /// exist relative to the including file or its shaderpack, like a C compiler's `-I` directories.
+    pub include_paths: Vec<String>,
+    /// Which files are validated when they are opened.
+    pub lint_on_open: LintOnOpen,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            unrecognized_roots: HashMap::new(),
+            ignore: Vec::new(),
+            use_gitignore: false,
+            validator: ValidatorKind::default(),
+            program_names: None,
+            validator_args: HashMap::new(),
+            merge_dedup: false,
+            warnings_as_errors: false,
+            log_level: LogLevel::default(),
+            validate_timeout_ms: None,
+            exclude_lint: Vec::new(),
+            min_severity: None,
+            document_links: true,
+            lint_scope: LintScope::default(),
+            max_include_depth: 64,
+            validate_debounce_ms: None,
+            diagnostic_regex: None,
+            index_dry_run: false,
+            include_paths: Vec::new(),
+            lint_on_open: LintOnOpen::default(),
+        }
+    }
+}
+
+impl Configuration {
+    /// Unsets the settings that the selected validator doesn't support, returning why each was.
+    pub fn reject_unsupported(&mut self) -> Vec<String> {
+        let mut rejected = Vec::new();
+        // the driver compiles on the thread its context is current on, which can't be interrupted
+        if self.validator == ValidatorKind::OpenGL && self.validate_timeout_ms.take().is_some() {
+            rejected.push("mcglsl.validateTimeoutMs isn't supported by the opengl validator, as the driver can't be interrupted while compiling".to_string());
+        }
+        rejected
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ValidatorKind {
+    /// The OpenGL driver of the machine the server runs on.
+    #[serde(rename = "opengl")]
+    OpenGL,
+    /// glslang linked in as a library. Requires the server to be built with the `glslang-lib` feature.
+    #[serde(rename = "glslang-lib")]
+    GlslangLib,
+    /// Checks of the structure of the source that need neither a GPU nor a compiler, for headless machines.
+    #[serde(rename = "static")]
+    Static,
+}
+
+impl Default for ValidatorKind {
+    fn default() -> Self {
+        ValidatorKind::OpenGL
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        LogLevel::Info
+    }
+}
+
+impl LogLevel {
+    pub fn level_filter(&self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LintScope {
+    /// Only the most recently opened top-level file, for big shaderpacks where validating every program is slow.
+    #[serde(rename = "changed")]
+    Changed,
+    /// Every top-level file.
+    #[serde(rename = "all-ancestors")]
+    AllAncestors,
+}
+
+impl Default for LintScope {
+    fn default() -> Self {
+        LintScope::AllAncestors
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintOnOpen {
+    /// Every opened file, validating each top-level file that includes it.
+    Always,
+    /// Only top-level files, as opening a shared header would validate every program including it.
+    RootsOnly,
+    /// No files, leaving validation to saves.
+    Never,
+}
+
+impl Default for LintOnOpen {
+    fn default() -> Self {
+        LintOnOpen::Always
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MinSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl MinSeverity {
+    pub fn severity(&self) -> DiagnosticSeverity {
+        match self {
+            MinSeverity::Error => DiagnosticSeverity::Error,
+            MinSeverity::Warning => DiagnosticSeverity::Warning,
+            MinSeverity::Info => DiagnosticSeverity::Information,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnrecognizedRootBehaviour {
+    /// Skip validation silently.
+    Ignore,
+    /// Skip validation and report a warning on the file.
+    Warn,
+    /// Validate the file as one of the given stages.
+    Fragment,
+    Vertex,
+    Geometry,
+    Compute,
+    TessControl,
+    TessEval,
+}
+
+impl UnrecognizedRootBehaviour {
+    pub fn tree_type(&self) -> Option<TreeType> {
+        match self {
+            UnrecognizedRootBehaviour::Ignore | UnrecognizedRootBehaviour::Warn => None,
+            UnrecognizedRootBehaviour::Fragment => Some(TreeType::Fragment),
+            UnrecognizedRootBehaviour::Vertex => Some(TreeType::Vertex),
+            UnrecognizedRootBehaviour::Geometry => Some(TreeType::Geometry),
+            UnrecognizedRootBehaviour::Compute => Some(TreeType::Compute),
+            UnrecognizedRootBehaviour::TessControl => Some(TreeType::TessControl),
+            UnrecognizedRootBehaviour::TessEval => Some(TreeType::TessEval),
+        }
+    }
+}
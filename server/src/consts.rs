@@ -1,4 +1,40 @@
 pub static SOURCE: &str = "mc-glsl";
 
-#[allow(dead_code)]
-pub static INCLUDE_DIRECTIVE: &str = "#extension GL_GOOGLE_include_directive : require\n";
\ No newline at end of file
+// the source of diagnostics about resolving includes, as opposed to those from the validator
+pub static INCLUDES_SOURCE: &str = "mcglsl-includes";
+
+// the NVIDIA compiler's C-prefixed error codes aren't documented individually, so they
+// link to the GLSL specification instead
+pub static ERROR_CODE_DOCUMENTATION: &[(&str, &str)] = &[
+    ("C", "https://www.khronos.org/registry/OpenGL/specs/gl/GLSLangSpec.4.60.html"),
+];
+
+// linked from the `#extension GL_GOOGLE_include_directive` line, noting that includes are enabled
+pub static INCLUDE_EXTENSION_DOCUMENTATION: &str = "https://github.com/KhronosGroup/GLSL/blob/master/extensions/google/GL_GOOGLE_include_directive.txt";
+
+// tried in order for an include without an extension that doesn't exist as written
+pub static DEFAULT_INCLUDE_EXTENSIONS: &[&str] = &["glsl", "inc"];
+
+// how many of the most recently opened top-level files are remembered for `mcglsl.lintScope`
+pub static RECENT_ROOTS_LIMIT: usize = 16;
+
+// the base names of the programs Optifine loads. A trailing `*` also matches the name
+// followed by a number, such as composite1 or deferred15
+pub static PROGRAM_NAMES: &[&str] = &[
+    "gbuffers_basic", "gbuffers_textured", "gbuffers_textured_lit", "gbuffers_skybasic", "gbuffers_skytextured",
+    "gbuffers_clouds", "gbuffers_terrain", "gbuffers_terrain_solid", "gbuffers_terrain_cutout_mip",
+    "gbuffers_terrain_cutout", "gbuffers_damagedblock", "gbuffers_block", "gbuffers_beaconbeam", "gbuffers_item",
+    "gbuffers_entities", "gbuffers_entities_glowing", "gbuffers_armor_glint", "gbuffers_spidereyes", "gbuffers_hand",
+    "gbuffers_weather", "gbuffers_water", "gbuffers_hand_water", "gbuffers_line", "shadow", "shadow_solid",
+    "shadow_cutout", "shadowcomp*", "prepare*", "deferred*", "composite*", "final",
+];
+
+// the commands that the client runs by itself, being those standing in for the requests and
+// notifications rust_lsp doesn't route, and the lint of a debounced save
+pub static BACKGROUND_COMMANDS: &[&str] = &["semanticTokens", "foldingRanges", "documentColors", "colorPresentations", "prepareRename", "lintDueSave", "workspaceFoldersChanged"];
+
+pub static INCLUDE_DIRECTIVE: &str = "#extension GL_GOOGLE_include_directive : require\n";
+
+// the file in a `shaders` directory that a shaderpack can ship its own `mcglsl` settings in, as
+// JSON or TOML
+pub static PROJECT_CONFIG_FILE: &str = ".mcglslrc";
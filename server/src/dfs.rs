@@ -14,15 +14,23 @@ struct VisitCount {
 pub struct Dfs<'a> {
     stack: Vec<NodeIndex>,
     graph: &'a CachedStableGraph,
-    cycle: Vec<VisitCount>
+    cycle: Vec<VisitCount>,
+    max_depth: usize,
 }
 
 impl <'a> Dfs<'a> {
     pub fn new(graph: &'a CachedStableGraph, start: NodeIndex) -> Self {
+        Dfs::with_max_depth(graph, start, usize::MAX)
+    }
+
+    /// Like `new`, but stops with an error at the first file included more than `max_depth` files deep,
+    /// where the files that `start` includes are 1 deep.
+    pub fn with_max_depth(graph: &'a CachedStableGraph, start: NodeIndex, max_depth: usize) -> Self {
         Dfs {
             stack: vec![start],
             graph,
-            cycle: Vec::new()
+            cycle: Vec::new(),
+            max_depth,
         }
     }
 
@@ -37,13 +45,13 @@ impl <'a> Dfs<'a> {
         }
     }
 
-    fn check_for_cycle(&self, children: &[NodeIndex]) -> Result<(), error::CycleError> {
+    fn check_for_cycle(&self, children: &[NodeIndex]) -> Result<(), error::DfsError> {
         for prev in &self.cycle {
             for child in children {
                 if prev.node == *child {
                     let cycle_nodes: Vec<NodeIndex> = self.cycle.iter().map(|n| n.node).collect();
                     return Err(
-                        error::CycleError::new(&cycle_nodes, *child, self.graph)
+                        error::CycleError::new(&cycle_nodes, *child, self.graph).into()
                     );
                 }
             }
@@ -53,9 +61,9 @@ impl <'a> Dfs<'a> {
 }
 
 impl <'a> Iterator for Dfs<'a> {
-    type Item = Result<(NodeIndex, Option<NodeIndex>), error::CycleError>;
+    type Item = Result<(NodeIndex, Option<NodeIndex>), error::DfsError>;
 
-    fn next(&mut self) -> Option<Result<(NodeIndex, Option<NodeIndex>), error::CycleError>> {
+    fn next(&mut self) -> Option<Result<(NodeIndex, Option<NodeIndex>), error::DfsError>> {
         let parent = match self.cycle.last() {
             Some(p) => Some(p.node),
             None => None,
@@ -84,6 +92,12 @@ impl <'a> Iterator for Dfs<'a> {
                     Ok(_) => {}
                     Err(e) => return Some(Err(e)),
                 };
+
+                // the children are one deeper than the files on the path down to them, including the root
+                if self.cycle.len() > self.max_depth {
+                    let path_nodes: Vec<NodeIndex> = self.cycle.iter().map(|n| n.node).collect();
+                    return Some(Err(error::DepthError::new(&path_nodes, children[children.len() - 1], self.max_depth, self.graph).into()));
+                }
     
                 for child in children {
                     self.stack.push(child);
@@ -118,6 +132,11 @@ pub mod error {
             resolved_nodes.push(graph.get_node(current_node).clone());
             CycleError(resolved_nodes)
         }
+
+        /// The files making up the cycle, starting and ending with the same file.
+        pub fn files(&self) -> &[PathBuf] {
+            &self.0
+        }
     }
     
     impl Display for CycleError {
@@ -137,7 +156,7 @@ pub mod error {
             Diagnostic{
                 severity: Some(DiagnosticSeverity::Error),
                 range: Range::new(Position::new(0, 0), Position::new(0, 500)),
-                source: Some(consts::SOURCE.into()),
+                source: Some(consts::INCLUDES_SOURCE.into()),
                 message: self.into(),
                 code: None,
                 tags: None,
@@ -153,4 +172,94 @@ pub mod error {
             format!("{}", self)
         }
     }
+
+    /// Raised for a file that is included more deeply than the configured maximum.
+    #[derive(Debug)]
+    pub struct DepthError {
+        files: Vec<PathBuf>,
+        max_depth: usize,
+    }
+
+    impl StdError for DepthError {}
+
+    impl DepthError {
+        pub fn new(nodes: &[NodeIndex], current_node: NodeIndex, max_depth: usize, graph: &CachedStableGraph) -> Self {
+            let mut files: Vec<PathBuf> = nodes.iter().map(|i| graph.get_node(*i)).collect();
+            files.push(graph.get_node(current_node));
+            DepthError { files, max_depth }
+        }
+
+        /// The files from the root down to the one that is too deep.
+        pub fn files(&self) -> &[PathBuf] {
+            &self.files
+        }
+    }
+
+    impl Display for DepthError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Includes are nested more than {} deep, the limit set by mcglsl.maxIncludeDepth:\n{:?}", self.max_depth, self.files[0])?;
+            for p in &self.files[1..] {
+                write!(f, " includes\n{:?}", p)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Why a depth-first search of the graph stopped early.
+    #[derive(Debug)]
+    pub enum DfsError {
+        Cycle(CycleError),
+        TooDeep(DepthError),
+    }
+
+    impl StdError for DfsError {}
+
+    impl DfsError {
+        pub fn files(&self) -> &[PathBuf] {
+            match self {
+                DfsError::Cycle(e) => e.files(),
+                DfsError::TooDeep(e) => e.files(),
+            }
+        }
+    }
+
+    impl Display for DfsError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                DfsError::Cycle(e) => Display::fmt(e, f),
+                DfsError::TooDeep(e) => Display::fmt(e, f),
+            }
+        }
+    }
+
+    impl From<CycleError> for DfsError {
+        fn from(e: CycleError) -> Self {
+            DfsError::Cycle(e)
+        }
+    }
+
+    impl From<DepthError> for DfsError {
+        fn from(e: DepthError) -> Self {
+            DfsError::TooDeep(e)
+        }
+    }
+
+    impl From<DfsError> for Diagnostic {
+        fn from(e: DfsError) -> Self {
+            match e {
+                DfsError::Cycle(e) => e.into(),
+                DfsError::TooDeep(e) => Diagnostic {
+                    severity: Some(DiagnosticSeverity::Error),
+                    range: Range::new(Position::new(0, 0), Position::new(0, 500)),
+                    source: Some(consts::INCLUDES_SOURCE.into()),
+                    message: e.to_string(),
+                    code: None,
+                    tags: None,
+                    related_information: None,
+                    code_description: Option::None,
+                    data: Option::None,
+                },
+            }
+        }
+    }
 }
\ No newline at end of file
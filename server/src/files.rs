@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where the server reads shaders from when building the include graph, merging trees, checking
+/// the case of included paths and running the commands that read sources. The server reads from
+/// disk, while tests can give it files held in memory instead. Finding the files of a workspace
+/// when indexing it and resolving symlinks when keying the graph's nodes still go to disk.
+pub trait FileProvider {
+    /// Returns the contents of the file at `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Returns whether there is a file or directory at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Returns the names of the entries of the directory at `path`.
+    fn entries(&self, path: &Path) -> io::Result<Vec<String>>;
+}
+
+/// Reads files from disk.
+pub struct DiskFileProvider;
+
+impl FileProvider for DiskFileProvider {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn entries(&self, path: &Path) -> io::Result<Vec<String>> {
+        Ok(fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect())
+    }
+}
+
+/// Reads the documents open in the client from their unsaved contents, and every other file from
+/// `files`, for the commands to see the same sources as linting does.
+pub struct OpenDocuments<'a> {
+    pub documents: &'a HashMap<PathBuf, String>,
+    pub files: &'a dyn FileProvider,
+}
+
+impl<'a> FileProvider for OpenDocuments<'a> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.documents.get(path) {
+            Some(text) => Ok(text.clone().into_bytes()),
+            None => self.files.read(path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.documents.contains_key(path) || self.files.exists(path)
+    }
+
+    fn entries(&self, path: &Path) -> io::Result<Vec<String>> {
+        self.files.entries(path)
+    }
+}
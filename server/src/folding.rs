@@ -0,0 +1,73 @@
+use std::convert::TryFrom;
+
+use rust_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::RE_INCLUDE;
+
+fn folding_range(start: usize, end: usize, kind: Option<FoldingRangeKind>) -> FoldingRange {
+    FoldingRange {
+        start_line: u32::try_from(start).unwrap(),
+        start_character: None,
+        end_line: u32::try_from(end).unwrap(),
+        end_character: None,
+        kind,
+    }
+}
+
+/// Returns the folding ranges of `source`, being every run of two or more consecutive `#include`
+/// lines and every `{ ... }` block spanning multiple lines. Nested blocks fold independently.
+/// A block's range ends on the line before its closing brace so that the brace stays visible.
+pub fn folding_ranges(source: &str) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+
+    // start line of the current run of includes
+    let mut include_run: Option<usize> = None;
+    // lines of the currently unclosed opening braces
+    let mut open_braces: Vec<usize> = Vec::new();
+    let mut in_block_comment = false;
+
+    let lines: Vec<&str> = source.lines().collect();
+    for (n, line) in lines.iter().enumerate() {
+        if RE_INCLUDE.is_match(line) {
+            include_run.get_or_insert(n);
+        } else if let Some(start) = include_run.take() {
+            if n - 1 > start {
+                ranges.push(folding_range(start, n - 1, Some(FoldingRangeKind::Imports)));
+            }
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                },
+                '{' => open_braces.push(n),
+                '}' => if let Some(start) = open_braces.pop() {
+                    if n > start + 1 {
+                        ranges.push(folding_range(start, n - 1, None));
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(start) = include_run {
+        if lines.len() - 1 > start {
+            ranges.push(folding_range(start, lines.len() - 1, Some(FoldingRangeKind::Imports)));
+        }
+    }
+
+    ranges.sort_by_key(|r| (r.start_line, r.end_line));
+    ranges
+}
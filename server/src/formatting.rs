@@ -0,0 +1,175 @@
+use std::convert::TryFrom;
+
+use rust_lsp::lsp_types::{FormattingOptions, Position, Range, TextEdit};
+
+/// Returns the indentation level of every line of `source` by brace depth. Lines starting with
+/// a `}` are dedented by one and `#` directives are always at level 0. Lines that start inside
+/// a block comment are `None`, as their indentation is left as written.
+pub fn indent_levels(source: &str) -> Vec<Option<usize>> {
+    let mut levels = Vec::new();
+
+    let mut depth: usize = 0;
+    let mut in_block_comment = false;
+
+    // split rather than `lines` so that an empty last line, such as one a newline was just typed into, has a level too
+    for line in source.split('\n') {
+        let trimmed = line.trim_start();
+
+        if in_block_comment {
+            levels.push(None);
+        } else if trimmed.starts_with('#') {
+            levels.push(Some(0));
+            // braces in macros don't change the nesting of the code around them
+            continue;
+        } else if trimmed.starts_with('}') {
+            levels.push(Some(depth.saturating_sub(1)));
+        } else {
+            levels.push(Some(depth));
+        }
+
+        let mut in_string = false;
+        let mut chars = trimmed.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            if in_string {
+                if c == '"' {
+                    in_string = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                },
+                '"' => in_string = true,
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    levels
+}
+
+/// Returns the whitespace that a single level of indentation is made up of.
+pub fn indent_unit(options: &FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".into()
+    }
+}
+
+/// Returns the edit that replaces the indentation of `text`, line `line` of a document, with `wanted`, if it differs.
+fn reindent(text: &str, line: usize, wanted: String) -> Option<TextEdit> {
+    let text = text.trim_end_matches('\r');
+    let current = &text[..text.len() - text.trim_start().len()];
+    if current == wanted {
+        return None;
+    }
+
+    let line = u32::try_from(line).unwrap();
+    Some(TextEdit::new(
+        Range::new(Position::new(line, 0), Position::new(line, u32::try_from(current.len()).unwrap())),
+        wanted,
+    ))
+}
+
+/// Returns the edit that re-indents line `line` of `source` to match its brace depth, if it
+/// isn't already. Used for on-type formatting after a `}` or a newline is typed.
+pub fn indent_line(source: &str, line: usize, options: &FormattingOptions) -> Option<TextEdit> {
+    let level = (*indent_levels(source).get(line)?)?;
+    let text = source.split('\n').nth(line)?;
+
+    reindent(text, line, indent_unit(options).repeat(level))
+}
+
+/// Returns the edits that re-indent lines `start` to `end` (inclusive) of `source` by brace depth,
+/// taking the nesting from the lines above `start` into account. Blank lines are emptied.
+pub fn format_range(source: &str, start: usize, end: usize, options: &FormattingOptions) -> Vec<TextEdit> {
+    let unit = indent_unit(options);
+
+    source.split('\n')
+        .zip(indent_levels(source))
+        .enumerate()
+        .skip(start)
+        .take((end + 1).saturating_sub(start))
+        .filter_map(|(n, (text, level))| {
+            let level = level?;
+            if text.trim().is_empty() {
+                return reindent(text, n, String::new());
+            }
+            reindent(text, n, unit.repeat(level))
+        })
+        .collect()
+}
+
+/// Formats `source` by re-indenting every line by brace depth, moving `#` directives to column 0
+/// and collapsing runs of blank lines into one. Lines are otherwise kept as they are, so string
+/// literals and the order of `#include`s are untouched, as are lines inside block comments.
+pub fn format_document(source: &str, options: &FormattingOptions) -> String {
+    let unit = indent_unit(options);
+    let newline = if source.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut text: Vec<&str> = source.split('\n').collect();
+    // the empty piece after a trailing newline isn't a blank line of its own
+    let trailing_newline = text.len() > 1 && text.last() == Some(&"");
+    if trailing_newline {
+        text.pop();
+    }
+
+    let mut lines: Vec<String> = Vec::with_capacity(text.len());
+    let mut previous_blank = false;
+    for (line, level) in text.into_iter().zip(indent_levels(source)) {
+        let line = line.trim_end_matches('\r');
+        let level = match level {
+            Some(level) => level,
+            None => {
+                lines.push(line.to_string());
+                previous_blank = false;
+                continue;
+            }
+        };
+
+        let content = line.trim_start();
+        if content.is_empty() {
+            if !previous_blank {
+                lines.push(String::new());
+            }
+            previous_blank = true;
+            continue;
+        }
+        previous_blank = false;
+        lines.push(format!("{}{}", unit.repeat(level), content));
+    }
+
+    let mut formatted = lines.join(newline);
+    if trailing_newline {
+        formatted.push_str(newline);
+    }
+    formatted
+}
+
+/// Returns the edit that replaces the whole of `source` with its formatted version, if it changes.
+pub fn format_document_edit(source: &str, options: &FormattingOptions) -> Option<TextEdit> {
+    let formatted = format_document(source, options);
+    if formatted == source {
+        return None;
+    }
+
+    let last_line = source.split('\n').count() - 1;
+    let last_line_len = source.split('\n').last().unwrap().encode_utf16().count();
+    Some(TextEdit::new(
+        Range::new(Position::new(0, 0), Position::new(u32::try_from(last_line).unwrap(), u32::try_from(last_line_len).unwrap())),
+        formatted,
+    ))
+}
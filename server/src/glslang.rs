@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use regex::Regex;
+
+use lazy_static::lazy_static;
+
+use log::error;
+
+use crate::opengl::{self, ShaderValidator, ValidationError, ValidatorInfo};
+use crate::TreeType;
+
+lazy_static! {
+    static ref RE_GLSLANG_MESSAGE: Regex = Regex::new(r#"^(?P<filepath>.+):(?P<linenum>\d+): (?P<severity>error|warning): (?P<output>.+)$"#).unwrap();
+    static ref RE_VERSION_LINE: Regex = Regex::new(r#"(?m)^\s*#version .*$"#).unwrap();
+}
+
+// glslang only accepts file names in #line directives with this extension enabled
+const LINE_DIRECTIVE_EXTENSION: &str = "#extension GL_GOOGLE_cpp_style_line_directive : require";
+
+// how many compiles may be running on worker threads at once. A timed out compile keeps its worker
+// until it finishes, so without a limit shaders that never finish compiling would pile them up
+const MAX_WORKERS: usize = 4;
+
+/// Validates shaders with glslang through `shaderc` instead of the OpenGL driver, so that
+/// no GPU or driver is needed. Messages are rewritten into the same format that the driver
+/// reports them in, so they are parsed by the same code.
+pub struct GlslangLibValidator {
+    compiler: shaderc::Compiler,
+    stage_args: HashMap<TreeType, Vec<String>>,
+    // the worker threads of `validate_with_timeout` that are still running
+    workers: Arc<AtomicUsize>,
+}
+
+impl GlslangLibValidator {
+    pub fn new() -> Option<GlslangLibValidator> {
+        shaderc::Compiler::new().map(|compiler| GlslangLibValidator {
+            compiler,
+            stage_args: HashMap::new(),
+            workers: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+}
+
+/// Counts a worker thread as running for as long as it is held, even if the compile panics.
+struct WorkerGuard(Arc<AtomicUsize>);
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Enables the `#line` file name extension directly after the `#version` directive of `source`.
+fn with_line_directive_extension(source: &str) -> String {
+    match RE_VERSION_LINE.find(source) {
+        // #line N numbers the directive itself, keeping the following lines numbered as they were
+        Some(m) => format!("{}\n{}\n#line {}{}", &source[..m.end()], LINE_DIRECTIVE_EXTENSION,
+            source[..m.end()].lines().count(), &source[m.end()..]),
+        None => format!("{}\n#line 0\n{}", LINE_DIRECTIVE_EXTENSION, source),
+    }
+}
+
+/// Rewrites glslang's `file:line: error: message` messages into the `file(line) : error: message`
+/// format reported by the OpenGL driver. From GLSL 330 onwards glslang treats `#line N` as the
+/// number of the line *following* the directive, while the driver (and our merged views) treat it
+/// as the number of the directive itself, so those line numbers are shifted by one.
+fn rewrite_messages(messages: &str, version: Option<u32>) -> String {
+    let offset = match version {
+        Some(version) if version >= 330 => 1,
+        _ => 0,
+    };
+
+    messages.lines()
+        .filter_map(|line| RE_GLSLANG_MESSAGE.captures(line))
+        .map(|cap| {
+            let line = cap["linenum"].parse::<u32>().unwrap_or(0) + offset;
+            format!("{}({}) : {}: {}\n", &cap["filepath"], line, &cap["severity"], &cap["output"])
+        })
+        .collect()
+}
+
+fn shader_kind(tree_type: TreeType) -> shaderc::ShaderKind {
+    match tree_type {
+        TreeType::Fragment => shaderc::ShaderKind::Fragment,
+        TreeType::Vertex => shaderc::ShaderKind::Vertex,
+        TreeType::Geometry => shaderc::ShaderKind::Geometry,
+        TreeType::Compute => shaderc::ShaderKind::Compute,
+        TreeType::TessControl => shaderc::ShaderKind::TessControl,
+        TreeType::TessEval => shaderc::ShaderKind::TessEvaluation,
+    }
+}
+
+/// Compiles `source` with `compiler`, returning glslang's messages if there were any.
+fn compile(compiler: &shaderc::Compiler, source: &str, kind: shaderc::ShaderKind) -> Option<String> {
+    let mut options = match shaderc::CompileOptions::new() {
+        Some(options) => options,
+        None => {
+            error!("failed to create glslang compile options");
+            return None;
+        }
+    };
+    options.set_source_language(shaderc::SourceLanguage::GLSL);
+    options.set_target_env(shaderc::TargetEnv::OpenGL, shaderc::EnvVersion::OpenGL4_5 as u32);
+
+    match compiler.compile_into_spirv(source, kind, "0", "main", Some(&options)) {
+        Ok(artifact) if artifact.get_num_warnings() > 0 => Some(artifact.get_warning_messages()),
+        Ok(_) => None,
+        Err(shaderc::Error::CompilationError(_, messages)) => Some(messages),
+        Err(e) => {
+            error!("glslang failed to validate shader: {}", e);
+            None
+        }
+    }
+}
+
+impl GlslangLibValidator {
+    /// Applies the stage's arguments and the `#line` file name extension to `source`.
+    fn prepare_source(&self, tree_type: TreeType, source: String) -> String {
+        let source = match self.stage_args.get(&tree_type) {
+            Some(args) => opengl::apply_stage_args(&source, args),
+            None => source,
+        };
+        with_line_directive_extension(&source)
+    }
+}
+
+impl ShaderValidator for GlslangLibValidator {
+    fn validate(&self, tree_type: TreeType, source: String) -> Option<String> {
+        let version = crate::get_shader_version(&source);
+        let source = self.prepare_source(tree_type, source);
+        compile(&self.compiler, &source, shader_kind(tree_type)).map(|messages| rewrite_messages(&messages, version))
+    }
+
+    /// Compiles on a worker thread, which is left to finish in the background if it takes longer than
+    /// `timeout`. Gives up straight away while `MAX_WORKERS` earlier compiles are still running.
+    fn validate_with_timeout(&self, tree_type: TreeType, source: String, timeout: Duration) -> Result<Option<String>, ValidationError> {
+        if self.workers.fetch_add(1, Ordering::SeqCst) >= MAX_WORKERS {
+            self.workers.fetch_sub(1, Ordering::SeqCst);
+            return Err(ValidationError::Busy(MAX_WORKERS));
+        }
+        let guard = WorkerGuard(self.workers.clone());
+
+        let version = crate::get_shader_version(&source);
+        let source = self.prepare_source(tree_type, source);
+        let kind = shader_kind(tree_type);
+
+        let (sender, receiver) = mpsc::channel();
+        // the compiler can't be sent across threads, so the worker creates its own
+        thread::spawn(move || {
+            let _guard = guard;
+            let messages = match shaderc::Compiler::new() {
+                Some(compiler) => compile(&compiler, &source, kind),
+                None => {
+                    error!("failed to create a glslang compiler");
+                    None
+                }
+            };
+            sender.send(messages).unwrap_or(());
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(messages) => Ok(messages.map(|messages| rewrite_messages(&messages, version))),
+            Err(_) => Err(ValidationError::TimedOut(timeout)),
+        }
+    }
+
+    fn describe_invocation(&self, tree_type: TreeType) -> String {
+        let stage = match tree_type {
+            TreeType::Fragment => "frag",
+            TreeType::Vertex => "vert",
+            TreeType::Geometry => "geom",
+            TreeType::Compute => "comp",
+            TreeType::TessControl => "tesc",
+            TreeType::TessEval => "tese",
+        };
+        format!("glslang through shaderc, like glslangValidator --target-env opengl -S {}{}",
+            stage, opengl::describe_stage_args(self.stage_args.get(&tree_type)))
+    }
+
+    fn info(&self) -> ValidatorInfo {
+        ValidatorInfo {
+            vendor: "Khronos".into(),
+            renderer: "glslang (shaderc)".into(),
+            version: "OpenGL 4.5".into(),
+            glsl_version: "4.60".into(),
+        }
+    }
+
+    fn set_stage_args(&mut self, args: HashMap<TreeType, Vec<String>>) {
+        self.stage_args = args;
+    }
+}
@@ -2,8 +2,9 @@ use petgraph::stable_graph::StableDiGraph;
 use petgraph::stable_graph::NodeIndex;
 use petgraph::Direction;
 use petgraph::stable_graph::EdgeIndex;
+use petgraph::visit::EdgeRef;
 
-use std::{collections::{HashMap, HashSet}, path::PathBuf, str::FromStr};
+use std::{cell::RefCell, collections::{HashMap, HashSet, VecDeque}, fs, path::{Path, PathBuf}, str::FromStr};
 
 use super::IncludePosition;
 
@@ -14,10 +15,35 @@ pub struct CachedStableGraph {
     // StableDiGraph is used as it allows for String node values, essential for
     // generating the GraphViz DOT render.
     pub graph: StableDiGraph<String, IncludePosition>,
+    // Keyed by `node_key` of the path, so that each file has a single node however it is spelled.
     cache: HashMap<PathBuf, NodeIndex>,
     // Maps a node index to its abstracted string representation.
     // Mainly used as the graph is based on NodeIndex and 
     reverse_index: HashMap<NodeIndex, PathBuf>,
+    // The `mcglsl.includePaths` directory that each file included through one was found in,
+    // keyed like `cache`. Kept apart from the nodes as it is known before the file's node is added.
+    include_roots: HashMap<PathBuf, String>,
+    // The `node_key` of every path that the graph has been given, so that each spelling of a path
+    // is only canonicalized the first time it is seen. Forgotten when the graph is cleared.
+    keys: RefCell<HashMap<PathBuf, PathBuf>>,
+}
+
+/// Returns the key that `path` is cached under. Paths are canonicalized where they exist, which
+/// resolves symlinks and relative components. Files that don't exist (yet) have their directory
+/// canonicalized instead, falling back to the normalized path if that doesn't exist either.
+fn node_key(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+
+    let normalized = crate::normalize_path(path);
+    match (normalized.parent(), normalized.file_name()) {
+        (Some(parent), Some(name)) => match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(name),
+            Err(_) => normalized,
+        },
+        _ => normalized,
+    }
 }
 
 impl CachedStableGraph {
@@ -26,28 +52,64 @@ impl CachedStableGraph {
             graph: StableDiGraph::new(),
             cache: HashMap::new(),
             reverse_index: HashMap::new(),
+            include_roots: HashMap::new(),
+            keys: RefCell::new(HashMap::new()),
         }
     }
 
+    /// Returns the `node_key` of `path`, canonicalizing it only if it hasn't been seen before.
+    pub fn key(&self, path: &Path) -> PathBuf {
+        if let Some(key) = self.keys.borrow().get(path) {
+            return key.clone();
+        }
+        let key = node_key(path);
+        self.keys.borrow_mut().insert(path.to_path_buf(), key.clone());
+        key
+    }
+
     /// Returns the `NodeIndex` for a given graph node with the value of `name`
     /// and caches the result in the `HashMap`. Complexity is **O(1)** if the value
     /// is cached (which should always be the case), else **O(n)** where **n** is
     /// the number of node indices, as an exhaustive search must be done.
     pub fn find_node(&mut self, name: &PathBuf) -> Option<NodeIndex> {
-        match self.cache.get(name) {
+        let key = self.key(name);
+        match self.cache.get(&key) {
             Some(n) => Some(*n),
             None => {
                 // If the string is not in cache, O(n) search the graph (i know...) and then cache the NodeIndex
                 // for later
-                let n = self.graph.node_indices().find(|n| self.graph[*n] == name.to_str().unwrap().to_string());
+                let n = self.graph.node_indices().find(|n| self.key(&self.get_node(*n)) == key);
                 if let Some(n) = n {
-                    self.cache.insert(name.into(), n);
+                    self.cache.insert(key, n);
                 }
                 n
             }
         }
     }
 
+    /// Removes every node and edge from the graph along with the lookup caches.
+    pub fn clear(&mut self) {
+        self.graph.clear();
+        self.cache.clear();
+        self.reverse_index.clear();
+        self.include_roots.clear();
+        self.keys.borrow_mut().clear();
+    }
+
+    /// Records the `mcglsl.includePaths` directory that `name` was found in when it was included,
+    /// or that it wasn't found in one if `root` is `None`.
+    pub fn set_include_root(&mut self, name: &Path, root: Option<String>) {
+        match root {
+            Some(root) => self.include_roots.insert(self.key(name), root),
+            None => self.include_roots.remove(&self.key(name)),
+        };
+    }
+
+    /// Returns the `mcglsl.includePaths` directory that `name` was found in, if it was included through one.
+    pub fn include_root(&self, name: &Path) -> Option<&String> {
+        self.include_roots.get(&self.key(name))
+    }
+
     pub fn get_node(&self, node: NodeIndex) -> PathBuf {
         PathBuf::from_str(&self.graph[node]).unwrap()
     }
@@ -58,18 +120,23 @@ impl CachedStableGraph {
 
     #[allow(dead_code)]
     pub fn remove_node(&mut self, name: &PathBuf) {
-        let idx = self.cache.remove(name);
+        let idx = self.cache.remove(&self.key(name));
         if let Some(idx) = idx {
             self.graph.remove_node(idx);
+            self.reverse_index.remove(&idx);
         }
     }
 
+    /// Returns the node of `name`, adding one if there is none yet for the file. A file reached
+    /// through a differently spelled path, such as through a symlink, keeps the path it was
+    /// first added with.
     pub fn add_node(&mut self, name: &PathBuf) -> NodeIndex {
-        if let Some(idx) = self.cache.get(name) {
+        let key = self.key(name);
+        if let Some(idx) = self.cache.get(&key) {
             return *idx;
         }
         let idx = self.graph.add_node(name.to_str().unwrap().to_string());
-        self.cache.insert(name.clone(), idx);
+        self.cache.insert(key, idx);
         self.reverse_index.insert(idx, name.clone());
         idx
     }
@@ -78,8 +145,7 @@ impl CachedStableGraph {
         self.graph.add_edge(parent, child, meta)
     }
 
-    pub fn remove_edge(&mut self, parent: NodeIndex, child: NodeIndex) {
-        let edge = self.graph.find_edge(parent, child).unwrap();
+    pub fn remove_edge(&mut self, edge: EdgeIndex) {
         self.graph.remove_edge(edge);
     }
 
@@ -93,12 +159,42 @@ impl CachedStableGraph {
         self.graph.neighbors(node).map(|n| self.reverse_index.get(&n).unwrap().clone()).collect()
     }
 
+    /// Returns the file and include position of every include in `node`, once for each include of
+    /// a file that is included more than once.
     pub fn child_node_meta(&self, node: NodeIndex) -> Vec<(PathBuf, IncludePosition)> {
-        self.graph.neighbors(node).map(|n| {
-            let edge = self.graph.find_edge(node, n).unwrap();
-            let edge_meta = self.graph.edge_weight(edge).unwrap();
-            return (self.reverse_index.get(&n).unwrap().clone(), edge_meta.clone())
-        }).collect()
+        self.child_include_edges(node).into_iter()
+            .map(|(_, path, position)| (path, position))
+            .collect()
+    }
+
+    /// Like `child_node_meta`, along with the edge of each include.
+    pub fn child_include_edges(&self, node: NodeIndex) -> Vec<(EdgeIndex, PathBuf, IncludePosition)> {
+        self.graph.edges(node)
+            .map(|edge| (edge.id(), self.reverse_index.get(&edge.target()).unwrap().clone(), edge.weight().clone()))
+            .collect()
+    }
+
+    /// Returns the path of every file in the graph.
+    pub fn nodes(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.graph.node_indices().map(move |node| self.get_node(node))
+    }
+
+    /// Returns every include in the graph as `(includer, included, position)`.
+    pub fn edges(&self) -> impl Iterator<Item = (PathBuf, PathBuf, IncludePosition)> + '_ {
+        self.graph.edge_indices().map(move |edge| {
+            let (parent, child) = self.graph.edge_endpoints(edge).unwrap();
+            (self.get_node(parent), self.get_node(child), self.graph[edge].clone())
+        })
+    }
+
+    /// Returns the file and include position of every include in `node`, ordered by line. A file
+    /// that is included more than once is returned once for each include.
+    pub fn child_edges(&self, node: NodeIndex) -> Vec<(NodeIndex, IncludePosition)> {
+        let mut edges: Vec<(NodeIndex, IncludePosition)> = self.graph.edges(node)
+            .map(|edge| (edge.target(), edge.weight().clone()))
+            .collect();
+        edges.sort_by_key(|(_, position)| position.line);
+        edges
     }
 
     pub fn child_node_indexes(&self, node: NodeIndex) -> Vec<NodeIndex> {
@@ -110,6 +206,15 @@ impl CachedStableGraph {
         self.graph.neighbors_directed(node, Direction::Incoming).map(|n| self.reverse_index.get(&n).unwrap().clone()).collect()
     }
 
+    /// Returns the file and include position of every include edge pointing at `node`.
+    pub fn parent_node_meta(&self, node: NodeIndex) -> Vec<(PathBuf, IncludePosition)> {
+        self.graph.neighbors_directed(node, Direction::Incoming).map(|n| {
+            let edge = self.graph.find_edge(n, node).unwrap();
+            let edge_meta = self.graph.edge_weight(edge).unwrap();
+            (self.reverse_index.get(&n).unwrap().clone(), edge_meta.clone())
+        }).collect()
+    }
+
     pub fn parent_node_indexes(&self, node: NodeIndex) -> Vec<NodeIndex> {
         self.graph.neighbors_directed(node, Direction::Incoming).collect()
     }
@@ -119,6 +224,54 @@ impl CachedStableGraph {
         self.graph.edges(node).map(|e| e.weight().clone()).collect()
     }
 
+    /// Returns every include edge above `node` as `(includer, included, position)`, found by
+    /// walking the graph backwards breadth first, so that the direct includers of `node` come
+    /// first and the top-level files last. Each edge is only returned once, even in a cycle, and
+    /// the includers of each file are ordered by path and line.
+    pub fn collect_ancestors(&self, node: NodeIndex) -> Vec<(NodeIndex, NodeIndex, IncludePosition)> {
+        let mut ancestors = Vec::new();
+
+        let mut visited = HashSet::new();
+        visited.insert(node);
+        let mut queue = VecDeque::new();
+        queue.push_back(node);
+
+        while let Some(child) = queue.pop_front() {
+            let mut includers: Vec<(NodeIndex, IncludePosition)> = self.graph.edges_directed(child, Direction::Incoming)
+                .map(|edge| (edge.source(), edge.weight().clone()))
+                .collect();
+            // edges are iterated in no particular order
+            includers.sort_by(|a, b| self.get_node(a.0).cmp(&self.get_node(b.0)).then(a.1.line.cmp(&b.1.line)));
+
+            for (includer, position) in includers {
+                ancestors.push((includer, child, position));
+                if visited.insert(includer) {
+                    queue.push_back(includer);
+                }
+            }
+        }
+
+        ancestors
+    }
+
+    /// Returns the top-level files whose programs `node` is part of, sorted by path and without
+    /// duplicates, however many ways they include it. A top-level file is its own only program.
+    pub fn affected_programs(&self, node: NodeIndex) -> Vec<PathBuf> {
+        let ancestors = self.collect_ancestors(node);
+        if ancestors.is_empty() {
+            return vec![self.get_node(node)];
+        }
+
+        let mut programs: Vec<PathBuf> = ancestors.into_iter()
+            .map(|(includer, _, _)| includer)
+            .filter(|includer| self.parent_node_indexes(*includer).is_empty())
+            .map(|includer| self.get_node(includer))
+            .collect();
+        programs.sort();
+        programs.dedup();
+        programs
+    }
+
     pub fn collect_root_ancestors(&self, node: NodeIndex) -> Vec<NodeIndex> {
         let mut visited = HashSet::new();
         self.get_root_ancestors(node, node, &mut visited)
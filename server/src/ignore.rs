@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A list of glob patterns for files and directories that should be skipped when walking
+/// the workspace. Patterns are matched against paths relative to the workspace root using
+/// `/` separators. `*` and `?` match within a single path component and `**` matches any
+/// number of components. A pattern without a `/` matches a file or directory of that name anywhere.
+#[derive(Debug, Default, Clone)]
+pub struct IgnoreList {
+    patterns: Vec<String>,
+}
+
+impl IgnoreList {
+    pub fn new(patterns: &[String]) -> IgnoreList {
+        IgnoreList {
+            patterns: patterns.iter()
+                .map(|p| p.trim().trim_end_matches('/').to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Adds the patterns of the `.gitignore` file in `root`, if there is one. Negated
+    /// patterns aren't supported and are skipped.
+    pub fn add_gitignore(&mut self, root: &PathBuf) {
+        let contents = match fs::read_to_string(root.join(".gitignore")) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+
+        self.patterns.extend(contents.lines()
+            .map(|line| line.trim().trim_end_matches('/'))
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+            .map(|line| line.to_string()));
+    }
+
+    /// Returns whether `relative`, or any of the directories it is in, matches one of the patterns.
+    pub fn is_ignored(&self, relative: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+
+        let components: Vec<String> = relative.components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+
+        (1..=components.len()).any(|len| {
+            let prefix: Vec<&str> = components[..len].iter().map(|c| c.as_str()).collect();
+            self.patterns.iter().any(|pattern| pattern_matches(pattern, &prefix))
+        })
+    }
+}
+
+fn pattern_matches(pattern: &str, path: &[&str]) -> bool {
+    let anchored = pattern.trim_start_matches('/');
+    let segments: Vec<&str> = anchored.split('/').collect();
+
+    if !pattern.contains('/') {
+        return path.last().map_or(false, |name| segment_matches(pattern, name));
+    }
+
+    segments_match(&segments, path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => segment_matches(segment, name) && segments_match(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    wildcard_matches(&pattern, &name)
+}
+
+fn wildcard_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard_matches(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_matches(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && wildcard_matches(rest, &name[1..]),
+    }
+}
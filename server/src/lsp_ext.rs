@@ -13,4 +13,17 @@ pub struct StatusParams {
     pub status: String,
     pub message: Option<String>,
     pub icon: Option<String>,
-}
\ No newline at end of file
+}
+// sent once a debounced save's window has passed, for the client to run the `lintDueSave` command,
+// as the timer's thread can't lint on the server's
+pub enum SaveLintDue {}
+
+impl Notification for SaveLintDue {
+    type Params = SaveLintDueParams;
+    const METHOD: &'static str = "mc-glsl/saveLintDue";
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct SaveLintDueParams {
+    pub path: String,
+}
@@ -4,18 +4,24 @@ use rust_lsp::lsp_types::{*, notification::*};
 
 use petgraph::stable_graph::NodeIndex;
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use url_norm::FromUrl;
+use url_norm::{FromUrl, FromJSON};
 use walkdir::WalkDir;
 
-use std::{cell::RefCell, path::PathBuf, str::FromStr};
+use std::{cell::RefCell, path::{Component, Path, PathBuf}, str::FromStr};
 use std::collections::{HashMap, HashSet};
-use std::collections::hash_map::RandomState;
+use std::collections::hash_map::{DefaultHasher, RandomState};
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter, Debug};
-use std::io::{stdin, stdout, BufRead, BufReader};
+use std::io::{stdin, stdout};
 use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
 use std::fs;
+use std::ffi::OsStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::hash::{Hash, Hasher};
 use std::iter::{Extend, FromIterator};
 
 use path_slash::PathBufExt;
@@ -28,6 +34,8 @@ use regex::Regex;
 
 use lazy_static::lazy_static;
 
+use log::{debug, error, info, trace, warn};
+
 mod graph;
 mod commands;
 mod lsp_ext;
@@ -36,48 +44,53 @@ mod merge_views;
 mod consts;
 mod opengl;
 mod url_norm;
+mod configuration;
+mod symbols;
+mod preprocessor;
+mod builtins;
+mod ignore;
+mod semantic_tokens;
+mod folding;
+mod formatting;
+mod colors;
+mod logging;
+mod properties;
+mod static_validator;
+mod files;
+#[cfg(feature = "glslang-lib")]
+mod glslang;
 
 #[cfg(test)]
 mod test;
 
 lazy_static! {
-    static ref RE_DIAGNOSTIC: Regex = Regex::new(r#"^(?P<filepath>[^?<>*|"]+)\((?P<linenum>\d+)\) : (?P<severity>error|warning) [A-C]\d+: (?P<output>.+)"#).unwrap();
-    static ref RE_VERSION: Regex = Regex::new(r#"#version [\d]{3}"#).unwrap();
+    static ref RE_DIAGNOSTIC: Regex = Regex::new(r#"^(?P<filepath>[^?<>*|"]+)\((?P<linenum>\d+)\) : (?P<severity>error|warning)(?: (?P<code>[A-C]\d+))?: (?P<output>.+)"#).unwrap();
+    static ref RE_VERSION: Regex = Regex::new(r#"#version ([\d]{3})"#).unwrap();
     static ref RE_INCLUDE: Regex = Regex::new(r#"^(?:\s)*?(?:#include) "(.+)"\r?"#).unwrap();
+    static ref RE_MACRO_INCLUDE: Regex = Regex::new(r#"^\s*#include\s+([A-Za-z_]\w*)\s*(?://.*)?\r?$"#).unwrap();
     static ref RE_INCLUDE_EXTENSION: Regex = Regex::new(r#"#extension GL_GOOGLE_include_directive ?: ?require"#).unwrap();
-    pub static ref RE_CRLF: Regex = Regex::new(r#"\r\n"#).unwrap();
+    // a CR on its own ends a line for editors and the GLSL preprocessor alike, as does CRLF
+    pub static ref RE_LINE_ENDING: Regex = Regex::new(r#"\r\n?"#).unwrap();
 }
 
 fn main() {
+    logging::init();
+
     let stdin = stdin();
 
     let endpoint_output = LSPEndpoint::create_lsp_output_with_output_stream(stdout);
 
-    let cache_graph = graph::CachedStableGraph::new();
-
-    let mut langserver = MinecraftShaderLanguageServer {
-        endpoint: endpoint_output.clone(),
-        graph: Rc::new(RefCell::new(cache_graph)),
-        wait: WaitGroup::new(),
-        root: "".into(),
-        command_provider: None,
-        opengl_context: Rc::new(opengl::OpenGLContext::new())
+    // the graph and navigation still work without OpenGL, so a failure is reported once initialized instead
+    let (validator, validator_error): (Rc<dyn opengl::ShaderValidator>, _) = match opengl::OpenGLContext::new() {
+        Ok(context) => (Rc::new(context), None),
+        Err(e) => {
+            error!("failed to initialize OpenGL, falling back to static checks: {}", e);
+            (Rc::new(static_validator::StaticValidator), Some(e.to_string()))
+        }
     };
 
-    langserver.command_provider = Some(commands::CustomCommandProvider::new(vec![
-        (
-            "graphDot",
-            Box::new(commands::GraphDotCommand {
-                graph: Rc::clone(&langserver.graph),
-            }),
-        ),
-        (
-            "virtualMerge",
-            Box::new(commands::VirtualMergedDocument{
-                graph: Rc::clone(&langserver.graph)
-            })
-        )
-    ]));
+    let mut langserver = MinecraftShaderLanguageServer::with_validator(endpoint_output.clone(), validator);
+    langserver.validator_error = validator_error;
 
     LSPEndpoint::run_server_from_input(&mut stdin.lock(), endpoint_output, langserver);
 }
@@ -86,9 +99,44 @@ struct MinecraftShaderLanguageServer {
     endpoint: Endpoint,
     graph: Rc<RefCell<graph::CachedStableGraph>>,
     wait: WaitGroup,
-    root: PathBuf,
+    // the workspace folders of the client, or its root if it doesn't support them
+    roots: Vec<PathBuf>,
     command_provider: Option<commands::CustomCommandProvider>,
-    opengl_context: Rc<dyn opengl::ShaderValidator>
+    opengl_context: Rc<dyn opengl::ShaderValidator>,
+    config: configuration::Configuration,
+    // the files that diagnostics were last published for, keyed by the top-level file of their tree
+    published_files: HashMap<PathBuf, HashSet<Url>>,
+    // the `shaders` directory of every shaderpack found under root
+    pack_roots: Vec<PathBuf>,
+    // the contents of the documents open in the client, which may not have been saved yet
+    documents: HashMap<PathBuf, String>,
+    // the latest version the client has sent of each open document, echoed back with its diagnostics
+    // while it has no unsaved changes
+    document_versions: HashMap<PathBuf, i32>,
+    // the open documents that have been changed since they were opened or last saved
+    unsaved_documents: HashSet<PathBuf>,
+    merge_cache: RefCell<merge_views::MergeCache>,
+    // the hash of the include trees of each file linted when opened or saved, and the diagnostics that lint gave
+    last_lint: HashMap<PathBuf, (u64, HashMap<Url, Vec<Diagnostic>>)>,
+    // the top-level files most recently opened in the client, most recent first
+    recent_roots: Vec<PathBuf>,
+    // when each file was last saved while `mcglsl.validateDebounceMs` is set, and the saved files left to lint once it has passed since then
+    last_save_lint: HashMap<PathBuf, Instant>,
+    pending_save_lints: HashSet<PathBuf>,
+    // the threads waiting out the debounce window of a save, with when they are due, and the flag
+    // that has them give up on shutdown
+    save_lint_timers: Vec<(Instant, thread::JoinHandle<()>)>,
+    save_lint_timers_cancelled: Arc<(Mutex<bool>, Condvar)>,
+    // the compiled `mcglsl.diagnosticRegex`, replacing `RE_DIAGNOSTIC` when it is set and valid
+    diagnostic_regex: Option<Regex>,
+    // set once the client asks the server to shut down, after which nothing more is validated or published
+    shutting_down: bool,
+    // where files are read from, being the disk outside of tests
+    files: Rc<dyn files::FileProvider>,
+    // why OpenGL couldn't be initialized at startup, shown to the user once the client has initialized
+    validator_error: Option<String>,
+    // the settings of the shaderpack's `.mcglslrc`, which those of the client are applied on top of
+    project_config: serde_json::Map<String, Value>,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -110,11 +158,546 @@ impl Display for IncludePosition {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub enum TreeType {
-    Fragment, Vertex, Geometry, Compute
+    Fragment, Vertex, Geometry, Compute, TessControl, TessEval
+}
+
+impl TreeType {
+    /// Returns the shader stage of a top-level file with the extension `ext`, if it is a known one.
+    pub fn from_extension(ext: &OsStr) -> Option<TreeType> {
+        match ext.to_str()? {
+            "fsh" => Some(TreeType::Fragment),
+            "vsh" => Some(TreeType::Vertex),
+            "gsh" => Some(TreeType::Geometry),
+            "csh" => Some(TreeType::Compute),
+            "tcs" => Some(TreeType::TessControl),
+            "tes" => Some(TreeType::TessEval),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the GLSL version declared by the first `#version` directive in `source`, if any.
+pub fn get_shader_version(source: &str) -> Option<u32> {
+    RE_VERSION.captures(source)
+        .and_then(|cap| cap.get(1))
+        .and_then(|version| version.as_str().parse::<u32>().ok())
+}
+
+/// Compiles a `mcglsl.diagnosticRegex` pattern for parsing the output of validators that don't
+/// report in the format of the OpenGL driver. The pattern must have `linenum` and `output` groups,
+/// while `filepath`, `severity` and `code` are optional as they are for the default pattern.
+pub fn compile_diagnostic_regex(pattern: &str) -> Result<Regex> {
+    let regex = Regex::new(pattern)?;
+    for group in &["linenum", "output"] {
+        if !regex.capture_names().any(|name| name == Some(*group)) {
+            return Err(anyhow!("the pattern has no {} group", group));
+        }
+    }
+    Ok(regex)
+}
+
+/// Converts a file path as reported by the validator into a native path. The validator echoes
+/// back the escaped paths from our `#line` directives and may mix separators on Windows.
+fn normalize_validator_path(path: &str) -> PathBuf {
+    PathBuf::from_slash(path.replace("\\\\", "/").replace('\\', "/"))
+}
+
+fn severity_rank(severity: Option<DiagnosticSeverity>) -> u8 {
+    match severity {
+        Some(DiagnosticSeverity::Error) => 0,
+        Some(DiagnosticSeverity::Warning) => 1,
+        Some(DiagnosticSeverity::Information) => 2,
+        Some(DiagnosticSeverity::Hint) => 3,
+        None => 4,
+    }
+}
+
+/// Sorts the diagnostics of a single file by position, then severity and message, so that
+/// the order they are published in doesn't depend on validator output order.
+pub fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| {
+        (a.range.start.line, a.range.start.character, severity_rank(a.severity), &a.message)
+            .cmp(&(b.range.start.line, b.range.start.character, severity_rank(b.severity), &b.message))
+    });
+}
+
+/// Clamps the range of every diagnostic to positions that exist in the file it is reported on,
+/// as validator line numbers can point past the end of a file and ranges span to column 1000.
+pub fn clamp_diagnostics(diagnostics: &mut HashMap<Url, Vec<Diagnostic>>, sources: &HashMap<PathBuf, String>) {
+    for (url, file_diagnostics) in diagnostics.iter_mut() {
+        let source = match url.to_file_path().ok().and_then(|path| sources.get(&path)) {
+            Some(source) => source,
+            None => continue,
+        };
+        let line_lengths: Vec<u32> = source.lines()
+            .map(|line| u32::try_from(line.encode_utf16().count()).unwrap())
+            .collect();
+        let last_line = u32::try_from(line_lengths.len().saturating_sub(1)).unwrap();
+
+        let clamp = |position: Position| {
+            let line = std::cmp::min(position.line, last_line);
+            let length = line_lengths.get(line as usize).copied().unwrap_or(0);
+            Position::new(line, std::cmp::min(position.character, length))
+        };
+
+        for diagnostic in file_diagnostics {
+            diagnostic.range = Range::new(clamp(diagnostic.range.start), clamp(diagnostic.range.end));
+        }
+    }
+}
+
+/// Removes repeated diagnostics with the same range, severity and message for each file. These
+/// occur when a file included by several top-level programs is reported once per program.
+pub fn dedup_diagnostics(diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) {
+    for file_diagnostics in diagnostics.values_mut() {
+        let mut seen = HashSet::new();
+        file_diagnostics.retain(|d| seen.insert((
+            d.range.start.line, d.range.start.character, d.range.end.line, d.range.end.character,
+            severity_rank(d.severity), d.message.clone(),
+        )));
+    }
+}
+
+/// Removes the diagnostics that are less severe than `min_severity`. Diagnostics without a
+/// severity are treated as the least severe.
+pub fn filter_diagnostics_by_severity(diagnostics: &mut HashMap<Url, Vec<Diagnostic>>, min_severity: DiagnosticSeverity) {
+    for file_diagnostics in diagnostics.values_mut() {
+        file_diagnostics.retain(|d| severity_rank(d.severity) <= severity_rank(Some(min_severity)));
+    }
+}
+
+/// Returns the status bar message and icon summarising the error and warning counts of `diagnostics`.
+pub fn diagnostics_summary(diagnostics: &HashMap<Url, Vec<Diagnostic>>) -> (String, &'static str) {
+    let count = |severity| diagnostics.values().flatten().filter(|d| d.severity == Some(severity)).count();
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+
+    let errors = count(DiagnosticSeverity::Error);
+    let warnings = count(DiagnosticSeverity::Warning);
+
+    let icon = if errors > 0 {
+        "$(error)"
+    } else if warnings > 0 {
+        "$(warning)"
+    } else {
+        "$(check)"
+    };
+
+    (format!("{}, {}", plural(errors, "error"), plural(warnings, "warning")), icon)
+}
+
+/// Returns whether `stem` is one of `names`, where a name ending in `*` also matches itself followed by digits.
+fn is_program_name<S: AsRef<str>>(stem: &str, names: &[S]) -> bool {
+    names.iter().any(|name| match name.as_ref().strip_suffix('*') {
+        Some(prefix) => match stem.strip_prefix(prefix) {
+            Some(number) => number.chars().all(|c| c.is_ascii_digit()),
+            None => false,
+        },
+        None => name.as_ref() == stem,
+    })
+}
+
+/// Returns a link to documentation for the validator error `code`, if its class is known.
+fn error_code_description(code: &str) -> Option<CodeDescription> {
+    consts::ERROR_CODE_DOCUMENTATION.iter()
+        .find(|(prefix, _)| code.starts_with(prefix))
+        .and_then(|(_, url)| Url::parse(url).ok())
+        .map(|href| CodeDescription { href })
+}
+
+/// Resolves the `.` and `..` components of `path` without touching the file system, so that an
+/// include reached through `../` maps to the same graph node as when it is reached directly,
+/// even if the file doesn't exist. A `..` at the root of `path` is dropped.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                },
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {},
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Returns `path` with the first of the default include extensions that exists appended, if it
+/// has no extension and doesn't exist as written, so that `#include "lib/common"` resolves to
+/// `lib/common.glsl`. Otherwise `path` is returned as is and reported missing if it is.
+fn resolve_default_extension(files: &dyn files::FileProvider, path: PathBuf) -> PathBuf {
+    if path.extension().is_some() || files.exists(&path) {
+        return path;
+    }
+
+    consts::DEFAULT_INCLUDE_EXTENSIONS.iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|candidate| files.exists(candidate))
+        .unwrap_or(path)
+}
+
+/// The line endings of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    /// Detects the line endings of `source` from its first line break, being `Lf` if it has none.
+    pub fn detect(source: &str) -> LineEnding {
+        match source.find(|c| c == '\r' || c == '\n') {
+            Some(i) if source[i..].starts_with("\r\n") => LineEnding::Crlf,
+            Some(i) if source[i..].starts_with('\r') => LineEnding::Cr,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// Replaces every CRLF and lone CR line ending of `source` with LF. Only the line breaks change,
+/// so columns don't move and lines are numbered the way the editor numbers them.
+pub fn normalize_line_endings(source: &str) -> String {
+    RE_LINE_ENDING.replace_all(source, "\n").to_string()
+}
+
+/// Reads the shader at `path` from `files`, stripping a leading UTF-8 byte order mark. Files that
+/// aren't valid UTF-8 are decoded lossily, in which case the returned flag is set.
+pub fn read_source_from(files: &dyn files::FileProvider, path: &Path) -> std::io::Result<(String, bool)> {
+    let bytes = files.read(path)?;
+    let bytes = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(&bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(source) => Ok((source.to_string(), false)),
+        Err(_) => Ok((String::from_utf8_lossy(bytes).to_string(), true)),
+    }
+}
+
+/// Parses the contents of a `.mcglslrc`, which is JSON if it starts with `{` and TOML otherwise.
+fn parse_project_config(contents: &str) -> Result<Value> {
+    if contents.trim_start().starts_with('{') {
+        Ok(serde_json::from_str(contents)?)
+    } else {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+fn invalid_utf8_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        code: None,
+        severity: Some(DiagnosticSeverity::Warning),
+        source: Some(consts::SOURCE.into()),
+        message: "File is not valid UTF-8, invalid characters were replaced before validating".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+fn disabled_program_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        code: None,
+        severity: Some(DiagnosticSeverity::Information),
+        source: Some(consts::SOURCE.into()),
+        message: "This program is disabled in shaders.properties, so it is not validated".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+/// Returns a diagnostic of `message` spanning what `include` names, for problems with the include itself.
+fn include_diagnostic(include: &IncludePosition, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+    Diagnostic {
+        range: Range::new(
+            Position::new(u32::try_from(include.line).unwrap(), u32::try_from(include.start).unwrap()),
+            Position::new(u32::try_from(include.line).unwrap(), u32::try_from(include.end).unwrap()),
+        ),
+        code: None,
+        severity: Some(severity),
+        source: Some(consts::INCLUDES_SOURCE.into()),
+        message,
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+fn missing_include_diagnostic(path: &PathBuf, include: &IncludePosition) -> Diagnostic {
+    include_diagnostic(include, DiagnosticSeverity::Error, format!("Included file {:?} does not exist", path))
+}
+
+/// Returns the spelling in `files` of `path` if it only resolves by ignoring case, comparing each
+/// component below `root` against the entries of its directory. Such includes work on Windows
+/// and macOS but fail on case-sensitive file systems like Linux, where Optifine often runs.
+fn case_mismatch(files: &dyn files::FileProvider, root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+
+    let mut actual = root.to_path_buf();
+    let mut mismatched = false;
+    for component in relative.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy().to_string(),
+            other => {
+                actual.push(other);
+                continue;
+            }
+        };
+
+        let entries = files.entries(&actual).ok()?;
+
+        if entries.contains(&name) {
+            actual.push(name);
+        } else {
+            let entry = entries.into_iter().find(|entry| entry.to_lowercase() == name.to_lowercase())?;
+            actual.push(entry);
+            mismatched = true;
+        }
+    }
+
+    if mismatched {
+        Some(actual)
+    } else {
+        None
+    }
+}
+
+fn include_case_mismatch_diagnostic(path: &PathBuf, actual: &PathBuf, include: &IncludePosition) -> Diagnostic {
+    include_diagnostic(include, DiagnosticSeverity::Warning, format!("Included file {:?} is named {:?} on disk, which won't resolve on case-sensitive file systems", path, actual))
+}
+
+fn include_outside_pack_diagnostic(path: &PathBuf, include: &IncludePosition) -> Diagnostic {
+    include_diagnostic(include, DiagnosticSeverity::Warning, format!("Included file {:?} is outside of the shaderpack, so Optifine won't load it", path))
+}
+
+/// Returns the includes of `source` that name a macro rather than a quoted path, such as
+/// `#include SHADOW_PATH`. What they include is only known once the macro is expanded.
+pub fn find_macro_includes(source: &str) -> Vec<(String, IncludePosition)> {
+    source.lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let name = RE_MACRO_INCLUDE.captures(text)?.get(1)?;
+            Some((name.as_str().to_string(), IncludePosition { line, start: name.start(), end: name.end() }))
+        })
+        .collect()
+}
+
+fn macro_include_diagnostic(name: &str, include: &IncludePosition) -> Diagnostic {
+    include_diagnostic(include, DiagnosticSeverity::Information, format!("Includes of macros aren't resolved, so the file that {} names isn't checked along with this one", name))
+}
+
+fn duplicate_include_diagnostic(path: &PathBuf, first: &IncludePosition, include: &IncludePosition) -> Diagnostic {
+    include_diagnostic(include, DiagnosticSeverity::Information, format!("{:?} is already included on line {}", path, first.line + 1))
+}
+
+fn missing_version_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        code: None,
+        severity: Some(DiagnosticSeverity::Warning),
+        source: Some(consts::SOURCE.into()),
+        message: "No #version directive found. Optifine expects one in top-level shader files".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+/// Returns whether `source` includes other files without enabling `GL_GOOGLE_include_directive`,
+/// without which Optifine fails to load the includes at runtime.
+fn lacks_include_extension(source: &str) -> bool {
+    source.lines().any(|line| RE_INCLUDE.is_match(line)) && !RE_INCLUDE_EXTENSION.is_match(source)
+}
+
+fn missing_include_extension_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        code: None,
+        severity: Some(DiagnosticSeverity::Warning),
+        source: Some(consts::SOURCE.into()),
+        message: "This file uses #include without \"#extension GL_GOOGLE_include_directive : require\", which Optifine needs to load includes".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
+}
+
+/// Returns what is left of the line `text` once comments are removed, trimmed. `in_block_comment`
+/// carries whether a block comment is still open from one line to the next.
+fn strip_comments(text: &str, in_block_comment: &mut bool) -> String {
+    let mut code = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if *in_block_comment {
+            if c == '*' && chars.peek() == Some(&'/') {
+                chars.next();
+                *in_block_comment = false;
+            }
+            continue;
+        }
+        match c {
+            '/' if chars.peek() == Some(&'/') => break,
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                *in_block_comment = true;
+            },
+            _ => code.push(c),
+        }
+    }
+    code.trim().to_string()
+}
+
+/// Returns the file and range of every `#version` directive in the `merged` source that anything
+/// other than comments and blank lines comes before, such as one in an included file. `line_map`
+/// is the file and line that each line of `merged` came from, as returned by `merge_views::line_map`.
+pub fn misplaced_versions(merged: &str, line_map: &[Option<(PathBuf, usize)>]) -> Vec<(PathBuf, Range)> {
+    let mut misplaced = Vec::new();
+    let mut seen_code = false;
+    let mut in_block_comment = false;
+
+    for (text, location) in merged.lines().zip(line_map.iter().cloned()) {
+        // the #line directives inserted when merging aren't in any file
+        let (file, line) = match location {
+            Some(location) => location,
+            None => continue,
+        };
+
+        let code = strip_comments(text, &mut in_block_comment);
+        if code.is_empty() {
+            continue;
+        }
+        if seen_code && code.starts_with('#') && RE_VERSION.is_match(&code) {
+            let line = u32::try_from(line).unwrap();
+            let start = u32::try_from(text.len() - text.trim_start().len()).unwrap();
+            let end = u32::try_from(text.trim_end().len()).unwrap();
+            misplaced.push((file, Range::new(Position::new(line, start), Position::new(line, end))));
+        }
+        seen_code = true;
+    }
+
+    misplaced
+}
+
+fn misplaced_version_diagnostic(range: Range) -> Diagnostic {
+    Diagnostic {
+        range,
+        code: None,
+        severity: Some(DiagnosticSeverity::Error),
+        source: Some(consts::SOURCE.into()),
+        message: "#version must come before anything else except comments".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    }
 }
 
 impl MinecraftShaderLanguageServer {
+    /// Creates a server that validates shaders with `validator`, such as the OpenGL driver or,
+    /// in tests, one returning canned output so that `lint` can run without a GPU.
+    pub fn with_validator(endpoint: Endpoint, validator: Rc<dyn opengl::ShaderValidator>) -> MinecraftShaderLanguageServer {
+        let mut server = MinecraftShaderLanguageServer {
+            endpoint,
+            graph: Rc::new(RefCell::new(graph::CachedStableGraph::new())),
+            wait: WaitGroup::new(),
+            roots: Vec::new(),
+            command_provider: None,
+            opengl_context: validator,
+            config: configuration::Configuration::default(),
+            published_files: HashMap::new(),
+            pack_roots: Vec::new(),
+            documents: HashMap::new(),
+            document_versions: HashMap::new(),
+            unsaved_documents: HashSet::new(),
+            merge_cache: RefCell::new(merge_views::MergeCache::default()),
+            last_lint: HashMap::new(),
+            recent_roots: Vec::new(),
+            last_save_lint: HashMap::new(),
+            pending_save_lints: HashSet::new(),
+            save_lint_timers: Vec::new(),
+            save_lint_timers_cancelled: Arc::new((Mutex::new(false), Condvar::new())),
+            diagnostic_regex: None,
+            shutting_down: false,
+            files: Rc::new(files::DiskFileProvider),
+            validator_error: None,
+            project_config: serde_json::Map::new(),
+        };
+
+        server.command_provider = Some(commands::CustomCommandProvider::new(vec![
+            (
+                "graphDot",
+                Box::new(commands::GraphDotCommand {
+                    graph: Rc::clone(&server.graph),
+                }),
+            ),
+            (
+                "dfsDump",
+                Box::new(commands::DfsDumpCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "whyIncluded",
+                Box::new(commands::WhyIncludedCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "treeOutline",
+                Box::new(commands::TreeOutlineCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "ancestors",
+                Box::new(commands::AncestorsCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "affectedPrograms",
+                Box::new(commands::AffectedProgramsCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "listRoots",
+                Box::new(commands::ListRootsCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            ),
+            (
+                "graphStats",
+                Box::new(commands::GraphStatsCommand{
+                    graph: Rc::clone(&server.graph)
+                })
+            )
+        ]));
+
+        server
+    }
+
     pub fn error_not_available<DATA>(data: DATA) -> MethodError<DATA> {
         let msg = "Functionality not implemented.".to_string();
         MethodError::<DATA> {
@@ -124,11 +707,40 @@ impl MinecraftShaderLanguageServer {
         }
     }
 
-    pub fn gen_initial_graph(&self) {
-        eprintln!("root of project is {:?}", self.root);
+    pub fn gen_initial_graph(&mut self) {
+        info!("roots of project are {:?}", self.roots);
+
+        let files = self.find_index_files();
+
+        if self.config.index_dry_run {
+            self.report_index_dry_run(&files);
+            return;
+        }
+
+        // iterate all valid found files, search for includes, add a node into the graph for each
+        // file and add a file->includes KV into the map
+        for path in files {
+            self.add_file_and_includes_to_graph(&path);
+        }
+
+        info!("finished building project include graph");
+    }
+
+    /// Finds the shaderpacks under the roots, and returns every file in them that is indexed
+    /// into the graph, that is the shader files that aren't ignored.
+    fn find_index_files(&mut self) -> Vec<PathBuf> {
+        let roots = self.roots.clone();
+        let mut files: Vec<PathBuf> = Vec::new();
 
-        // filter directories and files not ending in any of the 3 extensions
-        WalkDir::new(&self.root).into_iter().filter_map(|entry| {
+        // every pack root has to be known before any includes are resolved against them
+        self.find_pack_roots();
+
+        for root in &roots {
+            let is_ignored = self.ignore_filter(root);
+
+            // filter directories, and files that are neither a known or configured top-level file
+            // nor one of the include extensions
+            files.extend(WalkDir::new(root).into_iter().filter_map(|entry| {
                 if entry.is_err() {
                     return None;
                 }
@@ -141,132 +753,446 @@ impl MinecraftShaderLanguageServer {
 
                 let ext = match path.extension() {
                     Some(e) => e,
-                None => return None,
+                    None => return None,
                 };
 
-                if ext != "vsh" && ext != "fsh" && ext != "glsl" && ext != "inc" {
+                let is_indexed = TreeType::from_extension(ext).is_some()
+                    || ext.to_str().map_or(false, |ext| {
+                        consts::DEFAULT_INCLUDE_EXTENSIONS.contains(&ext) || self.config.unrecognized_roots.contains_key(ext)
+                    });
+                if !is_indexed {
+                    return None;
+                }
+
+                if is_ignored(path) {
                     return None;
                 }
 
                 Some(entry.into_path())
-        }).for_each(|path| {
-            // iterate all valid found files, search for includes, add a node into the graph for each
-            // file and add a file->includes KV into the map
-            self.add_file_and_includes_to_graph(&path);
-        });
+            }));
+        }
 
-        eprintln!("finished building project include graph");
+        // a folder nested in another one is walked twice
+        files.sort();
+        files.dedup();
+
+        files
     }
 
-    fn add_file_and_includes_to_graph(&self, path: &PathBuf) {
-        let includes = self.find_includes(path);
+    /// Finds the `shaders` directories under the roots that aren't ignored.
+    fn find_pack_roots(&mut self) {
+        let roots = self.roots.clone();
+        self.pack_roots.clear();
+
+        for root in &roots {
+            let is_ignored = self.ignore_filter(root);
+            self.pack_roots.extend(WalkDir::new(root).into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_dir() && entry.file_name() == "shaders")
+                .filter(|entry| !is_ignored(entry.path()))
+                .map(|entry| entry.into_path()));
+        }
 
-        let idx = self.graph.borrow_mut().add_node(&path);
+        self.pack_roots.sort();
+        self.pack_roots.dedup();
 
-        //eprintln!("adding {:?} with {:?}", path, includes);
-        for include in includes {
-            self.add_include(include, idx);
+        info!("found shaderpacks at {:?}", self.pack_roots);
+    }
+
+    /// Returns a filter of whether a path under `root` is left out by `mcglsl.ignore`, or by the `.gitignore`
+    /// of `root` if `mcglsl.useGitignore` is on.
+    fn ignore_filter<'a>(&self, root: &'a Path) -> impl Fn(&Path) -> bool + 'a {
+        let mut ignore_list = ignore::IgnoreList::new(&self.config.ignore);
+        if self.config.use_gitignore {
+            ignore_list.add_gitignore(root);
+        }
+        move |path: &Path| match path.strip_prefix(root) {
+            Ok(relative) => ignore_list.is_ignored(relative),
+            Err(_) => false,
         }
     }
 
-    fn add_include(&self, include: (PathBuf, IncludePosition), node: NodeIndex) {
-        let child = self.graph.borrow_mut().add_node(&include.0);
-        self.graph.borrow_mut().add_edge(node, child, include.1);
+    /// Logs the shaderpacks and files that `gen_initial_graph` would index with `mcglsl.indexDryRun`
+    /// turned off, so that the `ignore` settings can be checked without building the graph.
+    fn report_index_dry_run(&self, files: &[PathBuf]) {
+        let mut report = format!("mcglsl.indexDryRun is on, so the include graph wasn't built.\nShaderpacks found: {:?}\n{} files would be indexed:", self.pack_roots, files.len());
+        for file in files {
+            report.push_str(&format!("\n{}", file.display()));
+        }
+        info!("{}", report);
+        self.log_message(MessageType::Info, report);
     }
 
-    pub fn find_includes(&self, file: &PathBuf) -> Vec<(PathBuf, IncludePosition)> {
-        let mut includes = Vec::default();
+    /// Returns whether `path` matches one of the `mcglsl.excludeLint` patterns, meaning that
+    /// neither it nor, if it is a top-level file, its tree is validated.
+    fn is_lint_excluded(&self, path: &Path) -> bool {
+        if self.config.exclude_lint.is_empty() {
+            return false;
+        }
+        match self.root_for(path).and_then(|root| path.strip_prefix(root).ok()) {
+            Some(relative) => ignore::IgnoreList::new(&self.config.exclude_lint).is_ignored(relative),
+            None => false,
+        }
+    }
 
-        let buf = BufReader::new(std::fs::File::open(file).unwrap());
-        buf.lines()
-            .enumerate()
-            .filter_map(|line| match line.1 {
-                Ok(t) => Some((line.0, t)),
-                Err(_e) => None,
-            })
-            .filter(|line| RE_INCLUDE.is_match(line.1.as_str()))
-            .for_each(|line| {
-                let cap = RE_INCLUDE
-                    .captures(line.1.as_str())
-                    .unwrap()
-                    .get(1)
-                    .unwrap();
+    /// Returns whether the top-level file `path` belongs to a program that the `shaders.properties`
+    /// of its shaderpack disables, in which case neither Optifine nor Iris load it.
+    fn is_program_disabled(&self, path: &PathBuf) -> bool {
+        let pack_root = self.pack_root_for(path);
+        properties::ShadersProperties::load(&pack_root).map_or(false, |properties| properties.is_disabled(&pack_root, path))
+    }
 
-                let start = cap.start();
-                let end = cap.end();
-                let mut path: String = cap.as_str().into();
+    /// Returns whether `path` is inside one of the shaderpacks found under the workspace folders.
+    /// Files elsewhere in a folder, such as in a mixed repository, are only indexed so that includes
+    /// of them resolve, as linting them as programs of their own reports errors that aren't there.
+    fn in_shaderpack(&self, path: &Path) -> bool {
+        self.pack_roots.iter().any(|pack_root| path.starts_with(pack_root))
+    }
 
-                // TODO: difference between / and not
-                let full_include = if path.starts_with('/') {
-                    path = path.strip_prefix('/').unwrap().to_string();
-                    self.root.join("shaders").join(PathBuf::from_slash(&path))
-                } else {
-                    file.parent().unwrap().join(PathBuf::from_slash(&path))
-                };
+    /// Returns the innermost workspace folder that `path` is in.
+    fn root_for(&self, path: &Path) -> Option<&PathBuf> {
+        self.roots.iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+    }
 
-                includes.push((
-                    full_include,
-                    IncludePosition {
-                        line: line.0,
-                        start,
-                        end,
-                    }
-                ));
-            });
+    /// Handles a change of the client's workspace folders, rebuilding the graph from the new set of folders.
+    fn did_change_workspace_folders(&mut self, params: DidChangeWorkspaceFoldersParams) {
+        for removed in params.event.removed {
+            let removed = PathBuf::from_url(removed.uri);
+            self.roots.retain(|root| *root != removed);
+        }
+        for added in params.event.added {
+            let added = PathBuf::from_url(added.uri);
+            if !self.roots.contains(&added) {
+                self.roots.push(added);
+            }
+        }
 
-        includes
+        self.rebuild_graph();
     }
 
-    fn update_includes(&self, file: &PathBuf) {
-        let includes = self.find_includes(file);
+    /// Handles the `workspaceFoldersChanged` command, taking the parameters of a
+    /// `workspace/didChangeWorkspaceFolders` notification. rust_lsp doesn't route that notification,
+    /// so change notifications aren't asked for and the client sends the changes as this command instead.
+    fn workspace_folders_changed_command(&mut self, arguments: Vec<Value>) -> Result<Value> {
+        let params: DidChangeWorkspaceFoldersParams = match arguments.into_iter().next() {
+            Some(params) => serde_json::from_value(params)?,
+            None => return Err(anyhow!("expected an argument of the form {{ event: {{ added, removed }} }}")),
+        };
+
+        self.did_change_workspace_folders(params);
+        Ok(Value::Null)
+    }
 
-        eprintln!("updating {:?} with {:?}", file, includes);
+    /// Throws away the current include graph and builds it again from the files on disk.
+    pub fn rebuild_graph(&mut self) {
+        self.set_status("loading", "Rebuilding dependency graph...", "$(loading~spin)");
 
-        let idx = match self.graph.borrow_mut().find_node(&file) {
-            None => {
+        self.graph.borrow_mut().clear();
+        self.merge_cache.borrow_mut().clear();
+        self.last_lint.clear();
+        self.gen_initial_graph();
+
+        self.log_message(MessageType::Info, format!("Rebuilt the include graph of {:?}", self.roots));
+        self.set_status("ready", "Dependency graph rebuilt", "$(check)");
+    }
+
+    fn add_file_and_includes_to_graph(&self, path: &PathBuf) {
+        let includes = match self.find_includes(path) {
+            Ok(includes) => includes,
+            Err(e) => {
+                error!("error finding includes for {:?}: {}", path, e);
                 return
-            },
-            Some(n) => n,
+            }
         };
 
-        let prev_children: HashSet<_, RandomState> = HashSet::from_iter(self.graph.borrow().child_node_meta(idx));
-        let new_children: HashSet<_, RandomState> = HashSet::from_iter(includes.iter().map(|e| e.clone()));
+        let idx = self.graph.borrow_mut().add_node(&path);
 
-        let to_be_added = new_children.difference(&prev_children);
-        let to_be_removed = prev_children.difference(&new_children);
+        //eprintln!("adding {:?} with {:?}", path, includes);
+        for include in includes {
+            self.add_include(include, idx);
+        }
+    }
 
-        eprintln!("removing:\n\t{:?}\nadding:\n\t{:?}", to_be_removed, to_be_added);
+    /// Returns the `shaders` directory of the shaderpack that `file` belongs to, that being the
+    /// nearest enclosing pack root, or `shaders` in its workspace folder if it isn't inside any known pack.
+    fn pack_root_for(&self, file: &PathBuf) -> PathBuf {
+        self.pack_roots.iter()
+            .filter(|pack_root| file.starts_with(pack_root))
+            .max_by_key(|pack_root| pack_root.components().count())
+            .cloned()
+            .unwrap_or_else(|| self.root_for(file).or_else(|| self.roots.first()).cloned().unwrap_or_default().join("shaders"))
+    }
 
-        for removal in to_be_removed {
-            let child = self.graph.borrow_mut().find_node(&removal.0).unwrap();
-            self.graph.borrow_mut().remove_edge(idx, child);
+    /// Returns the tooltip for a link from `document` to the file it includes, that being the
+    /// path of `include` relative to the shaderpack root of `document` if it is inside it, or
+    /// relative to the `mcglsl.includePaths` directory it was found in along with that directory.
+    fn include_tooltip(&self, document: &PathBuf, include: &PathBuf) -> String {
+        let include_root = self.graph.borrow().include_root(include).cloned();
+        if let (Some(dir), Some(root)) = (include_root, self.root_for(document)) {
+            if let Ok(relative) = include.strip_prefix(normalize_path(&root.join(PathBuf::from_slash(&dir)))) {
+                return format!("/{} in the include path {}", relative.to_path_buf().to_slash_lossy(), dir);
+            }
         }
 
-        for insertion in to_be_added {
-            self.add_include(includes.iter().find(|f| f.0 == *insertion.0).unwrap().clone(), idx);
+        match include.strip_prefix(self.pack_root_for(document)) {
+            Ok(relative) => format!("/{}", relative.to_path_buf().to_slash_lossy()),
+            Err(_) => include.to_slash_lossy(),
         }
     }
 
-    pub fn lint(&self, uri: &PathBuf) -> Result<HashMap<Url, Vec<Diagnostic>>> {
-        // get all top level ancestors of this file
-        let file_ancestors = match self.get_file_toplevel_ancestors(uri) {
-            Ok(opt) => match opt {
-                Some(ancestors) => ancestors,
-                None => vec![],
-            },
-            Err(e) => return Err(e),
-        };
-        
-        eprintln!("ancestors for {:?}:\n\t{:?}", uri, file_ancestors.iter().map(|e| PathBuf::from_str(&self.graph.borrow().graph.node_weight(*e).unwrap().clone()).unwrap()).collect::<Vec<PathBuf>>());
+    /// Returns the links of `document`, being one to each included file that still exists and one
+    /// from `GL_GOOGLE_include_directive` to its specification, noting that includes are enabled.
+    /// Includes of deleted files are left out, as they are reported as missing when linting.
+    /// There are none when `mcglsl.documentLinks` is off.
+    fn document_links(&self, document: &PathBuf) -> Vec<DocumentLink> {
+        if !self.config.document_links {
+            return vec![];
+        }
 
-        // the set of all filepath->content. TODO: change to Url?
-        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
-        // the set of filepath->list of diagnostics to report
-        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+        let node = match self.graph.borrow_mut().find_node(document) {
+            Some(n) => n,
+            None => return vec![],
+        };
 
-        // we want to backfill the diagnostics map with all linked sources 
-        let back_fill = |all_sources, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>| {
-            for (path, _) in all_sources {
+        let mut links: Vec<DocumentLink> = self
+            .graph
+            .borrow()
+            .child_node_indexes(node)
+            .into_iter()
+            .filter_map(|child| {
+                let graph = self.graph.borrow();
+                let value = graph.get_edge_meta(node, child);
+                let path = graph.get_node(child);
+                if !self.files.exists(&path) {
+                    return None;
+                }
+                let url = match Url::from_file_path(&path) {
+                    Ok(url) => url,
+                    Err(e) => {
+                        error!("error converting {:?} into url: {:?}", path, e);
+                        return None;
+                    }
+                };
+
+                Some(DocumentLink {
+                    range: Range::new(
+                        Position::new(
+                            u32::try_from(value.line).unwrap(),
+                            u32::try_from(value.start).unwrap()),
+                        Position::new(
+                            u32::try_from(value.line).unwrap(),
+                            u32::try_from(value.end).unwrap()),
+                    ),
+                    target: Some(url),
+                    tooltip: Some(self.include_tooltip(document, &path)),
+                    data: None,
+                })
+            }).collect();
+
+        if let Ok(source) = self.document_text(document) {
+            for (n, line) in source.lines().enumerate() {
+                if !RE_INCLUDE_EXTENSION.is_match(line) {
+                    continue;
+                }
+                let start = line.find("GL_GOOGLE_include_directive").unwrap();
+                let end = start + "GL_GOOGLE_include_directive".len();
+                links.push(DocumentLink {
+                    range: Range::new(
+                        Position::new(u32::try_from(n).unwrap(), u32::try_from(start).unwrap()),
+                        Position::new(u32::try_from(n).unwrap(), u32::try_from(end).unwrap()),
+                    ),
+                    target: Url::parse(consts::INCLUDE_EXTENSION_DOCUMENTATION).ok(),
+                    tooltip: Some("#include is enabled in this file".into()),
+                    data: None,
+                });
+            }
+        }
+
+        links
+    }
+
+    fn add_include(&self, include: (PathBuf, IncludePosition), node: NodeIndex) {
+        let child = self.graph.borrow_mut().add_node(&include.0);
+        self.graph.borrow_mut().add_edge(node, child, include.1);
+    }
+
+    pub fn find_includes(&self, file: &PathBuf) -> Result<Vec<(PathBuf, IncludePosition)>> {
+        let mut includes = Vec::default();
+
+        let source = match read_source_from(self.files.as_ref(), file) {
+            Ok((source, _)) => normalize_line_endings(&source),
+            Err(e) => return Err(anyhow!("error opening {:?}: {}", file, e)),
+        };
+        source.lines()
+            .enumerate()
+            .map(|line| (line.0, line.1.to_string()))
+            .inspect(|line| if RE_MACRO_INCLUDE.is_match(line.1.as_str()) {
+                debug!("not following the macro include on line {} of {:?}", line.0 + 1, file);
+            })
+            .filter(|line| RE_INCLUDE.is_match(line.1.as_str()))
+            .for_each(|line| {
+                let cap = RE_INCLUDE
+                    .captures(line.1.as_str())
+                    .unwrap()
+                    .get(1)
+                    .unwrap();
+
+                // a stray carriage return would otherwise end up in the path and never match the file on disk
+                let mut path: String = cap.as_str().trim_end_matches('\r').into();
+                let start = cap.start();
+                let end = start + path.len();
+
+                // TODO: difference between / and not
+                let full_include = if path.starts_with('/') {
+                    path = path.strip_prefix('/').unwrap().to_string();
+                    self.pack_root_for(file).join(PathBuf::from_slash(&path))
+                } else {
+                    file.parent().unwrap().join(PathBuf::from_slash(&path))
+                };
+
+                let full_include = resolve_default_extension(self.files.as_ref(), normalize_path(&full_include));
+                let (full_include, include_root) = if self.files.exists(&full_include) {
+                    (full_include, None)
+                } else {
+                    match self.resolve_from_include_paths(file, &path) {
+                        Some((candidate, dir)) => (candidate, Some(dir)),
+                        None => (full_include, None),
+                    }
+                };
+                self.graph.borrow_mut().set_include_root(&full_include, include_root);
+
+                includes.push((
+                    full_include,
+                    IncludePosition {
+                        line: line.0,
+                        start,
+                        end,
+                    }
+                ));
+            });
+
+        Ok(includes)
+    }
+
+    /// Returns the first file that `include`, with any leading `/` removed, resolves to in the
+    /// `mcglsl.includePaths` directories of the workspace folder of `file`, tried in order, for
+    /// includes that don't exist relative to the file or its shaderpack. The directory it was
+    /// found in is returned with it, as configured.
+    fn resolve_from_include_paths(&self, file: &Path, include: &str) -> Option<(PathBuf, String)> {
+        let root = self.root_for(file)?;
+        self.config.include_paths.iter().find_map(|dir| {
+            let candidate = resolve_default_extension(self.files.as_ref(), normalize_path(&root.join(PathBuf::from_slash(dir)).join(PathBuf::from_slash(include))));
+            if !self.files.exists(&candidate) {
+                return None;
+            }
+            debug!("resolved the include {:?} of {:?} against the include path {:?}", include, file, dir);
+            Some((candidate, dir.clone()))
+        })
+    }
+
+    /// Returns whether `path` is in one of the `mcglsl.includePaths` directories of the workspace
+    /// folder of `file`, comparing them once symlinks and `..` are resolved.
+    fn in_include_paths(&self, file: &Path, path: &Path) -> bool {
+        let root = match self.root_for(file) {
+            Some(root) => root,
+            None => return false,
+        };
+        let graph = self.graph.borrow();
+        let path = graph.key(path);
+        self.config.include_paths.iter()
+            .any(|dir| path.starts_with(graph.key(&root.join(PathBuf::from_slash(dir)))))
+    }
+
+    fn update_includes(&self, file: &PathBuf) {
+        let includes = match self.find_includes(file) {
+            Ok(includes) => includes,
+            Err(e) => {
+                error!("error finding includes for {:?}: {}", file, e);
+                return
+            }
+        };
+
+        debug!("updating {:?} with {:?}", file, includes);
+
+        let idx = match self.graph.borrow_mut().find_node(&file) {
+            None => {
+                return
+            },
+            Some(n) => n,
+        };
+
+        // a file can be included more than once, so each include is told apart by its position too
+        let prev_edges = self.graph.borrow().child_include_edges(idx);
+        let prev_children: HashSet<(PathBuf, IncludePosition), RandomState> = prev_edges.iter()
+            .map(|(_, path, position)| (path.clone(), position.clone()))
+            .collect();
+        let new_children: HashSet<(PathBuf, IncludePosition), RandomState> = HashSet::from_iter(includes.iter().cloned());
+
+        let to_be_removed: Vec<_> = prev_edges.into_iter()
+            .filter(|(_, path, position)| !new_children.contains(&(path.clone(), position.clone())))
+            .collect();
+        let to_be_added: Vec<_> = includes.into_iter()
+            .filter(|include| !prev_children.contains(include))
+            .collect();
+
+        debug!("removing:\n\t{:?}\nadding:\n\t{:?}", to_be_removed, to_be_added);
+
+        for (edge, _, _) in to_be_removed {
+            self.graph.borrow_mut().remove_edge(edge);
+        }
+
+        for insertion in to_be_added {
+            self.add_include(insertion, idx);
+        }
+    }
+
+    pub fn lint(&self, uri: &PathBuf) -> Result<HashMap<Url, Vec<Diagnostic>>> {
+        self.lint_with_defines(uri, &[])
+    }
+
+    /// Lints the trees that `uri` belongs to with the given `NAME` or `NAME=VALUE` defines
+    /// injected into each merged tree. If any defines are given, includes in provably
+    /// inactive `#ifdef` branches are excluded from the tree first.
+    pub fn lint_with_defines(&self, uri: &PathBuf, defines: &[String]) -> Result<HashMap<Url, Vec<Diagnostic>>> {
+        if self.is_lint_excluded(uri) {
+            // clears any diagnostics the file had from before it was excluded
+            let mut diagnostics = HashMap::new();
+            diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![]);
+            return Ok(diagnostics);
+        }
+
+        // a file created since the graph was built may be linted before it is indexed
+        if self.graph.borrow_mut().find_node(uri).is_none() {
+            self.add_file_and_includes_to_graph(uri);
+            // it couldn't be read, so there is nothing to validate
+            if self.graph.borrow_mut().find_node(uri).is_none() {
+                let mut diagnostics = HashMap::new();
+                diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![]);
+                return Ok(diagnostics);
+            }
+        }
+
+        // get all top level ancestors of this file
+        let file_ancestors = match self.get_file_toplevel_ancestors(uri) {
+            Ok(opt) => match opt {
+                Some(ancestors) => ancestors,
+                None => vec![],
+            },
+            Err(e) => return Err(e),
+        };
+        
+        debug!("ancestors for {:?}:\n\t{:?}", uri, file_ancestors.iter().map(|e| PathBuf::from_str(&self.graph.borrow().graph.node_weight(*e).unwrap().clone()).unwrap()).collect::<Vec<PathBuf>>());
+
+        // the set of all filepath->content. TODO: change to Url?
+        let mut all_sources: HashMap<PathBuf, String> = HashMap::new();
+        // the set of filepath->list of diagnostics to report
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+        // we want to backfill the diagnostics map with all linked sources 
+        let back_fill = |all_sources, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>| {
+            for (path, _) in all_sources {
                 diagnostics.entry(Url::from_file_path(path).unwrap()).or_default();
             }
         };
@@ -274,23 +1200,49 @@ impl MinecraftShaderLanguageServer {
         // if we are a top-level file (this has to be one of the set defined by Optifine, right?)
         if file_ancestors.is_empty() {
             // gather the list of all descendants 
-            let root = self.graph.borrow_mut().find_node(&uri).unwrap();
+            let root = match self.graph.borrow_mut().find_node(&uri) {
+                Some(root) => root,
+                None => {
+                    diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![]);
+                    return Ok(diagnostics);
+                }
+            };
             let tree = match self.get_dfs_for_node(root) {
                 Ok(tree) => tree,
                 Err(e) => {
+                    self.log_message(MessageType::Warning, e.to_string());
                     diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
                     return Ok(diagnostics);
                 }
             };
 
-            all_sources.extend( self.load_sources(&tree)?);
+            all_sources.extend( self.load_sources_with_diagnostics(&tree, &mut diagnostics)?);
 
-            let view = {
-            let graph = self.graph.borrow();
-                merge_views::generate_merge_list(&tree, &all_sources, &graph)
+            // a new, still empty file has nothing to validate, and nothing to warn about yet either
+            let root_path = self.graph.borrow().get_node(root);
+            if all_sources.get(&root_path).map_or(false, |source| source.trim().is_empty()) {
+                back_fill(&all_sources, &mut diagnostics);
+                return Ok(diagnostics);
+            }
+            if self.is_program_disabled(&root_path) {
+                diagnostics.entry(Url::from_file_path(&root_path).unwrap()).or_default().push(disabled_program_diagnostic());
+                back_fill(&all_sources, &mut diagnostics);
+                return Ok(diagnostics);
+            }
+
+            let tree = if defines.is_empty() {
+                tree
+            } else {
+                preprocessor::filter_inactive_includes(&tree, &all_sources, &self.graph.borrow(), defines)
+            };
+            let merged = {
+                let graph = self.graph.borrow();
+                self.merge_cache.borrow_mut().get_or_merge(&tree, &all_sources, &graph, self.config.merge_dedup)
             };
+            let view = preprocessor::inject_defines(&merged.source, defines);
+            // the cached line map is of the view without the injected defines
+            let injected_line_map = if defines.is_empty() { None } else { Some(merge_views::line_map(&view, &root_path)) };
 
-            let root_path = self.graph.borrow().get_node(root);
             let ext = match root_path.extension() {
                 Some(ext) => ext,
                 None => {
@@ -298,207 +1250,1400 @@ impl MinecraftShaderLanguageServer {
                     return Ok(diagnostics)
                 },
             };
-            let tree_type = if ext == "fsh" {
-                TreeType::Fragment
-            } else if ext == "vsh" {
-                TreeType::Vertex
-            } else if ext == "gsh" {
-                TreeType::Geometry
-            } else if ext == "csh" {
-                TreeType::Compute
-            } else {
-                eprintln!("got a non fsh|vsh|gsh|csh ({:?}) as a file root ancestor: {:?}", ext, root_path);
-                back_fill(&all_sources, &mut diagnostics);
-                return Ok(diagnostics)
+            let tree_type = match TreeType::from_extension(ext).or_else(|| self.unrecognized_root_tree_type(ext, &root_path, &mut diagnostics)) {
+                Some(tree_type) => tree_type,
+                None => {
+                    back_fill(&all_sources, &mut diagnostics);
+                    return Ok(diagnostics)
+                }
             };
+            self.check_program_name(&root_path, &mut diagnostics);
+
+            let version = get_shader_version(&view);
+            if version.is_none() {
+                diagnostics.entry(Url::from_file_path(&root_path).unwrap()).or_default().push(missing_version_diagnostic());
+            }
+            for (file, range) in misplaced_versions(&view, injected_line_map.as_ref().unwrap_or(&merged.line_map)) {
+                diagnostics.entry(Url::from_file_path(&file).unwrap()).or_default().push(misplaced_version_diagnostic(range));
+            }
+            if all_sources.get(&root_path).map_or(false, |source| lacks_include_extension(source)) {
+                diagnostics.entry(Url::from_file_path(&root_path).unwrap()).or_default().push(missing_include_extension_diagnostic());
+            }
 
-            let stdout = match self.opengl_context.clone().validate(tree_type, view) {
+            let stdout = match self.validate(tree_type, view) {
                 Some(s) => s,
                 None => {
                     back_fill(&all_sources, &mut diagnostics);
                     return Ok(diagnostics)
                 },
             };
-            diagnostics.extend(self.parse_validator_stdout(uri, stdout, ""));
+            for (url, mut file_diagnostics) in self.validator_diagnostics(uri, stdout, tree_type, &tree, &all_sources, defines) {
+                diagnostics.entry(url).or_default().append(&mut file_diagnostics);
+            }
         } else {
-            let mut all_trees: Vec<(TreeType, Vec<(NodeIndex, Option<_>)>)> = Vec::new();
+            let mut all_trees: Vec<(TreeType, PathBuf, Vec<(NodeIndex, Option<_>)>)> = Vec::new();
 
-            for root in &file_ancestors {
+            for root in &self.scope_ancestors(file_ancestors) {
+                let root_path = self.graph.borrow().get_node(*root);
+                if self.is_lint_excluded(&root_path) || self.is_program_disabled(&root_path) {
+                    continue;
+                }
                 let nodes = match self.get_dfs_for_node(*root) {
                     Ok(nodes) => nodes,
                     Err(e) => {
+                        self.log_message(MessageType::Warning, e.to_string());
                         diagnostics.insert(Url::from_file_path(uri).unwrap(), vec![e.into()]);
                         back_fill(&all_sources, &mut diagnostics); // TODO: confirm
                         return Ok(diagnostics);
                     }
                 };
 
-                let root_path = self.graph.borrow().get_node(*root).clone();
                 let ext = match root_path.extension() {
                     Some(ext) => ext,
                     None => continue
                 };
-                let tree_type = if ext == "fsh" {
-                    TreeType::Fragment
-                } else if ext == "vsh" {
-                    TreeType::Vertex
-                } else if ext == "gsh" {
-                    TreeType::Geometry
-                } else if ext == "csh" {
-                    TreeType::Compute
+                let tree_type = match TreeType::from_extension(ext).or_else(|| self.unrecognized_root_tree_type(ext, &root_path, &mut diagnostics)) {
+                    Some(tree_type) => tree_type,
+                    None => continue,
+                };
+                self.check_program_name(&root_path, &mut diagnostics);
+
+                let sources = self.load_sources_with_diagnostics(&nodes, &mut diagnostics)?;
+                let nodes = if defines.is_empty() {
+                    nodes
                 } else {
-                    eprintln!("got a non fsh|vsh|gsh|csh ({:?}) as a file root ancestor: {:?}", ext, root_path);
-                    continue;
+                    preprocessor::filter_inactive_includes(&nodes, &sources, &self.graph.borrow(), defines)
+                };
+                all_trees.push((tree_type, root_path, nodes));
+                all_sources.extend(sources);
+            }
+
+            for tree in all_trees {
+                let merged = {
+                    let graph = self.graph.borrow();
+                    self.merge_cache.borrow_mut().get_or_merge(&tree.2, &all_sources, &graph, self.config.merge_dedup)
+                };
+                let view = preprocessor::inject_defines(&merged.source, defines);
+                // the cached line map is of the view without the injected defines
+                let injected_line_map = if defines.is_empty() { None } else { Some(merge_views::line_map(&view, &tree.1)) };
+
+                let version = get_shader_version(&view);
+                if version.is_none() {
+                    diagnostics.entry(Url::from_file_path(&tree.1).unwrap()).or_default().push(missing_version_diagnostic());
+                }
+                for (file, range) in misplaced_versions(&view, injected_line_map.as_ref().unwrap_or(&merged.line_map)) {
+                    diagnostics.entry(Url::from_file_path(&file).unwrap()).or_default().push(misplaced_version_diagnostic(range));
+                }
+                if all_sources.get(&tree.1).map_or(false, |source| lacks_include_extension(source)) {
+                    diagnostics.entry(Url::from_file_path(&tree.1).unwrap()).or_default().push(missing_include_extension_diagnostic());
+                }
+
+                let stdout = match self.validate(tree.0, view) {
+                    Some(s) => s,
+                    None => continue,
                 };
+                for (url, mut file_diagnostics) in self.validator_diagnostics(uri, stdout, tree.0, &tree.2, &all_sources, defines) {
+                    diagnostics.entry(url).or_default().append(&mut file_diagnostics);
+                }
+            }
+        };
+
+        back_fill(&all_sources, &mut diagnostics);
+        clamp_diagnostics(&mut diagnostics, &all_sources);
+        dedup_diagnostics(&mut diagnostics);
+        Ok(diagnostics)
+    }
+
+    /// Parses the validator's output for `tree`, tagging the diagnostics in blocks that are never enabled.
+    fn validator_diagnostics(&self, uri: &PathBuf, stdout: String, tree_type: TreeType, tree: &[(NodeIndex, Option<NodeIndex>)],
+        sources: &HashMap<PathBuf, String>, defines: &[String]) -> HashMap<Url, Vec<Diagnostic>> {
+        let mut diagnostics = self.parse_validator_lines(uri, stdout.lines(), tree);
+
+        // the stage's -D arguments define names for the validator too
+        let mut defines = defines.to_vec();
+        if let Some(args) = self.config.validator_args.get(&tree_type) {
+            defines.extend(args.iter().filter_map(|arg| arg.strip_prefix("-D")).map(String::from));
+        }
+        preprocessor::tag_disabled_diagnostics(&mut diagnostics, tree, sources, &self.graph.borrow(), &defines);
+
+        diagnostics
+    }
+
+    /// Narrows the top-level `ancestors` of a file being linted down to those that `mcglsl.lintScope`
+    /// asks for. With `changed`, only the most recently opened of them is validated, or the first
+    /// by path if none of them have been opened, leaving the rest to be validated when they are.
+    fn scope_ancestors(&self, ancestors: Vec<NodeIndex>) -> Vec<NodeIndex> {
+        if self.config.lint_scope == configuration::LintScope::AllAncestors || ancestors.len() <= 1 {
+            return ancestors;
+        }
+
+        let graph = self.graph.borrow();
+        let recent = self.recent_roots.iter()
+            .find_map(|recent| ancestors.iter().find(|root| graph.get_node(**root) == *recent));
+        match recent {
+            Some(root) => vec![*root],
+            None => ancestors.iter().min_by_key(|root| graph.get_node(**root)).into_iter().copied().collect(),
+        }
+    }
+
+    /// Remembers `path` as the most recently opened top-level file, if it is one.
+    fn note_recent_root(&mut self, path: &PathBuf) {
+        let node = self.graph.borrow_mut().find_node(path);
+        let is_root = match node {
+            Some(node) => self.graph.borrow().parent_node_indexes(node).is_empty(),
+            None => false,
+        };
+        if !is_root {
+            return;
+        }
+
+        self.recent_roots.retain(|recent| recent != path);
+        self.recent_roots.insert(0, path.clone());
+        self.recent_roots.truncate(consts::RECENT_ROOTS_LIMIT);
+    }
+
+    /// Returns whether linting the saved file `path` should wait, as it was last saved less than
+    /// `mcglsl.validateDebounceMs` ago. Every save restarts the file's timer, and `lint_due_save`
+    /// lints it once the timer runs out after the last save of a burst, so that save is validated
+    /// however many saves there were before it.
+    fn debounce_save_lint(&mut self, path: &PathBuf) -> bool {
+        let window = match self.config.validate_debounce_ms {
+            Some(ms) => Duration::from_millis(ms),
+            None => return false,
+        };
+
+        let now = Instant::now();
+        let waiting = match self.last_save_lint.insert(path.clone(), now) {
+            Some(last) => now.duration_since(last) < window,
+            None => false,
+        };
+        self.schedule_save_lint(path, window);
+
+        if waiting {
+            self.pending_save_lints.insert(path.clone());
+        }
+        waiting
+    }
+
+    /// Has the client run the `lintDueSave` command for `path` once `window` has passed. The server
+    /// only runs on the thread reading the client's messages, so the timer's thread asks the client
+    /// to send one rather than linting itself.
+    fn schedule_save_lint(&mut self, path: &PathBuf, window: Duration) {
+        // the timers that were due have sent their notification, or are just about to
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = self.save_lint_timers.drain(..).partition(|(due, _)| *due <= now);
+        self.save_lint_timers = waiting;
+        for (_, timer) in due {
+            timer.join().unwrap_or(());
+        }
+
+        let endpoint = self.endpoint.clone();
+        let cancelled = self.save_lint_timers_cancelled.clone();
+        let params = lsp_ext::SaveLintDueParams {
+            path: path.to_slash_lossy(),
+        };
+        let timer = thread::spawn(move || {
+            let (cancelled, condvar) = &*cancelled;
+            let (cancelled, _) = condvar.wait_timeout_while(cancelled.lock().unwrap(), window, |cancelled| !*cancelled).unwrap();
+            // the server is shutting down, so there is nothing to lint any more
+            if *cancelled {
+                return;
+            }
+            drop(cancelled);
+            if let Err(e) = endpoint.send_notification(lsp_ext::SaveLintDue::METHOD, params) {
+                warn!("failed to send {} notification: {:?}", lsp_ext::SaveLintDue::METHOD, e);
+            }
+        });
+        self.save_lint_timers.push((now + window, timer));
+    }
+
+    /// Stops the timers of `schedule_save_lint` from sending anything more and waits for their
+    /// threads to finish.
+    fn stop_save_lint_timers(&mut self) {
+        let (cancelled, condvar) = &*self.save_lint_timers_cancelled;
+        *cancelled.lock().unwrap() = true;
+        condvar.notify_all();
+        for (_, timer) in self.save_lint_timers.drain(..) {
+            timer.join().unwrap_or(());
+        }
+    }
+
+    /// Lints `path` if a save of it was put off by `debounce_save_lint` and its window has passed
+    /// since it was last saved. The timers of the earlier saves of a burst find the window still
+    /// open and leave it to the last one. Handles the `lintDueSave` command.
+    fn lint_due_save(&mut self, path: &PathBuf) {
+        let window = Duration::from_millis(self.config.validate_debounce_ms.unwrap_or(0));
+        let due = self.pending_save_lints.contains(path)
+            && self.last_save_lint.get(path).map_or(true, |last| last.elapsed() >= window);
+        if due {
+            self.pending_save_lints.remove(path);
+            self.lint_saved(path);
+        }
+    }
+
+    fn lint_due_save_command(&mut self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        self.lint_due_save(&path);
+        Ok(Value::Null)
+    }
+
+    /// Lints the saved files whose lint was put off by `debounce_save_lint` and whose window has
+    /// passed, in case the client didn't run `lintDueSave` for them.
+    fn lint_pending_saves(&mut self) {
+        let pending: Vec<PathBuf> = self.pending_save_lints.iter().cloned().collect();
+        for path in pending {
+            self.lint_due_save(&path);
+        }
+    }
+
+    /// Lints the saved file `path` and publishes its diagnostics, clearing those of the files
+    /// that it no longer includes.
+    fn lint_saved(&mut self, path: &PathBuf) {
+        if self.shutting_down {
+            return;
+        }
+        match self.lint_if_changed(path) {
+            Ok(diagnostics) => {
+                self.set_lint_status(&diagnostics);
+                self.publish_diagnostic(diagnostics, None)
+            },
+            Err(e) => {
+                error!("error linting: {}", e);
+                self.log_message(MessageType::Error, format!("Failed to validate {:?}: {}", path, e));
+            },
+        }
+
+        // clear out stale diagnostics of files that are no longer included
+        let dropped = self.update_published_files(path);
+        if !dropped.is_empty() {
+            self.publish_diagnostic(dropped.into_iter().map(|url| (url, vec![])).collect(), None);
+        }
+    }
+
+    /// Runs the validator on a merged view, within `mcglsl.validateTimeoutMs` if it is set.
+    /// A timed out validation is reported to the user and treated as having no output.
+    fn validate(&self, tree_type: TreeType, view: String) -> Option<String> {
+        let timeout = match self.config.validate_timeout_ms {
+            Some(ms) => Duration::from_millis(ms),
+            None => return self.opengl_context.clone().validate(tree_type, view),
+        };
+
+        match self.opengl_context.clone().validate_with_timeout(tree_type, view, timeout) {
+            Ok(stdout) => stdout,
+            Err(e) => {
+                warn!("{}", e);
+                self.endpoint.send_notification(ShowMessage::METHOD, ShowMessageParams {
+                    typ: MessageType::Warning,
+                    message: format!("Shader validation was skipped: {}", e),
+                }).unwrap_or(());
+                None
+            }
+        }
+    }
+
+    /// Hashes the paths and contents of every file in the include trees that `path` is part of,
+    /// which together with the configuration decide what linting it reports.
+    fn include_trees_hash(&self, path: &PathBuf) -> Result<u64> {
+        let mut sources = self.load_include_tree_sources(path)?;
+        sources.sort();
+
+        let mut hasher = DefaultHasher::new();
+        sources.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    /// Lints `path` unless none of the files in its include trees changed since it was last
+    /// linted through here, in which case the diagnostics of that lint are returned without
+    /// running the validator again. Used when opening and saving, where reopening a file that
+    /// was just saved would otherwise validate the same sources twice.
+    fn lint_if_changed(&mut self, path: &PathBuf) -> Result<HashMap<Url, Vec<Diagnostic>>> {
+        let hash = match self.include_trees_hash(path) {
+            Ok(hash) => hash,
+            // such as an include that doesn't exist, which the lint reports
+            Err(_) => {
+                self.last_lint.remove(path);
+                return self.lint(path);
+            }
+        };
+
+        if let Some((last_hash, diagnostics)) = self.last_lint.get(path) {
+            if *last_hash == hash {
+                debug!("{:?} is unchanged since it was last linted", path);
+                return Ok(diagnostics.clone());
+            }
+        }
+
+        let diagnostics = self.lint(path)?;
+        self.last_lint.insert(path.clone(), (hash, diagnostics.clone()));
+        Ok(diagnostics)
+    }
+
+    /// Handles the `lintWithDefines` command, taking `{ file, defines: [...] }` and returning
+    /// the diagnostics of linting the file's trees with those defines set.
+    fn lint_with_defines_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let args = match arguments.get(0) {
+            Some(Value::Object(args)) => args,
+            _ => return Err(anyhow!("expected an object argument of the form {{ file, defines }}")),
+        };
+
+        let path = match args.get("file") {
+            Some(file) => PathBuf::from_json(file)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        let defines: Vec<String> = match args.get("defines") {
+            Some(Value::Array(defines)) => defines.iter().filter_map(|d| d.as_str().map(String::from)).collect(),
+            Some(_) => return Err(anyhow!("defines must be an array of strings")),
+            None => vec![],
+        };
+
+        let diagnostics = self.lint_with_defines(&path, &defines)?;
+        Ok(serde_json::to_value(diagnostics)?)
+    }
+
+    /// Returns the code actions for `range` of `document`, being a `createFile` command for each
+    /// include of a file that doesn't exist, if its missing include diagnostic overlaps `range`.
+    /// rust_lsp only lets code actions be commands, so the file is created when the command is run
+    /// rather than through a `WorkspaceEdit`.
+    fn create_file_actions(&self, document: &PathBuf, range: Range, diagnostics: &[Diagnostic]) -> Vec<Command> {
+        let node = match self.graph.borrow_mut().find_node(document) {
+            Some(node) => node,
+            None => return vec![],
+        };
+
+        let overlaps = |other: &Range| (range.start.line, range.start.character) <= (other.end.line, other.end.character)
+            && (other.start.line, other.start.character) <= (range.end.line, range.end.character);
+
+        let includes = self.graph.borrow().child_node_meta(node);
+        includes.into_iter()
+            .filter(|(path, _)| !self.files.exists(path))
+            .filter(|(path, include)| {
+                let missing = missing_include_diagnostic(path, include);
+                diagnostics.iter().any(|diagnostic| *diagnostic == missing && overlaps(&diagnostic.range))
+            })
+            .filter_map(|(path, _)| {
+                let url = Url::from_file_path(&path).ok()?;
+                Some(Command {
+                    title: format!("Create file {}", self.include_tooltip(document, &path)),
+                    command: "createFile".into(),
+                    arguments: Some(vec![Value::String(url.path().into())]),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the edit enabling `GL_GOOGLE_include_directive` in `document` directly after its
+    /// `#version` line, or at the top if it has none, if the file includes others without it.
+    fn include_extension_edit(&self, document: &PathBuf) -> Result<Option<WorkspaceEdit>> {
+        let source = self.document_text(document)?;
+        if !lacks_include_extension(&source) {
+            return Ok(None);
+        }
+
+        let newline = LineEnding::detect(&source).as_str();
+        let source = normalize_line_endings(&source);
+        // inserted with the line ending of the document instead
+        let extension = consts::INCLUDE_DIRECTIVE.trim_end();
+        let edit = match source.lines().enumerate().find(|(_, line)| RE_VERSION.is_match(line)) {
+            Some((n, line)) => {
+                let end = Position::new(u32::try_from(n)?, u32::try_from(line.encode_utf16().count())?);
+                TextEdit::new(Range::new(end, end), format!("{}{}", newline, extension))
+            },
+            None => TextEdit::new(Range::new(Position::new(0, 0), Position::new(0, 0)), format!("{}{}", extension, newline)),
+        };
+
+        let mut changes = HashMap::new();
+        changes.insert(Url::from_file_path(document).map_err(|_| anyhow!("malformed file path {:?}", document))?, vec![edit]);
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    /// Handles the `includeExtensionEdit` command, returning the edit enabling includes in the
+    /// file it is given, or `null` if it doesn't need one.
+    fn include_extension_edit_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        Ok(serde_json::to_value(self.include_extension_edit(&path)?)?)
+    }
+
+    /// Returns the quick fix for the warning that `document` uses `#include` without enabling
+    /// `GL_GOOGLE_include_directive`, if it is among `diagnostics`. The client's
+    /// `mcglsl.insertIncludeExtension` asks for the edit with `includeExtensionEdit` and applies it.
+    fn include_extension_actions(&self, document: &PathBuf, diagnostics: &[Diagnostic]) -> Vec<Command> {
+        if !diagnostics.contains(&missing_include_extension_diagnostic()) {
+            return vec![];
+        }
+        let url = match Url::from_file_path(document) {
+            Ok(url) => url,
+            Err(_) => return vec![],
+        };
+
+        vec![Command {
+            title: format!("Insert {}", consts::INCLUDE_DIRECTIVE.trim_end()),
+            command: "mcglsl.insertIncludeExtension".into(),
+            arguments: Some(vec![Value::String(url.path().into())]),
+        }]
+    }
+
+    /// Handles the `createFile` command, creating the empty file at the path it is given, along
+    /// with any missing directories, and linting it so that the files including it are no longer
+    /// reported as including a missing file. An existing file is left as it is.
+    fn create_file_command(&mut self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new().write(true).create(true).open(&path)?;
+
+        self.graph.borrow_mut().add_node(&path);
+        self.merge_cache.borrow_mut().invalidate(&path);
+        self.lint_saved(&path);
+
+        Ok(Value::Null)
+    }
+
+    /// Lints every top-level file in the graph and publishes the diagnostics of the whole project,
+    /// returning them. Handles the `lintAll` command. Each tree's diagnostics are published as
+    /// soon as it has been validated, rather than after the whole project.
+    fn lint_all(&mut self) -> HashMap<Url, Vec<Diagnostic>> {
+        let mut roots: Vec<PathBuf> = {
+            let graph = self.graph.borrow();
+            graph.graph.node_indices()
+                .filter(|node| graph.parent_node_indexes(*node).is_empty())
+                .map(|node| graph.get_node(node))
+                .collect()
+        };
+        roots.sort();
+
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+        let mut dropped: HashSet<Url> = HashSet::new();
+        for (i, root) in roots.iter().enumerate() {
+            self.set_status("loading", format!("Validating {}/{} programs...", i + 1, roots.len()), "$(loading~spin)");
+
+            match self.lint(root) {
+                Ok(root_diagnostics) => {
+                    // every file of the tree has an entry, so that files without diagnostics are cleared too
+                    let urls: Vec<Url> = root_diagnostics.keys().cloned().collect();
+                    for (url, mut file_diagnostics) in root_diagnostics {
+                        diagnostics.entry(url).or_default().append(&mut file_diagnostics);
+                    }
+                    dedup_diagnostics(&mut diagnostics);
+
+                    // each publish replaces a file's diagnostics, so files shared with trees
+                    // validated earlier are published with the diagnostics of those too
+                    self.publish_diagnostic(urls.into_iter().map(|url| {
+                        let file_diagnostics = diagnostics[&url].clone();
+                        (url, file_diagnostics)
+                    }).collect(), None);
+                },
+                Err(e) => error!("error linting {:?}: {}", root, e),
+            }
+            dropped.extend(self.update_published_files(root));
+        }
+
+        // clear out stale diagnostics of files that are no longer included
+        let dropped: HashMap<Url, Vec<Diagnostic>> = dropped.into_iter()
+            .filter(|url| !diagnostics.contains_key(url))
+            .map(|url| (url, vec![]))
+            .collect();
+        self.publish_diagnostic(dropped.clone(), None);
+        diagnostics.extend(dropped);
+
+        self.set_lint_status(&diagnostics);
+        diagnostics
+    }
+
+    /// Decides how to treat a top-level file whose extension isn't a known shader stage,
+    /// based on the `unrecognizedRoots` configuration. Returns the stage to validate it as,
+    /// or `None` if it should be skipped.
+    fn unrecognized_root_tree_type(&self, ext: &OsStr, root_path: &PathBuf, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) -> Option<TreeType> {
+        let behaviour = ext.to_str().and_then(|ext| self.config.unrecognized_roots.get(ext));
+
+        match behaviour {
+            Some(configuration::UnrecognizedRootBehaviour::Ignore) => None,
+            Some(configuration::UnrecognizedRootBehaviour::Warn) => {
+                diagnostics.entry(Url::from_file_path(root_path).unwrap()).or_default().push(Diagnostic {
+                    range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                    code: None,
+                    severity: Some(DiagnosticSeverity::Warning),
+                    source: Some(consts::SOURCE.into()),
+                    message: format!("Top-level file with unrecognized extension {:?} will not be validated", ext),
+                    related_information: None,
+                    tags: None,
+                    code_description: Option::None,
+                    data: Option::None,
+                });
+                None
+            },
+            Some(behaviour) => behaviour.tree_type(),
+            None => {
+                warn!("got a non fsh|vsh|gsh|csh|tcs|tes ({:?}) as a file root ancestor: {:?}", ext, root_path);
+                None
+            }
+        }
+    }
+
+    /// Adds an informational diagnostic to `root_path` if its name isn't one of the programs that Optifine loads.
+    fn check_program_name(&self, root_path: &PathBuf, diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) {
+        let stem = match root_path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => stem,
+            None => return,
+        };
+
+        let known = match &self.config.program_names {
+            Some(names) => is_program_name(stem, names.as_slice()),
+            None => is_program_name(stem, consts::PROGRAM_NAMES),
+        };
+        if known {
+            return;
+        }
+
+        diagnostics.entry(Url::from_file_path(root_path).unwrap()).or_default().push(Diagnostic {
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            code: None,
+            severity: Some(DiagnosticSeverity::Information),
+            source: Some(consts::SOURCE.into()),
+            message: format!("{:?} is not a program name known to Optifine, so this file will not be loaded", stem),
+            related_information: None,
+            tags: None,
+            code_description: Option::None,
+            data: Option::None,
+        });
+    }
+
+    /// Returns the include path from the root of `tree` down to the first place in it that `file` is
+    /// included, as the position of each `#include` along the way. Empty if `file` is the root.
+    fn include_chain(&self, tree: &[(NodeIndex, Option<NodeIndex>)], file: &PathBuf) -> Vec<DiagnosticRelatedInformation> {
+        let graph = self.graph.borrow();
+
+        let mut index = match tree.iter().position(|(node, _)| &graph.get_node(*node) == file) {
+            Some(index) => index,
+            None => return vec![],
+        };
+
+        let mut chain = Vec::new();
+        while let (child, Some(parent)) = tree[index] {
+            let include = graph.get_edge_meta(parent, child);
+            let parent_path = graph.get_node(parent);
+            let child_path = graph.get_node(child);
+            chain.push(DiagnosticRelatedInformation {
+                location: Location::new(
+                    Url::from_file_path(&parent_path).unwrap(),
+                    Range::new(
+                        Position::new(u32::try_from(include.line).unwrap(), u32::try_from(include.start).unwrap()),
+                        Position::new(u32::try_from(include.line).unwrap(), u32::try_from(include.end).unwrap()),
+                    ),
+                ),
+                message: format!("{} is included here", child_path.file_name().unwrap().to_string_lossy()),
+            });
+
+            // the parent's own entry is the closest one before, as the tree is in depth-first order
+            index = match tree[..index].iter().rposition(|(node, _)| *node == parent) {
+                Some(index) => index,
+                None => break,
+            };
+        }
+
+        chain.reverse();
+        chain
+    }
+
+    /// Parses the validator's output for the merged source of `tree` a line at a time as `lines`
+    /// yields it, attaching the include path from the root of `tree` to every diagnostic in an
+    /// included file. Only the diagnostics are held on to, not the lines they were parsed from.
+    fn parse_validator_lines<S: AsRef<str>>(&self, uri: &PathBuf, lines: impl IntoIterator<Item = S>, tree: &[(NodeIndex, Option<NodeIndex>)]) -> HashMap<Url, Vec<Diagnostic>> {
+        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+
+        for line in lines {
+            let line = line.as_ref();
+            let diagnostic_capture = match self.diagnostic_regex.as_ref().unwrap_or(&RE_DIAGNOSTIC).captures(line) {
+                Some(d) => d,
+                None => continue
+            };
+
+            trace!("match {:?}", diagnostic_capture);
+            
+            let msg = diagnostic_capture.name("output").unwrap().as_str();
+
+            let line = match diagnostic_capture.name("linenum") {
+                Some(c) => match c.as_str().parse::<u32>() {
+                    Ok(i) => i,
+                    Err(_) => 0,
+                },
+                None => 0,
+            }.saturating_sub(2);
+
+            // TODO: line matching maybe
+            /* let line_text = source_lines[line as usize];
+            let leading_whitespace = line_text.len() - line_text.trim_start().len(); */
+
+            let severity = match diagnostic_capture.name("severity") {
+                Some(c) => match c.as_str() {
+                    "error" => DiagnosticSeverity::Error,
+                    "warning" if self.config.warnings_as_errors => DiagnosticSeverity::Error,
+                    "warning" => DiagnosticSeverity::Warning,
+                    _ => DiagnosticSeverity::Information,
+                }
+                _ => DiagnosticSeverity::Information,
+            };
+
+            let code = diagnostic_capture.name("code").map(|c| c.as_str().to_string());
+
+            let origin = match diagnostic_capture.name("filepath") {
+                Some(o) if o.as_str() != "0" => normalize_validator_path(o.as_str()),
+                _ => uri.clone(),
+            };
+
+            let diagnostic = Diagnostic {
+                range: Range::new(
+                    /* Position::new(line, leading_whitespace as u64),
+                    Position::new(line, line_text.len() as u64) */
+                    Position::new(line, 0),
+                    Position::new(line, 1000),
+                ),
+                code_description: code.as_deref().and_then(error_code_description),
+                code: code.map(NumberOrString::String),
+                severity: Some(severity),
+                source: Some(consts::SOURCE.into()),
+                message: msg.trim().into(),
+                related_information: match self.include_chain(tree, &origin) {
+                    chain if chain.is_empty() => None,
+                    chain => Some(chain),
+                },
+                tags: None,
+                data: Option::None,
+            };
+
+            let origin_url = match Url::from_file_path(&origin) {
+                Ok(url) => url,
+                Err(_) => {
+                    warn!("skipping diagnostic with malformed file path {:?}", origin);
+                    continue;
+                }
+            };
+            match diagnostics.get_mut(&origin_url) {
+                Some(d) => d.push(diagnostic),
+                None => {
+                    diagnostics.insert(origin_url, vec![diagnostic]);
+                },
+            };
+        }
+        diagnostics
+    }
+
+    pub fn get_dfs_for_node(&self, root: NodeIndex) -> Result<Vec<(NodeIndex, Option<NodeIndex>)>, dfs::error::DfsError> {
+        let graph_ref = self.graph.borrow();
+
+        let dfs = dfs::Dfs::with_max_depth(&graph_ref, root, self.config.max_include_depth);
+
+        dfs.collect::<Result<Vec<_>, _>>()
+    }
+
+    pub fn load_sources(&self, nodes: &[(NodeIndex, Option<NodeIndex>)]) -> Result<HashMap<PathBuf, String>> {
+        self.load_sources_with_diagnostics(nodes, &mut HashMap::new())
+    }
+
+    /// Same as `load_sources`, but adds a warning to `diagnostics` for every file that isn't valid UTF-8.
+    /// Included files that don't exist are reported on their `#include` line and treated as empty.
+    pub fn load_sources_with_diagnostics(&self, nodes: &[(NodeIndex, Option<NodeIndex>)], diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) -> Result<HashMap<PathBuf, String>> {
+        self.load_sources_from(self.files.as_ref(), nodes, diagnostics)
+    }
+
+    /// Same as `load_sources_with_diagnostics`, but reads the files through `files`, such as the
+    /// documents open in the client.
+    pub fn load_sources_from(&self, files: &dyn files::FileProvider, nodes: &[(NodeIndex, Option<NodeIndex>)], diagnostics: &mut HashMap<Url, Vec<Diagnostic>>) -> Result<HashMap<PathBuf, String>> {
+        let mut sources = HashMap::new();
+
+        for node in nodes {
+            let graph = self.graph.borrow();
+            let path = graph.get_node(node.0);
+
+            if sources.contains_key(&path) {
+                continue;
+            }
+
+            let mut included: HashMap<NodeIndex, IncludePosition> = HashMap::new();
+            for (child, include) in graph.child_edges(node.0) {
+                match included.get(&child) {
+                    Some(first) => diagnostics.entry(Url::from_file_path(&path).unwrap()).or_default()
+                        .push(duplicate_include_diagnostic(&graph.get_node(child), first, &include)),
+                    None => {
+                        included.insert(child, include);
+                    },
+                }
+            }
+
+            if let Some(parent) = node.1 {
+                if let Some(actual) = self.root_for(&path).and_then(|root| case_mismatch(files, root, &path)) {
+                    let parent_path = graph.get_node(parent);
+                    let include = graph.get_edge_meta(parent, node.0);
+                    diagnostics.entry(Url::from_file_path(&parent_path).unwrap()).or_default().push(include_case_mismatch_diagnostic(&path, &actual, include));
+                }
+
+                // compared once symlinks and `..` are resolved, as either can lead out of the shaderpack.
+                // The `mcglsl.includePaths` directories are outside of it on purpose
+                let parent_path = graph.get_node(parent);
+                if !graph.key(&path).starts_with(graph.key(&self.pack_root_for(&parent_path))) && !self.in_include_paths(&parent_path, &path) {
+                    let include = graph.get_edge_meta(parent, node.0);
+                    diagnostics.entry(Url::from_file_path(&parent_path).unwrap()).or_default().push(include_outside_pack_diagnostic(&path, include));
+                }
+            }
+
+            let source = match read_source_from(files, &path) {
+                Ok((s, false)) => s,
+                Ok((s, true)) => {
+                    diagnostics.entry(Url::from_file_path(&path).unwrap()).or_default().push(invalid_utf8_diagnostic());
+                    s
+                },
+                Err(e) => match (e.kind(), node.1) {
+                    (std::io::ErrorKind::NotFound, Some(parent)) => {
+                        let parent_path = graph.get_node(parent);
+                        let include = graph.get_edge_meta(parent, node.0);
+                        diagnostics.entry(Url::from_file_path(&parent_path).unwrap()).or_default().push(missing_include_diagnostic(&path, include));
+                        String::new()
+                    },
+                    _ => return Err(anyhow!("error reading {:?}: {}", path, e)),
+                }
+            };
+            // so that the validator counts lines the same way as the editor
+            let source = normalize_line_endings(&source);
+            for (name, include) in find_macro_includes(&source) {
+                diagnostics.entry(Url::from_file_path(&path).unwrap()).or_default().push(macro_include_diagnostic(&name, &include));
+            }
+            let source = merge_views::rewrite_line_directives(&path, &source);
+            sources.insert(path.clone(), source);
+        }
+
+        Ok(sources)
+    }
+
+    fn get_file_toplevel_ancestors(&self, uri: &PathBuf) -> Result<Option<Vec<petgraph::stable_graph::NodeIndex>>> {
+        let curr_node = match self.graph.borrow_mut().find_node(uri) {
+            Some(n) => n,
+            None => return Err(anyhow!("node not found {:?}", uri)),
+        };
+        let roots = self.graph.borrow().collect_root_ancestors(curr_node);
+        if roots.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(roots))
+    }
+
+    /// Returns whether `path` is validated when it is opened, as set by `mcglsl.lintOnOpen`. With
+    /// `rootsOnly`, files that are included by others are left to be validated when they are saved.
+    fn lints_on_open(&self, path: &PathBuf) -> bool {
+        match self.config.lint_on_open {
+            configuration::LintOnOpen::Always => true,
+            configuration::LintOnOpen::RootsOnly => match self.get_file_toplevel_ancestors(path) {
+                Ok(Some(_)) => false,
+                Ok(None) => true,
+                Err(e) => {
+                    error!("error finding the top-level files including {:?}: {}", path, e);
+                    false
+                },
+            },
+            configuration::LintOnOpen::Never => false,
+        }
+    }
+
+    /// Returns every file in the include trees that `path` is part of, that is the
+    /// DFS over each of its top-level ancestors (or over `path` itself if it is top-level).
+    fn get_include_tree_files(&self, path: &PathBuf) -> Result<Vec<PathBuf>> {
+        let roots = match self.get_file_toplevel_ancestors(path)? {
+            Some(roots) => roots,
+            None => vec![self.graph.borrow_mut().find_node(path).unwrap()],
+        };
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        for root in roots {
+            for (node, _) in self.get_dfs_for_node(root)? {
+                let file = self.graph.borrow().get_node(node);
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Returns the files as the server sees them, being the unsaved contents of the documents open
+    /// in the client and everything else as read by `files`.
+    fn open_documents(&self) -> files::OpenDocuments {
+        files::OpenDocuments {
+            documents: &self.documents,
+            files: self.files.as_ref(),
+        }
+    }
+
+    /// Returns the contents of `path` as open in the client, or as on disk if it isn't open.
+    fn document_text(&self, path: &PathBuf) -> Result<String> {
+        match self.documents.get(path) {
+            Some(text) => Ok(text.clone()),
+            None => Ok(read_source_from(self.files.as_ref(), path)?.0),
+        }
+    }
+
+    /// Loads the contents of every file in the include trees that `path` is part of, preferring
+    /// the unsaved contents of those open in the client.
+    fn load_include_tree_sources(&self, path: &PathBuf) -> Result<Vec<(PathBuf, String)>> {
+        let files = self.get_include_tree_files(path)?;
+
+        let mut sources = Vec::with_capacity(files.len());
+        for file in files {
+            let source = match self.document_text(&file) {
+                Ok(s) => s,
+                Err(e) => return Err(anyhow!("error reading {:?}: {}", file, e))
+            };
+            sources.push((file, source));
+        }
+
+        Ok(sources)
+    }
+
+    /// Returns the token under `position` if it can be renamed, along with the sources
+    /// of the include tree it was resolved against. A token is renameable if it is a
+    /// `#define` or a user-declared identifier somewhere in the tree, and not a keyword.
+    fn renameable_token(&self, path: &PathBuf, position: Position) -> Result<(symbols::Token, Vec<(PathBuf, String)>)> {
+        let source = self.document_text(path)?;
+        let token = match symbols::token_at_position(&source, position.line as usize, position.character as usize) {
+            Some(token) => token,
+            None => return Err(anyhow!("no renameable symbol at cursor")),
+        };
+
+        if symbols::is_reserved(&token.text) {
+            return Err(anyhow!("{} is a reserved name and can't be renamed", token.text));
+        }
+
+        let sources = self.load_include_tree_sources(path)?;
+
+        let declared = sources.iter().any(|(_, source)| {
+            !symbols::find_defines(source, &token.text).is_empty() || !symbols::find_declarations(source, &token.text).is_empty()
+        });
+        if !declared {
+            return Err(anyhow!("{} is not defined in this include tree", token.text));
+        }
+
+        Ok((token, sources))
+    }
+
+    /// Runs the `workspace/executeCommand` command `command`, returning its result.
+    fn run_command(&mut self, command: &str, arguments: Vec<Value>) -> Result<Value> {
+        // graph-only commands are given the first workspace folder, where graphDot writes its output
+        let root = self.roots.first().cloned().unwrap_or_default();
+        // commands that need access to the server itself rather than just the graph
+        match command {
+            "lintWithDefines" => self.lint_with_defines_command(arguments),
+            "rebuildGraph" => {
+                self.rebuild_graph();
+                Ok(Value::Null)
+            },
+            "lintAll" => serde_json::to_value(self.lint_all()).map_err(|e| e.into()),
+            "createFile" => self.create_file_command(arguments),
+            "includeExtensionEdit" => self.include_extension_edit_command(arguments),
+            "semanticTokens" => self.semantic_tokens_command(arguments),
+            "foldingRanges" => self.folding_ranges_command(arguments),
+            "documentColors" => self.document_colors_command(arguments),
+            "colorPresentations" => self.color_presentations_command(arguments),
+            "prepareRename" => self.prepare_rename_command(arguments),
+            "lintDueSave" => self.lint_due_save_command(arguments),
+            "workspaceFoldersChanged" => self.workspace_folders_changed_command(arguments),
+            // created for each invocation, as they read the open documents as they are now
+            "virtualMerge" => commands::Invokeable::run_command(&commands::VirtualMergedDocument { server: self, files: &self.open_documents() }, &root, arguments),
+            "mergedView" => commands::Invokeable::run_command(&commands::MergedViewCommand { server: self, files: &self.open_documents() }, &root, arguments),
+            "mergedDefinition" => commands::Invokeable::run_command(&commands::MergedDefinitionCommand { server: self, files: &self.open_documents() }, &root, arguments),
+            "unusedIncludes" => commands::Invokeable::run_command(&commands::UnusedIncludesCommand { graph: Rc::clone(&self.graph), files: &self.open_documents() }, &root, arguments),
+            "glInfo" => commands::Invokeable::run_command(&commands::GlInfoCommand { validator: self.opengl_context.as_ref() }, &root, arguments),
+            "validateSnippet" => commands::Invokeable::run_command(&commands::ValidateSnippetCommand { server: self }, &root, arguments),
+            _ => self.command_provider.as_ref().unwrap().execute(command, arguments, &root),
+        }
+    }
+
+    /// Returns the semantic tokens of the preprocessor directives of a document, using its unsaved
+    /// contents if it is open. rust_lsp doesn't route `textDocument/semanticTokens/full`, so the
+    /// client's provider asks for them with the `semanticTokens` command instead, using the legend
+    /// advertised in the experimental capabilities.
+    fn semantic_tokens_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        Ok(serde_json::to_value(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::tokens(&self.document_text(&path)?),
+        })?)
+    }
+
+    /// Returns the folding ranges of a document, using its unsaved contents if it is open.
+    /// rust_lsp doesn't route `textDocument/foldingRange`, so the client's provider asks for them
+    /// with the `foldingRanges` command instead.
+    fn folding_ranges_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        Ok(serde_json::to_value(folding::folding_ranges(&self.document_text(&path)?))?)
+    }
+
+    /// Returns the colors of a document, using its unsaved contents if it is open. rust_lsp doesn't
+    /// route `textDocument/documentColor`, so the client's provider asks for them with the
+    /// `documentColors` command instead.
+    fn document_colors_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+
+        Ok(serde_json::to_value(colors::document_colors(&self.document_text(&path)?))?)
+    }
+
+    /// Returns the presentations of a color picked for the literal at a range of a document. Takes
+    /// the document, the color and the range, and stands in for `textDocument/colorPresentation`.
+    fn color_presentations_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+        let color: Color = match arguments.get(1) {
+            Some(color) => serde_json::from_value(color.clone())?,
+            None => return Err(anyhow!("missing color argument")),
+        };
+        let range: Range = match arguments.get(2) {
+            Some(range) => serde_json::from_value(range.clone())?,
+            None => return Err(anyhow!("missing range argument")),
+        };
+
+        Ok(serde_json::to_value(colors::color_presentations(&self.document_text(&path)?, color, range))?)
+    }
+
+    /// Returns the range and text of the symbol under a position if it can be renamed.
+    /// rust_lsp doesn't route `textDocument/prepareRename`, so the client's rename provider
+    /// asks with the `prepareRename` command instead.
+    fn prepare_rename_command(&self, arguments: Vec<Value>) -> Result<Value> {
+        let path = match arguments.get(0) {
+            Some(path) => PathBuf::from_json(path)?,
+            None => return Err(anyhow!("missing file argument")),
+        };
+        let position: Position = match arguments.get(1) {
+            Some(position) => serde_json::from_value(position.clone())?,
+            None => return Err(anyhow!("missing position argument")),
+        };
+
+        let (token, _) = self.renameable_token(&path, position)?;
+        Ok(serde_json::to_value(PrepareRenameResponse::RangeWithPlaceholder {
+            range: token.range(),
+            placeholder: token.text,
+        })?)
+    }
+
+    /// Builds the edits required to rename the symbol under `position` to `new_name`
+    /// in every file of the include tree that `path` belongs to. A position without a
+    /// renameable symbol is rejected here too, for clients that don't ask `prepareRename` first.
+    fn rename_symbol(&self, path: &PathBuf, position: Position, new_name: &str) -> Result<WorkspaceEdit> {
+        if !symbols::is_identifier(new_name) || symbols::is_reserved(new_name) {
+            return Err(anyhow!("{:?} is not a valid identifier", new_name));
+        }
+
+        let (token, sources) = self.renameable_token(path, position)?;
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for (file, source) in sources {
+            let edits: Vec<TextEdit> = symbols::find_token_occurrences(&source, &token.text)
+                .iter()
+                .map(|occurrence| TextEdit::new(occurrence.range(), new_name.to_string()))
+                .collect();
+            if !edits.is_empty() {
+                changes.insert(Url::from_file_path(&file).unwrap(), edits);
+            }
+        }
+
+        Ok(WorkspaceEdit::new(changes))
+    }
+
+    /// Returns the hover for a macro under `position`, showing its `#define` and, if it is defined as
+    /// another macro, every `#define` it goes through down to the value it ends up as. The defines
+    /// are looked up in `path` first and then in the rest of its include trees.
+    fn define_hover(&self, path: &PathBuf, position: Position) -> Result<Option<Hover>> {
+        let source = self.document_text(path)?;
+        let token = match symbols::token_at_position(&source, position.line as usize, position.character as usize) {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        let mut sources = vec![(path.clone(), source)];
+        // a file that isn't in the graph yet only has its own defines
+        if let Ok(tree_sources) = self.load_include_tree_sources(path) {
+            sources.extend(tree_sources.into_iter().filter(|(file, _)| file != path));
+        }
+
+        let mut chain: Vec<(String, String)> = Vec::new();
+        // a macro defined in terms of itself would otherwise be followed forever
+        let mut visited = HashSet::new();
+        let mut name = token.text.clone();
+        let cycle = loop {
+            if !visited.insert(name.clone()) {
+                break true;
+            }
+            let value = match sources.iter().find_map(|(_, source)| symbols::define_value(source, &name)) {
+                Some(value) => value,
+                None => break false,
+            };
+            chain.push((name, value.clone()));
+            if !symbols::is_identifier(&value) {
+                break false;
+            }
+            name = value;
+        };
+
+        let value = match chain.last() {
+            Some((_, value)) => value.clone(),
+            None => return Ok(None),
+        };
+
+        let defines: Vec<String> = chain.iter().map(|(name, value)| format!("#define {} {}", name, value).trim_end().to_string()).collect();
+        let mut markdown = format!("```glsl\n{}\n```", defines.join("\n"));
+        if cycle {
+            markdown.push_str(&format!("\n\n`{}` is defined in terms of itself, so it never expands to a value", token.text));
+        } else if chain.len() > 1 {
+            markdown.push_str(&format!("\n\nExpands to `{}`", value));
+        }
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: markdown,
+            }),
+            range: Some(token.range()),
+        }))
+    }
 
-                let sources = self.load_sources(&nodes)?;
-                all_trees.push((tree_type, nodes));
-                all_sources.extend(sources);
-            }
+    /// Returns the hover for the `#version` line of a top-level file, describing how the validator is
+    /// invoked for the file's stage, or `None` if `position` isn't on such a line.
+    fn version_hover(&self, path: &PathBuf, position: Position) -> Result<Option<Hover>> {
+        let source = self.document_text(path)?;
+        let line = match source.lines().nth(position.line as usize) {
+            Some(line) if RE_VERSION.is_match(line) => line,
+            _ => return Ok(None),
+        };
 
-            for tree in all_trees {
-                let view = {
-                let graph = self.graph.borrow();
-                    merge_views::generate_merge_list(&tree.1, &all_sources, &graph)
-                };
+        let node = self.graph.borrow_mut().find_node(path);
+        let is_top_level = match node {
+            Some(node) => self.graph.borrow().parent_node_indexes(node).is_empty(),
+            None => true,
+        };
+        if !is_top_level {
+            return Ok(None);
+        }
 
-                let stdout = match self.opengl_context.clone().validate(tree.0, view) {
-                    Some(s) => s,
-                    None => continue,
-                };
-                diagnostics.extend(self.parse_validator_stdout(uri, stdout, ""));
-            }
+        let tree_type = match path.extension() {
+            Some(ext) => TreeType::from_extension(ext)
+                .or_else(|| ext.to_str().and_then(|ext| self.config.unrecognized_roots.get(ext)).and_then(|behaviour| behaviour.tree_type())),
+            None => None,
+        };
+        let tree_type = match tree_type {
+            Some(tree_type) => tree_type,
+            None => return Ok(None),
         };
 
-        back_fill(&all_sources, &mut diagnostics);
-        Ok(diagnostics)
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("Validated as a {:?} shader by\n\n```\n{}\n```", tree_type, self.opengl_context.describe_invocation(tree_type)),
+            }),
+            range: Some(Range::new(
+                Position::new(position.line, 0),
+                Position::new(position.line, u32::try_from(line.len()).unwrap()),
+            )),
+        }))
     }
 
-    fn parse_validator_stdout(&self, uri: &PathBuf, stdout: String, _source: &str) -> HashMap<Url, Vec<Diagnostic>> {
-        let stdout_lines = stdout.split('\n');
-        let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::with_capacity(stdout_lines.count());
-        let stdout_lines = stdout.split('\n');
-        
-        for line in stdout_lines {
-            let diagnostic_capture = match RE_DIAGNOSTIC.captures(line) {
-                Some(d) => d,
-                None => continue
-            };
+    /// Returns the signatures of the function whose argument list `position` is in, looking
+    /// through user definitions in the include tree first and then the built-in functions.
+    fn signature_help_at(&self, path: &PathBuf, position: Position) -> Result<Option<SignatureHelp>> {
+        let source = self.document_text(path)?;
+        let (name, active_parameter) = match symbols::call_at_position(&source, position.line as usize, position.character as usize) {
+            Some(call) => call,
+            None => return Ok(None),
+        };
 
-            eprintln!("match {:?}", diagnostic_capture);
-            
-            let msg = diagnostic_capture.name("output").unwrap().as_str();
+        let sources = match self.load_include_tree_sources(path) {
+            Ok(sources) => sources,
+            Err(e) => {
+                warn!("error loading include tree for {:?}, falling back to the file itself: {}", path, e);
+                vec![(path.clone(), source)]
+            }
+        };
 
-            let line = match diagnostic_capture.name("linenum") {
-                Some(c) => match c.as_str().parse::<u32>() {
-                    Ok(i) => i,
-                    Err(_) => 0,
-                },
-                None => 0,
-            } - 2;
+        let mut signatures: Vec<(String, Vec<String>)> = sources.iter()
+            .flat_map(|(_, source)| symbols::find_function_signatures(source, &name))
+            .map(|signature| (signature.label(), signature.parameters))
+            .collect();
+        signatures.dedup();
+
+        if signatures.is_empty() {
+            signatures = builtins::builtin_signatures(&name).into_iter()
+                .map(|(return_type, parameters)| (
+                    format!("{} {}({})", return_type, name, parameters.join(", ")),
+                    parameters.iter().map(|p| p.to_string()).collect(),
+                ))
+                .collect();
+        }
 
-            // TODO: line matching maybe
-            /* let line_text = source_lines[line as usize];
-            let leading_whitespace = line_text.len() - line_text.trim_start().len(); */
+        if signatures.is_empty() {
+            return Ok(None);
+        }
 
-            let severity = match diagnostic_capture.name("severity") {
-                Some(c) => match c.as_str() {
-                    "error" => DiagnosticSeverity::Error,
-                    "warning" => DiagnosticSeverity::Warning,
-                    _ => DiagnosticSeverity::Information,
+        // prefer the first overload that has enough parameters for the argument under the cursor
+        let active_signature = signatures.iter()
+            .position(|(_, parameters)| parameters.len() > active_parameter)
+            .unwrap_or(0);
+
+        Ok(Some(SignatureHelp {
+            signatures: signatures.into_iter().map(|(label, parameters)| SignatureInformation {
+                label,
+                documentation: None,
+                parameters: Some(parameters.into_iter().map(|p| ParameterInformation {
+                    label: ParameterLabel::Simple(p),
+                    documentation: None,
+                }).collect()),
+                active_parameter: None,
+            }).collect(),
+            active_signature: Some(u32::try_from(active_signature).unwrap()),
+            active_parameter: Some(u32::try_from(active_parameter).unwrap()),
+        }))
+    }
+
+    /// Records the files currently in each tree that `path` belongs to, returning the files that
+    /// had diagnostics published as part of one of those trees but have since dropped out of it
+    /// and aren't part of any other known tree.
+    fn update_published_files(&mut self, path: &PathBuf) -> Vec<Url> {
+        let roots = match self.get_file_toplevel_ancestors(path) {
+            Ok(Some(roots)) => roots,
+            Ok(None) => vec![self.graph.borrow_mut().find_node(path).unwrap()],
+            Err(e) => {
+                error!("error getting ancestors for {:?}: {}", path, e);
+                return vec![];
+            }
+        };
+
+        let mut dropped: HashSet<Url> = HashSet::new();
+        for root in roots {
+            let nodes = match self.get_dfs_for_node(root) {
+                Ok(nodes) => nodes,
+                Err(e) => {
+                    error!("error getting tree for {:?}: {}", path, e);
+                    continue;
                 }
-                _ => DiagnosticSeverity::Information,
             };
 
-            let origin = match diagnostic_capture.name("filepath") {
-                Some(o) => {
-                    if o.as_str().to_string() == "0" {
-                        uri.to_str().unwrap().to_string()
-                    } else {
-                        o.as_str().to_string()
-                    }
-                },
-                None => uri.to_str().unwrap().to_string(),
-            };
+            let root_path = self.graph.borrow().get_node(root);
+            let files: HashSet<Url> = nodes.iter()
+                .filter_map(|(node, _)| Url::from_file_path(self.graph.borrow().get_node(*node)).ok())
+                .collect();
 
-            let diagnostic = Diagnostic {
-                range: Range::new(
-                    /* Position::new(line, leading_whitespace as u64),
-                    Position::new(line, line_text.len() as u64) */
-                    Position::new(line, 0),
-                    Position::new(line, 1000),
-                ),
-                code: None,
-                severity: Some(severity),
-                source: Some(consts::SOURCE.into()),
-                message: msg.trim().into(),
-                related_information: None,
-                tags: None,
-                code_description: Option::None,
-                data: Option::None,
-            };
+            if let Some(previous) = self.published_files.insert(root_path, files.clone()) {
+                dropped.extend(previous.difference(&files).cloned());
+            }
+        }
 
-            let origin_url = Url::from_file_path(origin).unwrap();
-            match diagnostics.get_mut(&origin_url) {
-                Some(d) => d.push(diagnostic),
-                None => {
-                    diagnostics.insert(origin_url, vec![diagnostic]);
-                },
-            };
+        dropped.into_iter()
+            .filter(|url| !self.published_files.values().any(|files| files.contains(url)))
+            .collect()
+    }
+
+    /// Returns the latest version of the document at `uri` that the client has sent, if it is open
+    /// and has no unsaved changes. Linting reads from disk, so the diagnostics of a document with
+    /// unsaved changes weren't computed from any version of it.
+    fn document_version(&self, uri: &Url) -> Option<i32> {
+        let path = PathBuf::from_url(uri.clone());
+        if self.unsaved_documents.contains(&path) {
+            return None;
         }
-        diagnostics
+        self.document_versions.get(&path).copied()
     }
 
-    pub fn get_dfs_for_node(&self, root: NodeIndex) -> Result<Vec<(NodeIndex, Option<NodeIndex>)>, dfs::error::CycleError> {
-        let graph_ref = self.graph.borrow();
+    /// Publishes `diagnostics` with `document_version`, or with the latest version of each open
+    /// document that has no unsaved changes if it is `None`, so that the client can drop diagnostics
+    /// of older versions.
+    pub fn publish_diagnostic(&self, diagnostics: HashMap<Url, Vec<Diagnostic>>, document_version: Option<i32>) {
+        trace!("DIAGNOSTICS:\n{:?}", diagnostics);
+        // the client may already have torn down its end
+        if self.shutting_down {
+            return;
+        }
+        for params in self.diagnostics_to_publish(diagnostics, document_version) {
+            if let Err(e) = self.endpoint.send_notification(PublishDiagnostics::METHOD, params) {
+                error!("failed to publish diagnostics: {:?}", e);
+            }
+        }
+    }
 
-        let dfs = dfs::Dfs::new(&graph_ref, root);
+    /// Returns the notifications that `publish_diagnostic` sends for `diagnostics`, one per file,
+    /// with anything below `mcglsl.minSeverity` left out and the rest sorted by position.
+    fn diagnostics_to_publish(&self, mut diagnostics: HashMap<Url, Vec<Diagnostic>>, document_version: Option<i32>) -> Vec<PublishDiagnosticsParams> {
+        if let Some(min_severity) = self.config.min_severity {
+            filter_diagnostics_by_severity(&mut diagnostics, min_severity.severity());
+        }
+        diagnostics.into_iter()
+            .map(|(uri, mut diagnostics)| {
+                sort_diagnostics(&mut diagnostics);
+                let version = document_version.or_else(|| self.document_version(&uri));
+                PublishDiagnosticsParams {
+                    uri,
+                    diagnostics,
+                    version,
+                }
+            })
+            .collect()
+    }
 
-        dfs.collect::<Result<Vec<_>, _>>()
+    fn set_lint_status(&self, diagnostics: &HashMap<Url, Vec<Diagnostic>>) {
+        // only count what was published
+        let (message, icon) = match self.config.min_severity {
+            Some(min_severity) => {
+                let mut diagnostics = diagnostics.clone();
+                filter_diagnostics_by_severity(&mut diagnostics, min_severity.severity());
+                diagnostics_summary(&diagnostics)
+            },
+            None => diagnostics_summary(diagnostics),
+        };
+        self.set_status("ready", message, icon);
     }
 
-    pub fn load_sources(&self, nodes: &[(NodeIndex, Option<NodeIndex>)]) -> Result<HashMap<PathBuf, String>> {
-        let mut sources = HashMap::new();
+    /// Switches over to `config`, returning whether the include graph has to be rebuilt for it.
+    fn apply_configuration(&mut self, mut config: configuration::Configuration) -> bool {
+        for rejected in config.reject_unsupported() {
+            error!("{}", rejected);
+            self.log_message(MessageType::Error, format!("Ignoring a setting: {}", rejected));
+        }
 
-        for node in nodes {
-            let graph = self.graph.borrow();
-            let path = graph.get_node(node.0);
+        let ignore_changed = config.ignore != self.config.ignore || config.use_gitignore != self.config.use_gitignore
+            || config.index_dry_run != self.config.index_dry_run || config.include_paths != self.config.include_paths;
+        let validator_changed = config.validator != self.config.validator;
+        if validator_changed {
+            self.set_validator(config.validator);
+        }
+        // a newly selected validator starts out without any arguments
+        if validator_changed || config.validator_args != self.config.validator_args {
+            self.set_validator_args(config.validator_args.clone());
+        }
+        log::set_max_level(config.log_level.level_filter());
+        if config.diagnostic_regex != self.config.diagnostic_regex {
+            self.set_diagnostic_regex(config.diagnostic_regex.as_deref());
+        }
+        // settings such as warningsAsErrors change what the same sources lint to
+        self.last_lint.clear();
+        self.config = config;
+        ignore_changed
+    }
 
-            if sources.contains_key(&path) {
-                continue;
-            }
+    /// Reads the settings that a shaderpack ships in the `.mcglslrc` of the first of its `shaders`
+    /// directories that has one, being a JSON or TOML table of the same keys as the `mcglsl` section
+    /// without the prefix.
+    fn load_project_config(&mut self) {
+        self.project_config = serde_json::Map::new();
+
+        // found again with the file's own ignore settings once they're applied
+        self.find_pack_roots();
+        let path = match self.pack_roots.iter().map(|root| root.join(consts::PROJECT_CONFIG_FILE)).find(|path| self.files.exists(path)) {
+            Some(path) => path,
+            None => return,
+        };
+        let parsed = read_source_from(self.files.as_ref(), &path)
+            .map_err(anyhow::Error::from)
+            .and_then(|(contents, _)| parse_project_config(&contents));
+        match parsed {
+            Ok(Value::Object(settings)) => {
+                info!("using the settings of {:?}", path);
+                self.project_config = settings;
+            },
+            Ok(_) => {
+                error!("{:?} isn't a table of settings", path);
+                self.log_message(MessageType::Error, format!("Ignoring {:?}, as it isn't a JSON object or TOML table of settings", path));
+            },
+            Err(e) => {
+                error!("error reading {:?}: {}", path, e);
+                self.log_message(MessageType::Error, format!("Failed to read {:?}: {}", path, e));
+            },
+        }
+    }
 
-            let source = match fs::read_to_string(&path) {
-                Ok(s) => s,
-                Err(e) => return Err(anyhow!("error reading {:?}: {}", path, e))
-            };
-            let source = RE_CRLF.replace_all(&source, "\n").to_string();
-            sources.insert(path.clone(), source);
+    /// Returns the client's `settings` on top of those of the shaderpack's `.mcglslrc`, so that
+    /// a setting is taken from the file unless the client sets it. VSCode sends the defaults of
+    /// settings that aren't set too, so a setting left at its default doesn't replace the file's.
+    fn with_project_config(&self, settings: &Value) -> Value {
+        let defaults = serde_json::to_value(configuration::Configuration::default()).unwrap_or(Value::Null);
+
+        let mut merged = self.project_config.clone();
+        if let Value::Object(settings) = settings {
+            for (key, value) in settings {
+                if merged.contains_key(key) && defaults.get(key) == Some(value) {
+                    continue;
+                }
+                merged.insert(key.clone(), value.clone());
+            }
         }
+        Value::Object(merged)
+    }
 
-        Ok(sources)
+    /// Swaps out the validator for the one selected by `kind`.
+    fn set_validator(&mut self, kind: configuration::ValidatorKind) {
+        match kind {
+            configuration::ValidatorKind::OpenGL => match opengl::OpenGLContext::new() {
+                Ok(context) => self.opengl_context = Rc::new(context),
+                Err(e) => {
+                    error!("failed to initialize OpenGL, keeping the current validator: {}", e);
+                    self.log_message(MessageType::Error, format!("Failed to initialize OpenGL, keeping the current validator: {}", e));
+                },
+            },
+            #[cfg(feature = "glslang-lib")]
+            configuration::ValidatorKind::GlslangLib => match glslang::GlslangLibValidator::new() {
+                Some(validator) => self.opengl_context = Rc::new(validator),
+                None => {
+                    error!("failed to initialize glslang, keeping the current validator");
+                    self.log_message(MessageType::Error, "Failed to initialize glslang, keeping the current validator");
+                },
+            },
+            #[cfg(not(feature = "glslang-lib"))]
+            configuration::ValidatorKind::GlslangLib => {
+                error!("the glslang-lib validator was selected but the server was built without the glslang-lib feature");
+                self.log_message(MessageType::Error, "The glslang-lib validator was selected but the server was built without the glslang-lib feature");
+            }
+            configuration::ValidatorKind::Static => self.opengl_context = Rc::new(static_validator::StaticValidator),
+        }
     }
 
-    fn get_file_toplevel_ancestors(&self, uri: &PathBuf) -> Result<Option<Vec<petgraph::stable_graph::NodeIndex>>> {
-        let curr_node = match self.graph.borrow_mut().find_node(uri) {
-            Some(n) => n,
-            None => return Err(anyhow!("node not found {:?}", uri)),
+    /// Compiles `pattern` from `mcglsl.diagnosticRegex` to parse validator output with, falling
+    /// back to the format of the OpenGL driver if it is unset or invalid.
+    fn set_diagnostic_regex(&mut self, pattern: Option<&str>) {
+        self.diagnostic_regex = match pattern.map(compile_diagnostic_regex) {
+            Some(Ok(regex)) => Some(regex),
+            Some(Err(e)) => {
+                error!("invalid diagnostic regex: {}", e);
+                self.log_message(MessageType::Error, format!("mcglsl.diagnosticRegex is invalid, using the default: {}", e));
+                None
+            },
+            None => None,
         };
-        let roots = self.graph.borrow().collect_root_ancestors(curr_node);
-        if roots.is_empty() {
-            return Ok(None);
-        }
-        Ok(Some(roots))
     }
 
-    pub fn publish_diagnostic(&self, diagnostics: HashMap<Url, Vec<Diagnostic>>, document_version: Option<i32>) {
-        eprintln!("DIAGNOSTICS:\n{:?}", diagnostics);
-        for (uri, diagnostics) in diagnostics {
-            self.endpoint.send_notification(PublishDiagnostics::METHOD, PublishDiagnosticsParams {
-                uri,
-                diagnostics,
-                version: document_version,
-            }).expect("failed to publish diagnostics");
+    /// Hands the per-stage arguments from `mcglsl.validatorArgs` to the current validator.
+    fn set_validator_args(&mut self, args: HashMap<TreeType, Vec<String>>) {
+        match Rc::get_mut(&mut self.opengl_context) {
+            Some(validator) => validator.set_stage_args(args),
+            None => warn!("the validator is in use, its per-stage arguments were not updated"),
         }
     }
 
@@ -509,6 +2654,15 @@ impl MinecraftShaderLanguageServer {
             icon: Some(icon.into()),
         }).unwrap_or(());
     }
+
+    /// Sends `message` to the client's output channel through `window/logMessage`, for events
+    /// that users may want to see without digging through the server's stderr.
+    fn log_message(&self, typ: MessageType, message: impl Into<String>) {
+        self.endpoint.send_notification(LogMessage::METHOD, LogMessageParams {
+            typ,
+            message: message.into(),
+        }).unwrap_or(());
+    }
 }
 
 impl LanguageServerHandling for MinecraftShaderLanguageServer {
@@ -516,7 +2670,22 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         self.wait.add(1);
 
         let mut capabilities = ServerCapabilities::default();
-        capabilities.hover_provider = None;
+        capabilities.hover_provider = Some(HoverProviderCapability::Simple(true));
+        capabilities.rename_provider = Some(OneOf::Left(true));
+        capabilities.signature_help_provider = Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".into(), ",".into()]),
+            retrigger_characters: None,
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+        });
+        capabilities.code_action_provider = Some(CodeActionProviderCapability::Simple(true));
+        capabilities.document_formatting_provider = Some(OneOf::Left(true));
+        capabilities.document_range_formatting_provider = Some(OneOf::Left(true));
+        capabilities.document_on_type_formatting_provider = Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".into(),
+            more_trigger_character: Some(vec!["\n".into()]),
+        });
         capabilities.document_link_provider = Some(DocumentLinkOptions {
             resolve_provider: None,
             work_done_progress_options: WorkDoneProgressOptions {
@@ -524,11 +2693,19 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
             },
         });
         capabilities.execute_command_provider = Some(ExecuteCommandOptions {
-            commands: vec!["graphDot".into()],
+            commands: vec!["graphDot".into(), "whyIncluded".into(), "lintWithDefines".into(), "rebuildGraph".into(), "mergedView".into(), "dfsDump".into(), "listRoots".into(), "mergedDefinition".into(), "lintAll".into(), "glInfo".into(), "graphStats".into(), "ancestors".into(), "treeOutline".into(), "validateSnippet".into(), "affectedPrograms".into(), "unusedIncludes".into(), "createFile".into(), "includeExtensionEdit".into(), "semanticTokens".into(), "foldingRanges".into(), "documentColors".into(), "colorPresentations".into(), "prepareRename".into(), "lintDueSave".into(), "workspaceFoldersChanged".into()],
             work_done_progress_options: WorkDoneProgressOptions {
                 work_done_progress: None,
             },
         });
+        // rust_lsp doesn't route these requests, so the client registers its own providers for
+        // them, backed by the commands of the same names
+        capabilities.experimental = Some(serde_json::json!({
+            "semanticTokensLegend": semantic_tokens::legend(),
+            "foldingRangeProvider": true,
+            "colorProvider": true,
+            "prepareRenameProvider": true,
+        }));
         capabilities.text_document_sync = Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
                 open_close: Some(true),
@@ -541,9 +2718,10 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
             },
         ));
 
-        let root = match params.root_uri {
-            Some(uri) => PathBuf::from_url(uri),
-            None => {
+        let roots = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) if !folders.is_empty() => folders.into_iter().map(|folder| PathBuf::from_url(folder.uri)).collect(),
+            (_, Some(uri)) => vec![PathBuf::from_url(uri)],
+            _ => {
                 completable.complete(Err(MethodError {
                     code: 42069,
                     message: "Must be in workspace".into(),
@@ -560,9 +2738,31 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
             server_info: None,
         }));
 
+        if let Some(e) = self.validator_error.take() {
+            self.endpoint.send_notification(ShowMessage::METHOD, ShowMessageParams {
+                typ: MessageType::Error,
+                message: format!("Failed to initialize OpenGL, so shaders are only checked for unbalanced brackets, comments and #ifs: {}", e),
+            }).expect("failed to send popup/show message notification");
+        }
+
         self.set_status("loading", "Building dependency graph...", "$(loading~spin)");
 
-        self.root = root;
+        self.roots = roots;
+
+        // the client's settings arrive later, and are applied on top of these
+        self.load_project_config();
+        if !self.project_config.is_empty() {
+            match serde_json::from_value::<configuration::Configuration>(self.with_project_config(&Value::Null)) {
+                Ok(config) => {
+                    // the graph is built next anyway
+                    self.apply_configuration(config);
+                },
+                Err(e) => {
+                    error!("error parsing {}: {}", consts::PROJECT_CONFIG_FILE, e);
+                    self.log_message(MessageType::Error, format!("Failed to parse {}: {}", consts::PROJECT_CONFIG_FILE, e));
+                },
+            }
+        }
 
         self.gen_initial_graph();
 
@@ -570,18 +2770,33 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     }
 
     fn shutdown(&mut self, _: (), completable: LSCompletable<()>) {
-        eprintln!("shutting down language server...");
+        info!("shutting down language server...");
+        self.shutting_down = true;
+        self.pending_save_lints.clear();
+        self.stop_save_lint_timers();
         completable.complete(Ok(()));
     }
 
     fn exit(&mut self, _: ()) {
+        // the client may exit without asking to shut down first
+        self.stop_save_lint_timers();
         self.endpoint.request_shutdown();
     }
 
     fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams) {
-        //let config = params.settings.as_object().unwrap().get("mcglsl").unwrap();
-
-        eprintln!("{:?}", params.settings.as_object().unwrap());
+        debug!("{:?}", params.settings.as_object().unwrap());
+
+        if let Some(settings) = params.settings.get("mcglsl") {
+            match serde_json::from_value::<configuration::Configuration>(self.with_project_config(settings)) {
+                Ok(config) => {
+                    // the initial graph is built before the configuration arrives
+                    if self.apply_configuration(config) && !self.roots.is_empty() {
+                        self.rebuild_graph();
+                    }
+                },
+                Err(e) => error!("error parsing configuration: {}", e),
+            }
+        }
 
         self.wait.done();
     }
@@ -589,34 +2804,76 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) {
         //eprintln!("opened doc {}", params.text_document.uri);
         let path = PathBuf::from_url(params.text_document.uri);
-        if !path.starts_with(&self.root) {
+        // a document can be reopened with the unsaved changes of an earlier session
+        match read_source_from(self.files.as_ref(), &path) {
+            Ok((source, _)) if source == params.text_document.text => self.unsaved_documents.remove(&path),
+            _ => self.unsaved_documents.insert(path.clone()),
+        };
+        self.documents.insert(path.clone(), params.text_document.text);
+        self.document_versions.insert(path.clone(), params.text_document.version);
+        if self.root_for(&path).is_none() || self.shutting_down {
             return
         }
         if self.graph.borrow_mut().find_node(&path) == None {
             self.add_file_and_includes_to_graph(&path);
         }
-        match self.lint(&path) {
-            Ok(diagnostics) => self.publish_diagnostic(diagnostics, None),
-            Err(e) => eprintln!("error linting: {}", e),
+        if !self.in_shaderpack(&path) {
+            return
+        }
+        self.note_recent_root(&path);
+        if !self.lints_on_open(&path) {
+            return
+        }
+        match self.lint_if_changed(&path) {
+            Ok(diagnostics) => {
+                self.set_lint_status(&diagnostics);
+                self.publish_diagnostic(diagnostics, None)
+            },
+            Err(e) => {
+                error!("error linting: {}", e);
+                self.log_message(MessageType::Error, format!("Failed to validate {:?}: {}", path, e));
+            },
         }
+        self.update_published_files(&path);
     }
 
-    fn did_change_text_document(&mut self, _: DidChangeTextDocumentParams) {}
+    fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams) {
+        // documents are synced in full, so the last change holds the whole text
+        let path = PathBuf::from_url(params.text_document.uri);
+        self.merge_cache.borrow_mut().invalidate(&path);
+        self.document_versions.insert(path.clone(), params.text_document.version);
+        self.unsaved_documents.insert(path.clone());
+        if let Some(change) = params.content_changes.into_iter().last() {
+            self.documents.insert(path, change.text);
+        }
+        self.lint_pending_saves();
+    }
 
-    fn did_close_text_document(&mut self, _: DidCloseTextDocumentParams) {}
+    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        self.documents.remove(&path);
+        self.document_versions.remove(&path);
+        self.unsaved_documents.remove(&path);
+    }
 
     fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams) {
         //eprintln!("saved doc {}", params.text_document.uri);
 
         let path = PathBuf::from_url(params.text_document.uri);
-        if !path.starts_with(&self.root) {
+        self.merge_cache.borrow_mut().invalidate(&path);
+        self.unsaved_documents.remove(&path);
+        if self.root_for(&path).is_none() {
             return
         }
         self.update_includes(&path);
-        
-        match self.lint(&path) {
-            Ok(diagnostics) => self.publish_diagnostic(diagnostics, None),
-            Err(e) => eprintln!("error linting: {}", e),
+        if !self.in_shaderpack(&path) {
+            return
+        }
+
+        let lint_now = !self.debounce_save_lint(&path);
+        self.lint_pending_saves();
+        if lint_now {
+            self.lint_saved(&path);
         }
     }
 
@@ -630,21 +2887,36 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         completable.complete(Err(Self::error_not_available(())));
     }
 
-    fn hover(&mut self, _: TextDocumentPositionParams, _: LSCompletable<Hover>) {
+    fn hover(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<Hover>) {
         self.wait.wait();
-        /* completable.complete(Ok(Hover{
-            contents: HoverContents::Markup(MarkupContent{
-                kind: MarkupKind::Markdown,
-                value: String::from("# Hello World"),
-            }),
-            range: None,
-        })); */
+        let path = PathBuf::from_url(params.text_document.uri);
+        let hover = match self.version_hover(&path, params.position) {
+            Ok(None) => self.define_hover(&path, params.position),
+            hover => hover,
+        };
+        match hover {
+            Ok(Some(hover)) => completable.complete(Ok(hover)),
+            Ok(None) => completable.complete(Ok(Hover {
+                contents: HoverContents::Array(vec![]),
+                range: None,
+            })),
+            Err(e) => {
+                error!("failed to get hover: {}", e);
+                completable.complete(Err(MethodError::new(32420, e.to_string(), ())))
+            },
+        }
     }
 
     fn execute_command(&mut self, params: ExecuteCommandParams, completable: LSCompletable<Option<Value>>) {
-        match self.command_provider.as_ref().unwrap().execute(&params.command, params.arguments, &self.root) {
+        let result = self.run_command(&params.command, params.arguments);
+
+        match result {
+            // the client runs these by itself, as often as on every edit, so they don't pop up a message
+            Ok(resp) if consts::BACKGROUND_COMMANDS.contains(&params.command.as_str()) => {
+                completable.complete(Ok(Some(resp)))
+            },
             Ok(resp) => {
-                eprintln!("executed {} successfully", params.command);
+                info!("executed {} successfully", params.command);
                 self.endpoint.send_notification(ShowMessage::METHOD, ShowMessageParams {
                     typ: MessageType::Info,
                     message: format!("Command {} executed successfully.", params.command),
@@ -656,14 +2928,26 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
                     typ: MessageType::Error,
                     message: format!("Failed to execute `{}`. Reason: {}", params.command, err),
                 }).expect("failed to send popup/show message notification");
-                eprintln!("failed to execute {}: {}", params.command, err);
+                error!("failed to execute {}: {}", params.command, err);
                 completable.complete(Err(MethodError::new(32420, err.to_string(), ())))
             },
         }
     }
 
-    fn signature_help(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<SignatureHelp>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn signature_help(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<SignatureHelp>) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        match self.signature_help_at(&path, params.position) {
+            Ok(Some(help)) => completable.complete(Ok(help)),
+            Ok(None) => completable.complete(Ok(SignatureHelp {
+                signatures: vec![],
+                active_signature: None,
+                active_parameter: None,
+            })),
+            Err(e) => {
+                error!("failed to get signature help: {}", e);
+                completable.complete(Err(MethodError::new(32420, e.to_string(), ())))
+            },
+        }
     }
 
     fn goto_definition(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<Vec<Location>>) {
@@ -686,8 +2970,11 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         completable.complete(Err(Self::error_not_available(())));
     }
 
-    fn code_action(&mut self, _: CodeActionParams, completable: LSCompletable<Vec<Command>>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn code_action(&mut self, params: CodeActionParams, completable: LSCompletable<Vec<Command>>) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        let mut actions = self.create_file_actions(&path, params.range, &params.context.diagnostics);
+        actions.extend(self.include_extension_actions(&path, &params.context.diagnostics));
+        completable.complete(Ok(actions));
     }
 
     fn code_lens(&mut self, _: CodeLensParams, completable: LSCompletable<Vec<CodeLens>>) {
@@ -699,54 +2986,15 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
     }
 
     fn document_link(&mut self, params: DocumentLinkParams, completable: LSCompletable<Vec<DocumentLink>>) {
-        eprintln!("document link file: {:?}", params.text_document.uri.to_file_path().unwrap());
-        // node for current document
+        trace!("document link file: {:?}", params.text_document.uri.to_file_path().unwrap());
+        // path of the current document
         let curr_doc = params
             .text_document
             .uri
             .to_file_path()
             .unwrap();
-        let node = match self.graph.borrow_mut().find_node(&curr_doc) {
-            Some(n) => n,
-            None => {
-                completable.complete(Ok(vec![]));
-                return
-            },
-        };
-
-        let edges: Vec<DocumentLink> = self
-            .graph
-            .borrow()
-            .child_node_indexes(node)
-            .into_iter()
-            .filter_map(|child| {
-                let graph = self.graph.borrow();
-                let value = graph.get_edge_meta(node, child);
-                let path = graph.get_node(child);
-                let url = match Url::from_file_path(&path) {
-                    Ok(url) => url,
-                    Err(e) => {
-                        eprintln!("error converting {:?} into url: {:?}", path, e);
-                        return None;
-                    }
-                };
-
-                Some(DocumentLink {
-                    range: Range::new(
-                        Position::new(
-                            u32::try_from(value.line).unwrap(),
-                            u32::try_from(value.start).unwrap()),
-                        Position::new(
-                            u32::try_from(value.line).unwrap(),
-                            u32::try_from(value.end).unwrap()),
-                    ),
-                    target: Some(url),
-                    //tooltip: Some(url.path().to_string().strip_prefix(self.root.clone().unwrap().as_str()).unwrap().to_string()),
-                    tooltip: None,
-                    data: None,
-                })
-            }).collect();
-        eprintln!("links: {:?}", edges);
+        let edges = self.document_links(&curr_doc);
+        trace!("links: {:?}", edges);
         completable.complete(Ok(edges));
     }
 
@@ -754,19 +3002,63 @@ impl LanguageServerHandling for MinecraftShaderLanguageServer {
         completable.complete(Err(Self::error_not_available(())));
     }
 
-    fn formatting(&mut self, _: DocumentFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn formatting(&mut self, params: DocumentFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        let source = match self.document_text(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                error!("error reading {:?} for formatting: {}", path, e);
+                completable.complete(Ok(vec![]));
+                return
+            }
+        };
+
+        completable.complete(Ok(formatting::format_document_edit(&source, &params.options).into_iter().collect()));
     }
 
-    fn range_formatting(&mut self, _: DocumentRangeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn range_formatting(&mut self, params: DocumentRangeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
+        let path = PathBuf::from_url(params.text_document.uri);
+        let source = match self.document_text(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                error!("error reading {:?} for range formatting: {}", path, e);
+                completable.complete(Ok(vec![]));
+                return
+            }
+        };
+
+        let start = params.range.start.line as usize;
+        // a selection ending at the start of a line doesn't cover any of it
+        let end = match params.range.end {
+            Position { line, character: 0 } if line > params.range.start.line => line as usize - 1,
+            Position { line, .. } => line as usize,
+        };
+        completable.complete(Ok(formatting::format_range(&source, start, end, &params.options)));
     }
 
-    fn on_type_formatting(&mut self, _: DocumentOnTypeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
+        let path = PathBuf::from_url(params.text_document_position.text_document.uri);
+        let source = match self.document_text(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                error!("error reading {:?} for on-type formatting: {}", path, e);
+                completable.complete(Ok(vec![]));
+                return
+            }
+        };
+
+        let line = params.text_document_position.position.line as usize;
+        completable.complete(Ok(formatting::indent_line(&source, line, &params.options).into_iter().collect()));
     }
 
-    fn rename(&mut self, _: RenameParams, completable: LSCompletable<WorkspaceEdit>) {
-        completable.complete(Err(Self::error_not_available(())));
+    fn rename(&mut self, params: RenameParams, completable: LSCompletable<WorkspaceEdit>) {
+        let path = PathBuf::from_url(params.text_document_position.text_document.uri);
+        match self.rename_symbol(&path, params.text_document_position.position, &params.new_name) {
+            Ok(edit) => completable.complete(Ok(edit)),
+            Err(e) => {
+                error!("failed to rename: {}", e);
+                completable.complete(Err(MethodError::new(32420, e.to_string(), ())))
+            },
+        }
     }
 }
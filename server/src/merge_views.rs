@@ -1,13 +1,26 @@
-use std::{collections::{HashMap, LinkedList, VecDeque}, path::PathBuf};
-use std::iter::Peekable;
+use std::{collections::{HashMap, HashSet, LinkedList, VecDeque}, path::PathBuf};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::iter::{FromIterator, Peekable};
 use std::cmp::min;
+use std::rc::Rc;
 
 use core::slice::Iter;
 
 use petgraph::stable_graph::NodeIndex;
 
+use regex::Regex;
+
+use lazy_static::lazy_static;
+
 use crate::graph::CachedStableGraph;
 
+lazy_static! {
+    static ref RE_LINE_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*line\s+\d+"#).unwrap();
+    static ref RE_PRAGMA_ONCE: Regex = Regex::new(r#"(?m)^\s*#\s*pragma\s+once\s*$"#).unwrap();
+    static ref RE_GENERATED_LINE_DIRECTIVE: Regex = Regex::new(r#"^#line (\d+) "(.*)"$"#).unwrap();
+}
+
 /// FilialTuple represents a tuple with a parent at index 0 
 /// and a child at index 1. Parent can be nullable in the case of
 /// the child being a top level node in the tree.
@@ -20,10 +33,83 @@ impl From<(Option<&NodeIndex>, NodeIndex)> for FilialTuple {
     }
 }
 
+/// A merged source along with the file and 0-indexed line that each of its lines came from, as per `line_map`.
+pub struct MergedView {
+    pub source: String,
+    // used to place the diagnostics found in the merged source itself, as those of the validator
+    // are placed by it echoing the #line directives back
+    pub line_map: Vec<Option<(PathBuf, usize)>>,
+}
+
+/// Caches merged views by a hash of their tree's files, include edges and file contents, so
+/// that linting an unchanged tree again doesn't merge it again. Entries are keyed by content
+/// so a stale entry is never returned, but `invalidate` should still be called when a file
+/// changes so that the entries it was part of don't pile up.
+#[derive(Default)]
+pub struct MergeCache {
+    entries: HashMap<u64, (HashSet<PathBuf>, Rc<MergedView>)>,
+}
+
+impl MergeCache {
+    /// Returns the merged view of the tree of `nodes`, merging it only if it isn't cached.
+    pub fn get_or_merge(
+        &mut self,
+        nodes: &[(NodeIndex, Option<NodeIndex>)],
+        sources: &HashMap<PathBuf, String>,
+        graph: &CachedStableGraph,
+        dedup: bool,
+    ) -> Rc<MergedView> {
+        let key = tree_hash(nodes, sources, graph, dedup);
+        if let Some((_, view)) = self.entries.get(&key) {
+            return Rc::clone(view);
+        }
+
+        let root = graph.get_node(nodes[0].0);
+        let source = generate_merge_list(nodes, sources, graph, dedup);
+        let view = Rc::new(MergedView {
+            line_map: line_map(&source, &root),
+            source,
+        });
+
+        let files = nodes.iter().map(|(node, _)| graph.get_node(*node)).collect();
+        self.entries.insert(key, (files, Rc::clone(&view)));
+        view
+    }
+
+    /// Drops every cached view that `path` is part of.
+    pub fn invalidate(&mut self, path: &PathBuf) {
+        self.entries.retain(|_, (files, _)| !files.contains(path));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn tree_hash(nodes: &[(NodeIndex, Option<NodeIndex>)], sources: &HashMap<PathBuf, String>, graph: &CachedStableGraph, dedup: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    dedup.hash(&mut hasher);
+    for (child, parent) in nodes {
+        let path = graph.get_node(*child);
+        path.hash(&mut hasher);
+        sources.get(&path).hash(&mut hasher);
+        if let Some(parent) = parent {
+            graph.get_node(*parent).hash(&mut hasher);
+            graph.get_edge_meta(*parent, *child).line.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Merges the tree of `nodes` into a single source. With `dedup`, every file is only merged in
+/// at its first include in the tree and any further includes of it are dropped, as Optifine does.
+/// Files with a `#pragma once` are only merged in once either way, for shaders shared with engines
+/// that support it.
 pub fn generate_merge_list<'a>(
     nodes: &'a [(NodeIndex, Option<NodeIndex>)],
     sources: &'a HashMap<PathBuf, String>, 
-    graph: &'a CachedStableGraph
+    graph: &'a CachedStableGraph,
+    dedup: bool,
 ) -> String {
     let mut line_directives: Vec<String> = Vec::new();
 
@@ -36,8 +122,10 @@ pub fn generate_merge_list<'a>(
 
     let mut nodes_iter = nodes.iter().peekable();
 
-    // invariant: nodes_iter always has _at least_ one element. Can't save a not-file :B
-    let first = nodes_iter.next().unwrap().0;
+    let first = match nodes_iter.next() {
+        Some(first) => first.0,
+        None => return String::new(),
+    };
     let first_path = graph.get_node(first);
 
     last_offset_set.insert(FilialTuple(None, first), 0);
@@ -45,13 +133,17 @@ pub fn generate_merge_list<'a>(
     // stack to keep track of the depth first traversal
     let mut stack = VecDeque::<NodeIndex>::new();
 
-    create_merge_views(&mut nodes_iter, &mut merge_list, &mut last_offset_set, graph, sources, &mut line_directives, &mut stack);
+    // the files merged in so far
+    let mut merged_files = HashSet::from_iter(vec![first]);
+
+    create_merge_views(&mut nodes_iter, &mut merge_list, &mut last_offset_set, graph, sources, &mut line_directives, &mut stack, &mut merged_files, dedup);
 
     // now we add a view of the remainder of the root file
     let offset = *last_offset_set.get(&FilialTuple(None, first)).unwrap();
 
-    let len = sources.get(&first_path).unwrap().len();
-    merge_list.push_back(&sources.get(&first_path).unwrap()[min(offset, len) ..]);
+    // an empty or unreadable root has nothing left to add
+    let first_source = sources.get(first_path).map_or("", |s| s.as_str());
+    merge_list.push_back(&first_source[min(offset, first_source.len())..]);
 
     let total_len = merge_list.iter().fold(0, |a, b| {
        a + b.len()
@@ -65,6 +157,7 @@ pub fn generate_merge_list<'a>(
     merged
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_merge_views<'a>(
     nodes: &mut Peekable<Iter<(NodeIndex, Option<NodeIndex>)>>,
     merge_list: &mut LinkedList<&'a str>,
@@ -73,6 +166,8 @@ fn create_merge_views<'a>(
     sources: &'a HashMap<PathBuf, String>,
     line_directives: &mut Vec<String>,
     stack: &mut VecDeque<NodeIndex>,
+    merged_files: &mut HashSet<NodeIndex>,
+    dedup: bool,
 ) {
     
     loop {
@@ -93,6 +188,17 @@ fn create_merge_views<'a>(
         
         let offset = *last_offset_set.insert((stack.back(), parent).into(), char_following_line).get_or_insert(0);
         merge_list.push_back(&parent_source[offset..char_for_line]);
+
+        if !merged_files.insert(child) && (dedup || has_pragma_once(sources.get(&child_path).unwrap())) {
+            // the include line is dropped, so only the rest of the parent needs renumbering
+            add_skipped_include_line_directive(edge.line+2, &parent_path, merge_list, line_directives);
+            skip_subtree(nodes, child);
+            if nodes.peek().is_some() && stack.contains(&nodes.peek().unwrap().1.unwrap()) {
+                return;
+            }
+            continue;
+        }
+
         add_opening_line_directive(&child_path, merge_list, line_directives);
 
         match nodes.peek() {
@@ -120,7 +226,7 @@ fn create_merge_views<'a>(
                 }
                 
                 stack.push_back(parent);
-                create_merge_views(nodes, merge_list, last_offset_set, graph, sources, line_directives, stack);
+                create_merge_views(nodes, merge_list, last_offset_set, graph, sources, line_directives, stack, merged_files, dedup);
                 stack.pop_back();
 
                 let offset = *last_offset_set.get(&FilialTuple(Some(parent), child)).unwrap();
@@ -164,6 +270,70 @@ fn create_merge_views<'a>(
     }
 }
 
+/// Returns whether `source` asks to only be included once with `#pragma once`.
+fn has_pragma_once(source: &str) -> bool {
+    RE_PRAGMA_ONCE.is_match(source)
+}
+
+/// Consumes the nodes below `root` in the traversal, which directly follow it.
+fn skip_subtree(nodes: &mut Peekable<Iter<(NodeIndex, Option<NodeIndex>)>>, root: NodeIndex) {
+    let mut subtree = HashSet::new();
+    subtree.insert(root);
+    while let Some(next) = nodes.peek() {
+        if !subtree.contains(&next.1.unwrap()) {
+            break;
+        }
+        subtree.insert(next.0);
+        nodes.next();
+    }
+}
+
+/// Maps every line of the `merged` source of the tree rooted at `root` back to the 0-indexed
+/// line of the file it came from, by following the `#line` directives inserted during merging.
+/// The directives themselves map to `None`.
+pub fn line_map(merged: &str, root: &PathBuf) -> Vec<Option<(PathBuf, usize)>> {
+    let mut file = root.clone();
+    let mut line = 0;
+
+    merged.lines().map(|text| {
+        match RE_GENERATED_LINE_DIRECTIVE.captures(text) {
+            Some(cap) => {
+                // #line is 1 indexed and references the *following* line
+                line = cap[1].parse::<usize>().unwrap_or(1).saturating_sub(1);
+                file = PathBuf::from(cap[2].replace("\\\\", "\\"));
+                None
+            },
+            None => {
+                line += 1;
+                Some((file.clone(), line - 1))
+            }
+        }
+    }).collect()
+}
+
+/// Rewrites any `#line` directives already present in `source` so that they point back at the
+/// real following line of `path`. Otherwise the validator would report every line after one
+/// of them relative to the directive rather than the file, putting diagnostics on the wrong line.
+pub fn rewrite_line_directives(path: &PathBuf, source: &str) -> String {
+    if !source.lines().any(|line| RE_LINE_DIRECTIVE.is_match(line)) {
+        return source.to_string();
+    }
+
+    let mut rewritten = String::with_capacity(source.len());
+    for (n, line) in source.split('\n').enumerate() {
+        if n > 0 {
+            rewritten.push('\n');
+        }
+        if RE_LINE_DIRECTIVE.is_match(line) {
+            // +2 because n is 0 indexed but #line is 1 indexed and references the *following* line
+            rewritten.push_str(&format!("#line {} \"{}\"", n+2, path.to_str().unwrap().replace("\\", "\\\\")));
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+    rewritten
+}
+
 // returns the character offset + 1 of the end of line number `line` and the character
 // offset + 1 for the end of the line after the previous one
 fn char_offset_for_line(line_num: usize, source: &str) -> (usize, usize) {
@@ -202,6 +372,13 @@ fn add_closing_line_directive(line: usize, path: &PathBuf, merge_list: &mut Link
     unsafe_get_and_insert(merge_list, line_directives);
 }
 
+fn add_skipped_include_line_directive(line: usize, path: &PathBuf, merge_list: &mut LinkedList<&str>, line_directives: &mut Vec<String>) {
+    // the preceding view always ends in a newline, as it stops at the start of the include line
+    let line_directive = format!("#line {} \"{}\"\n", line, path.to_str().unwrap().replace("\\", "\\\\"));
+    line_directives.push(line_directive);
+    unsafe_get_and_insert(merge_list, line_directives);
+}
+
 fn unsafe_get_and_insert(merge_list: &mut LinkedList<&str>, line_directives: &Vec<String>) {
     // :^)
     unsafe {
@@ -1,39 +1,230 @@
 use std::ptr;
+use std::collections::HashMap;
+use std::error::Error as StdError;
 use std::ffi::{CString, CStr};
+use std::fmt::{Display, Formatter};
+use std::panic;
+use std::time::Duration;
+
+use regex::Regex;
+
+use serde::Serialize;
+
+use anyhow::{Result, anyhow};
+
+use lazy_static::lazy_static;
+
+use log::{info, warn};
+
+use crate::TreeType;
+
+lazy_static! {
+    static ref RE_VERSION_LINE: Regex = Regex::new(r#"(?m)^\s*#version .*$"#).unwrap();
+}
 
 #[cfg(test)]
 use mockall::automock;
 #[cfg_attr(test, automock)]
 pub trait ShaderValidator {
+    /// Validates the merged `source` as a shader of `tree_type`.
     fn validate(&self, tree_type: super::TreeType, source: String) -> Option<String>;
+
+    /// Like `validate`, but gives up once validating has taken longer than `timeout`, from `mcglsl.validateTimeoutMs`.
+    fn validate_with_timeout(&self, tree_type: super::TreeType, source: String, timeout: Duration) -> Result<Option<String>, ValidationError>;
+
+    /// Describes the environment that shaders are validated in.
+    fn info(&self) -> ValidatorInfo;
+
+    /// Describes how a shader of `tree_type` is validated, being the equivalent command line or
+    /// API calls along with the stage and any extra arguments, for debugging validation problems.
+    fn describe_invocation(&self, tree_type: TreeType) -> String;
+
+    /// Replaces the extra arguments, from `mcglsl.validatorArgs`, that shaders of each stage are validated with.
+    fn set_stage_args(&mut self, args: HashMap<TreeType, Vec<String>>);
+}
+
+/// The vendor, renderer and versions reported by a validator, as returned by the `glInfo` command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub glsl_version: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// Validating took longer than the given timeout.
+    TimedOut(Duration),
+    /// That many earlier validations that timed out are still running, and no more may be started until one finishes.
+    Busy(usize),
+}
+
+impl StdError for ValidationError {}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::TimedOut(timeout) => write!(f, "validation timed out after {}ms", timeout.as_millis()),
+            ValidationError::Busy(workers) => write!(f, "{} earlier validations are still running after timing out", workers),
+        }
+    }
+}
+
+/// Returns the directive that a single validator argument stands for, if it is a supported one:
+/// `-DNAME[=VALUE]` defines a macro, `-UNAME` undefines one and `--extension=NAME[:behaviour]`
+/// enables (or sets the behaviour of) an extension.
+fn arg_directive(arg: &str) -> Option<String> {
+    if let Some(define) = arg.strip_prefix("-D") {
+        let mut parts = define.splitn(2, '=');
+        let name = parts.next().unwrap();
+        return match parts.next() {
+            Some(value) => Some(format!("#define {} {}", name, value)),
+            None => Some(format!("#define {}", name)),
+        };
+    }
+    if let Some(name) = arg.strip_prefix("-U") {
+        return Some(format!("#undef {}", name));
+    }
+    if let Some(extension) = arg.strip_prefix("--extension=") {
+        let mut parts = extension.splitn(2, ':');
+        let name = parts.next().unwrap();
+        return Some(format!("#extension {} : {}", name, parts.next().unwrap_or("enable")));
+    }
+    None
+}
+
+/// Describes the arguments that a stage is validated with, for `describe_invocation`.
+pub fn describe_stage_args(args: Option<&Vec<String>>) -> String {
+    match args {
+        Some(args) if !args.is_empty() => format!(", with arguments {}", args.join(" ")),
+        _ => String::new(),
+    }
+}
+
+/// Applies a stage's validator arguments to `source`. Neither the driver nor glslang-as-a-library
+/// take a command line, so the arguments are turned into directives that are inserted directly
+/// after the `#version` directive, followed by a `#line` directive that keeps the line numbers
+/// reported by the validator the same as without them. Unsupported arguments are skipped.
+pub fn apply_stage_args(source: &str, args: &[String]) -> String {
+    let directives: Vec<String> = args.iter()
+        .filter_map(|arg| {
+            let directive = arg_directive(arg);
+            if directive.is_none() {
+                warn!("skipping unsupported validator argument {:?}", arg);
+            }
+            directive
+        })
+        .collect();
+    if directives.is_empty() {
+        return source.to_string();
+    }
+
+    match RE_VERSION_LINE.find(source) {
+        // #line N numbers the directive itself, keeping the following lines numbered as they were
+        Some(m) => format!("{}\n{}\n#line {}{}", &source[..m.end()], directives.join("\n"),
+            source[..m.end()].lines().count(), &source[m.end()..]),
+        None => format!("{}\n#line 0\n{}", directives.join("\n"), source),
+    }
+}
+
+/// Returns the newest GLSL version that a device supports from its `GL_SHADING_LANGUAGE_VERSION`,
+/// which starts with the version as `major.minor`, e.g. `4.60 NVIDIA` is GLSL 460.
+pub fn supported_glsl_version(glsl_version: &str) -> Option<u32> {
+    let version = glsl_version.split_whitespace().next()?;
+    let mut parts = version.splitn(2, '.');
+    let major = parts.next()?.parse::<u32>().ok()?;
+    let minor = parts.next()?.parse::<u32>().ok()?;
+    Some(major * 100 + minor)
+}
+
+/// Returns an error in the driver's format, on the `#version` directive of `source`, when it
+/// declares a version newer than the device described by `info` supports, naming the newest one
+/// it does support rather than leaving the wording up to the driver.
+pub fn unsupported_version_error(source: &str, info: &ValidatorInfo) -> Option<String> {
+    let version = crate::get_shader_version(source)?;
+    let supported = supported_glsl_version(&info.glsl_version)?;
+    if version <= supported {
+        return None;
+    }
+
+    let line = RE_VERSION_LINE.find(source).map_or(0, |m| source[..m.start()].matches('\n').count());
+    // `parse_validator_lines` takes two off the line numbers that validators report
+    Some(format!("0({}) : error: GLSL {} is not supported by {}, which supports up to GLSL {}\n",
+        line + 2, version, info.renderer, supported))
+}
+
+/// A validator that reports the same output for every shader, for driving `lint` end-to-end in tests.
+#[cfg(test)]
+pub struct CannedValidator {
+    pub output: Option<String>,
+}
+
+#[cfg(test)]
+impl ShaderValidator for CannedValidator {
+    fn validate(&self, _: super::TreeType, _: String) -> Option<String> {
+        self.output.clone()
+    }
+
+    fn validate_with_timeout(&self, tree_type: super::TreeType, source: String, _: Duration) -> Result<Option<String>, ValidationError> {
+        Ok(self.validate(tree_type, source))
+    }
+
+    fn describe_invocation(&self, tree_type: TreeType) -> String {
+        format!("canned output for a {:?} shader", tree_type)
+    }
+
+    fn info(&self) -> ValidatorInfo {
+        ValidatorInfo {
+            vendor: "canned".into(),
+            renderer: "canned".into(),
+            version: String::new(),
+            glsl_version: String::new(),
+        }
+    }
+
+    fn set_stage_args(&mut self, _: HashMap<TreeType, Vec<String>>) {}
 }
 
 pub struct OpenGLContext {
-    _ctx: glutin::Context<glutin::PossiblyCurrent>
+    _ctx: glutin::Context<glutin::PossiblyCurrent>,
+    stage_args: HashMap<TreeType, Vec<String>>,
+    info: ValidatorInfo,
 }
 
 impl OpenGLContext {
-    pub fn new() -> OpenGLContext {
-        let events_loop = glutin::event_loop::EventLoop::new();
-        let gl_window = glutin::ContextBuilder::new().build_headless(&*events_loop, glutin::dpi::PhysicalSize::new(1, 1)).unwrap();
-    
+    /// Creates a headless OpenGL context to validate shaders with, failing on machines without
+    /// a display or a working driver.
+    pub fn new() -> Result<OpenGLContext> {
+        // creating the event loop panics rather than erroring when there is no display to connect to
+        let events_loop = panic::catch_unwind(glutin::event_loop::EventLoop::new)
+            .map_err(|_| anyhow!("no display or windowing system is available"))?;
+        let gl_window = glutin::ContextBuilder::new().build_headless(&*events_loop, glutin::dpi::PhysicalSize::new(1, 1))
+            .map_err(|e| anyhow!("failed to create a headless OpenGL context: {}", e))?;
+
         let gl_window = unsafe {
-            let gl_window = gl_window.make_current().unwrap();
+            let gl_window = gl_window.make_current().map_err(|(_, e)| anyhow!("failed to make the OpenGL context current: {}", e))?;
             gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
             gl_window
         };
 
-        unsafe {
-            eprintln!(
-                "Using OpenGL device {} {} {}", 
-                String::from_utf8(CStr::from_ptr(gl::GetString(gl::VENDOR) as *const _).to_bytes().to_vec()).unwrap(),
-                String::from_utf8(CStr::from_ptr(gl::GetString(gl::VERSION) as *const _).to_bytes().to_vec()).unwrap(),
-                String::from_utf8(CStr::from_ptr(gl::GetString(gl::RENDERER) as *const _).to_bytes().to_vec()).unwrap()
-            );
-        }
-        OpenGLContext{
+        let info = unsafe {
+            let get_string = |name| String::from_utf8(CStr::from_ptr(gl::GetString(name) as *const _).to_bytes().to_vec()).unwrap();
+            ValidatorInfo {
+                vendor: get_string(gl::VENDOR),
+                renderer: get_string(gl::RENDERER),
+                version: get_string(gl::VERSION),
+                glsl_version: get_string(gl::SHADING_LANGUAGE_VERSION),
+            }
+        };
+        info!("Using OpenGL device {} {} {}", info.vendor, info.version, info.renderer);
+
+        Ok(OpenGLContext{
             _ctx: gl_window,
-        }
+            stage_args: HashMap::new(),
+            info,
+        })
     }
 
     unsafe fn compile_and_get_shader_log(&self, shader: gl::types::GLuint, source: String) -> Option<String> {
@@ -61,6 +252,15 @@ impl OpenGLContext {
 
 impl ShaderValidator for OpenGLContext {
     fn validate(&self, tree_type: super::TreeType, source: String) -> Option<String> {
+        if let Some(error) = unsupported_version_error(&source, &self.info) {
+            return Some(error);
+        }
+
+        let source = match self.stage_args.get(&tree_type) {
+            Some(args) => apply_stage_args(&source, args),
+            None => source,
+        };
+
         unsafe {
             match tree_type {
                 crate::TreeType::Fragment => {
@@ -83,7 +283,45 @@ impl ShaderValidator for OpenGLContext {
                     let compute_shader= gl::CreateShader(gl::COMPUTE_SHADER);
                     self.compile_and_get_shader_log(compute_shader, source)
                 }
+                crate::TreeType::TessControl => {
+                    // Tessellation control shader
+                    let tess_control_shader = gl::CreateShader(gl::TESS_CONTROL_SHADER);
+                    self.compile_and_get_shader_log(tess_control_shader, source)
+                }
+                crate::TreeType::TessEval => {
+                    // Tessellation evaluation shader
+                    let tess_eval_shader = gl::CreateShader(gl::TESS_EVALUATION_SHADER);
+                    self.compile_and_get_shader_log(tess_eval_shader, source)
+                }
             }
         }
     }
+
+    // the driver compiles on the thread the context is current on, which can't be interrupted, so
+    // `Configuration::reject_unsupported` doesn't let a timeout be set with this validator
+    fn validate_with_timeout(&self, tree_type: super::TreeType, source: String, _: Duration) -> Result<Option<String>, ValidationError> {
+        Ok(self.validate(tree_type, source))
+    }
+
+    fn info(&self) -> ValidatorInfo {
+        self.info.clone()
+    }
+
+    fn describe_invocation(&self, tree_type: TreeType) -> String {
+        let shader_type = match tree_type {
+            TreeType::Fragment => "GL_FRAGMENT_SHADER",
+            TreeType::Vertex => "GL_VERTEX_SHADER",
+            TreeType::Geometry => "GL_GEOMETRY_SHADER",
+            TreeType::Compute => "GL_COMPUTE_SHADER",
+            TreeType::TessControl => "GL_TESS_CONTROL_SHADER",
+            TreeType::TessEval => "GL_TESS_EVALUATION_SHADER",
+        };
+        format!("glCreateShader({}), glShaderSource, glCompileShader on {} {} (OpenGL {}, GLSL {}){}",
+            shader_type, self.info.vendor, self.info.renderer, self.info.version, self.info.glsl_version,
+            describe_stage_args(self.stage_args.get(&tree_type)))
+    }
+
+    fn set_stage_args(&mut self, args: HashMap<TreeType, Vec<String>>) {
+        self.stage_args = args;
+    }
 }
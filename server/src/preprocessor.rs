@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use petgraph::stable_graph::NodeIndex;
+
+use regex::Regex;
+
+use rust_lsp::lsp_types::{Diagnostic, DiagnosticTag, Url};
+
+use lazy_static::lazy_static;
+
+use crate::graph::CachedStableGraph;
+use crate::{symbols, RE_VERSION};
+
+lazy_static! {
+    static ref RE_CONDITIONAL: Regex = Regex::new(r#"^\s*#\s*(ifdef|ifndef|if|elif|else|endif)\b\s*(.*)"#).unwrap();
+    static ref RE_OPTION: Regex = Regex::new(r#"^\s*//\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RE_DEFINED: Regex = Regex::new(r#"^(!)?\s*defined\s*\(?\s*([A-Za-z_][A-Za-z0-9_]*)\s*\)?\s*$"#).unwrap();
+}
+
+struct Conditional {
+    // whether the region enclosing this conditional is active
+    parent_active: bool,
+    // whether a previous branch of this conditional was provably taken
+    taken: bool,
+    // whether the current branch is active
+    active: bool,
+}
+
+/// Splits a user-supplied define of the form `NAME` or `NAME=VALUE`.
+pub fn split_define(define: &str) -> (&str, Option<&str>) {
+    match define.find('=') {
+        Some(i) => (define[..i].trim(), Some(define[i+1..].trim())),
+        None => (define.trim(), None),
+    }
+}
+
+/// Evaluates the condition of a `#ifdef`, `#ifndef`, `#if` or `#elif`. Only `defined(NAME)`
+/// style conditions are understood, anything else evaluates to `None` as it can't be proven either way.
+fn evaluate_condition(directive: &str, expression: &str, defines: &HashSet<String>) -> Option<bool> {
+    match directive {
+        "ifdef" => expression.split_whitespace().next().map(|name| defines.contains(name)),
+        "ifndef" => expression.split_whitespace().next().map(|name| !defines.contains(name)),
+        _ => RE_DEFINED.captures(expression).map(|cap| {
+            let defined = defines.contains(&cap[2]);
+            if cap.get(1).is_some() { !defined } else { defined }
+        }),
+    }
+}
+
+/// Returns, for every line of `source`, whether it lies in a region that is active
+/// given the set of defined macro names. Lines in branches whose condition can't be
+/// evaluated are considered active.
+pub fn active_lines(source: &str, defines: &HashSet<String>) -> Vec<bool> {
+    let mut stack: Vec<Conditional> = Vec::new();
+    let mut lines = Vec::new();
+
+    for line in source.lines() {
+        let active = stack.last().map_or(true, |c| c.active);
+
+        let cap = match RE_CONDITIONAL.captures(line) {
+            Some(cap) => cap,
+            None => {
+                lines.push(active);
+                continue
+            }
+        };
+
+        match &cap[1] {
+            "endif" => {
+                let enclosing = stack.pop().map_or(true, |c| c.parent_active);
+                lines.push(enclosing);
+            },
+            "else" => {
+                let enclosing = stack.last().map_or(true, |c| c.parent_active);
+                if let Some(c) = stack.last_mut() {
+                    c.active = c.parent_active && !c.taken;
+                }
+                lines.push(enclosing);
+            },
+            "elif" => {
+                let enclosing = stack.last().map_or(true, |c| c.parent_active);
+                let condition = evaluate_condition("elif", cap[2].trim(), defines);
+                if let Some(c) = stack.last_mut() {
+                    c.active = c.parent_active && !c.taken && condition != Some(false);
+                    c.taken |= condition == Some(true);
+                }
+                lines.push(enclosing);
+            },
+            directive => {
+                let condition = evaluate_condition(directive, cap[2].trim(), defines);
+                stack.push(Conditional {
+                    parent_active: active,
+                    taken: condition == Some(true),
+                    active: active && condition != Some(false),
+                });
+                lines.push(active);
+            }
+        }
+    }
+
+    lines
+}
+
+/// Removes every node from the DFS `nodes` (along with its subtree) whose `#include` line
+/// in its parent lies in a provably inactive region. The set of defined names is `defines`
+/// plus every name `#define`d anywhere in the tree.
+pub fn filter_inactive_includes(
+    nodes: &[(NodeIndex, Option<NodeIndex>)],
+    sources: &HashMap<PathBuf, String>,
+    graph: &CachedStableGraph,
+    defines: &[String],
+) -> Vec<(NodeIndex, Option<NodeIndex>)> {
+    let mut defined: HashSet<String> = defines.iter().map(|d| split_define(d).0.to_string()).collect();
+    for (node, _) in nodes {
+        if let Some(source) = sources.get(&graph.get_node(*node)) {
+            defined.extend(symbols::defined_names(source));
+        }
+    }
+
+    let mut active_cache: HashMap<NodeIndex, Vec<bool>> = HashMap::new();
+    // the path from the root to the current node
+    let mut path: Vec<NodeIndex> = Vec::new();
+    // the depth of the subtree currently being skipped, if any
+    let mut skip_depth: Option<usize> = None;
+    let mut filtered = Vec::with_capacity(nodes.len());
+
+    for &(node, parent) in nodes {
+        match parent {
+            Some(parent) => while !path.is_empty() && path.last() != Some(&parent) {
+                path.pop();
+            },
+            None => path.clear(),
+        }
+        let depth = path.len();
+        path.push(node);
+
+        if let Some(skip) = skip_depth {
+            if depth > skip {
+                continue;
+            }
+            skip_depth = None;
+        }
+
+        if let Some(parent) = parent {
+            let active = active_cache.entry(parent).or_insert_with(|| {
+                match sources.get(&graph.get_node(parent)) {
+                    Some(source) => active_lines(source, &defined),
+                    None => vec![],
+                }
+            });
+            let line = graph.get_edge_meta(parent, node).line;
+            if !active.get(line).copied().unwrap_or(true) {
+                skip_depth = Some(depth);
+                continue;
+            }
+        }
+
+        filtered.push((node, parent));
+    }
+
+    filtered
+}
+
+/// Tags the diagnostics that lie in a region of their file that is inactive however the
+/// shaderpack is configured, being a branch on names that are neither in `defines` nor
+/// `#define`d anywhere in the tree of `nodes`, not even by a commented out shader option such as
+/// `//#define SHADOWS` that users can switch on. Such diagnostics are marked as unnecessary code
+/// and noted as being in a disabled block, as they can't show up in game.
+pub fn tag_disabled_diagnostics(
+    diagnostics: &mut HashMap<Url, Vec<Diagnostic>>,
+    nodes: &[(NodeIndex, Option<NodeIndex>)],
+    sources: &HashMap<PathBuf, String>,
+    graph: &CachedStableGraph,
+    defines: &[String],
+) {
+    let mut defined: HashSet<String> = defines.iter().map(|d| split_define(d).0.to_string()).collect();
+    for (node, _) in nodes {
+        if let Some(source) = sources.get(&graph.get_node(*node)) {
+            defined.extend(symbols::defined_names(source));
+            defined.extend(source.lines().filter_map(|line| RE_OPTION.captures(line).map(|cap| cap[1].to_string())));
+        }
+    }
+
+    for (url, file_diagnostics) in diagnostics.iter_mut() {
+        let active = match url.to_file_path().ok().and_then(|path| sources.get(&path)) {
+            Some(source) => active_lines(source, &defined),
+            None => continue,
+        };
+        for diagnostic in file_diagnostics {
+            if active.get(diagnostic.range.start.line as usize).copied().unwrap_or(true) {
+                continue;
+            }
+            diagnostic.tags.get_or_insert_with(Vec::new).push(DiagnosticTag::Unnecessary);
+            diagnostic.message.push_str(" (in a preprocessor block that is never enabled)");
+        }
+    }
+}
+
+/// Inserts a `#define` for each of `defines` directly after the `#version` directive of
+/// `source` (or at the very top if there is none), followed by a `#line` directive so that
+/// line numbers reported by the validator are unaffected.
+pub fn inject_defines(source: &str, defines: &[String]) -> String {
+    if defines.is_empty() {
+        return source.to_string();
+    }
+
+    let mut injected = String::new();
+    for define in defines {
+        match split_define(define) {
+            (name, Some(value)) => injected.push_str(&format!("#define {} {}\n", name, value)),
+            (name, None) => injected.push_str(&format!("#define {}\n", name)),
+        }
+    }
+
+    let version_line = source.lines().position(|line| RE_VERSION.is_match(line));
+    let (offset, next_line) = match version_line {
+        Some(n) => {
+            let offset = source.lines().take(n + 1).fold(0, |acc, line| acc + line.len() + 1);
+            // #line is 1-indexed and refers to the line following it
+            (std::cmp::min(offset, source.len()), n + 2)
+        },
+        None => (0, 1),
+    };
+    injected.push_str(&format!("#line {}\n", next_line));
+
+    let mut result = String::with_capacity(source.len() + injected.len() + 1);
+    result.push_str(&source[..offset]);
+    if offset > 0 && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(&injected);
+    result.push_str(&source[offset..]);
+    result
+}
@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use path_slash::PathBufExt;
+
+/// The parts of a shaderpack's `shaders.properties` that decide which programs are loaded, as
+/// read by Optifine and Iris. Only programs that are switched off outright with
+/// `program.<name>.enabled = false` are tracked, as conditions on shader options can't be
+/// evaluated without the user's option values, so those programs are assumed to be enabled.
+#[derive(Debug, Default, Clone)]
+pub struct ShadersProperties {
+    disabled_programs: HashSet<String>,
+}
+
+impl ShadersProperties {
+    pub fn parse(source: &str) -> ShadersProperties {
+        let mut disabled_programs = HashSet::new();
+
+        // a trailing \ continues the property on the next line
+        let source = source.replace("\\\r\n", "").replace("\\\n", "");
+        for line in source.lines().map(str::trim) {
+            if line.starts_with('#') || line.starts_with('!') {
+                continue;
+            }
+            let (key, value) = match line.find(|c| c == '=' || c == ':') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+            let program = match key.strip_prefix("program.").and_then(|key| key.strip_suffix(".enabled")) {
+                Some(program) => program,
+                None => continue,
+            };
+            if value == "false" {
+                disabled_programs.insert(program.to_string());
+            }
+        }
+
+        ShadersProperties { disabled_programs }
+    }
+
+    /// Reads the `shaders.properties` of the shaderpack root `pack_root`, if it has one.
+    pub fn load(pack_root: &Path) -> Option<ShadersProperties> {
+        fs::read_to_string(pack_root.join("shaders.properties")).ok().map(|source| ShadersProperties::parse(&source))
+    }
+
+    /// Returns whether the program that the top-level file `path` of the shaderpack root
+    /// `pack_root` belongs to is disabled. Programs are named by their path relative to the
+    /// shaderpack root without the extension, such as `composite1` or `world-1/gbuffers_water`.
+    pub fn is_disabled(&self, pack_root: &Path, path: &Path) -> bool {
+        let program = match path.strip_prefix(pack_root) {
+            Ok(relative) => relative.with_extension(""),
+            Err(_) => return false,
+        };
+        self.disabled_programs.contains(program.to_slash_lossy().as_str())
+    }
+}
@@ -0,0 +1,71 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+
+use rust_lsp::lsp_types::{SemanticToken, SemanticTokenType, SemanticTokensLegend};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RE_DIRECTIVE: Regex = Regex::new(r#"^\s*(#\s*[a-z]+)\b"#).unwrap();
+    static ref RE_INCLUDE_PATH: Regex = Regex::new(r#"^\s*#\s*include\s+("[^"]*")"#).unwrap();
+    static ref RE_DEFINE_NAME: Regex = Regex::new(r#"^\s*#\s*(?:define|undef|ifdef|ifndef)\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RE_VERSION_NUMBER: Regex = Regex::new(r#"^\s*#\s*version\s+(\d+)"#).unwrap();
+}
+
+// indexes into the token types of `legend`
+const DIRECTIVE: u32 = 0;
+const INCLUDE_PATH: u32 = 1;
+const MACRO: u32 = 2;
+const NUMBER: u32 = 3;
+
+/// Returns the legend of the token types in `tokens`, advertised to the client in the experimental
+/// capabilities.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::KEYWORD,
+            SemanticTokenType::STRING,
+            SemanticTokenType::MACRO,
+            SemanticTokenType::NUMBER,
+        ],
+        token_modifiers: vec![],
+    }
+}
+
+/// Returns the semantic tokens of the preprocessor directives in `source`, being the directive
+/// itself, the quoted path of an `#include`, the name of a `#define` and the `#version` number.
+pub fn tokens(source: &str) -> Vec<SemanticToken> {
+    // (line, start, length, token type), in document order
+    let mut absolute: Vec<(usize, usize, usize, u32)> = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        let directive = match RE_DIRECTIVE.captures(line) {
+            Some(cap) => cap.get(1).unwrap(),
+            None => continue,
+        };
+        absolute.push((n, directive.start(), directive.end() - directive.start(), DIRECTIVE));
+
+        let argument = RE_INCLUDE_PATH.captures(line).map(|cap| (cap.get(1).unwrap(), INCLUDE_PATH))
+            .or_else(|| RE_DEFINE_NAME.captures(line).map(|cap| (cap.get(1).unwrap(), MACRO)))
+            .or_else(|| RE_VERSION_NUMBER.captures(line).map(|cap| (cap.get(1).unwrap(), NUMBER)));
+        if let Some((m, token_type)) = argument {
+            absolute.push((n, m.start(), m.end() - m.start(), token_type));
+        }
+    }
+
+    let mut previous = (0, 0);
+    absolute.into_iter().map(|(line, start, length, token_type)| {
+        let delta_line = line - previous.0;
+        let delta_start = if delta_line == 0 { start - previous.1 } else { start };
+        previous = (line, start);
+
+        SemanticToken {
+            delta_line: u32::try_from(delta_line).unwrap(),
+            delta_start: u32::try_from(delta_start).unwrap(),
+            length: u32::try_from(length).unwrap(),
+            token_type,
+            token_modifiers_bitset: 0,
+        }
+    }).collect()
+}
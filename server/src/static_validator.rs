@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use regex::Regex;
+
+use lazy_static::lazy_static;
+
+use crate::opengl::{ShaderValidator, ValidationError, ValidatorInfo};
+use crate::TreeType;
+
+lazy_static! {
+    static ref RE_LINE_DIRECTIVE: Regex = Regex::new(r#"^\s*#\s*line\s+(\d+)(?:\s+"(.*)")?"#).unwrap();
+    static ref RE_CONDITIONAL: Regex = Regex::new(r#"^\s*#\s*(if|ifdef|ifndef|elif|else|endif)\b"#).unwrap();
+}
+
+/// Checks the structure of shaders without compiling them, for machines where no OpenGL context
+/// can be created, such as headless servers and CI. Reports unbalanced brackets, unterminated
+/// block comments and unbalanced `#if`/`#endif` in the same format as the OpenGL driver. A missing
+/// `#version` and missing includes are already reported by the server for every validator.
+pub struct StaticValidator;
+
+/// A file of the merged source, as named by its `#line` directive (`0` for the root), and a
+/// 0-indexed line in it.
+type Location = (String, usize);
+
+/// Formats an error at `location` like the driver reports it. The driver numbers lines 2 ahead
+/// of the 0-indexed line, which is undone when its output is parsed.
+fn error(location: &Location, message: &str) -> String {
+    format!("{}({}) : error: {}\n", location.0, location.1 + 2, message)
+}
+
+/// Returns the errors in the merged `source`, if there are any. The branches after an `#else`
+/// or `#elif` aren't checked for brackets, as they usually repeat the opening lines of the first.
+pub fn check(source: &str) -> Option<String> {
+    let mut errors = String::new();
+
+    let mut file = "0".to_string();
+    let mut line = 0;
+    // the location of each unclosed bracket
+    let mut brackets: Vec<(char, Location)> = Vec::new();
+    // the location of each unclosed #if, and whether it is past its first branch
+    let mut conditionals: Vec<(Location, bool)> = Vec::new();
+    let mut block_comment: Option<Location> = None;
+
+    for text in source.lines() {
+        let here = (file.clone(), line);
+        line += 1;
+
+        if block_comment.is_none() {
+            if let Some(cap) = RE_LINE_DIRECTIVE.captures(text) {
+                // #line is 1-indexed and refers to the line following it
+                line = cap[1].parse::<usize>().unwrap_or(1).saturating_sub(1);
+                if let Some(path) = cap.get(2) {
+                    file = path.as_str().to_string();
+                }
+                continue;
+            }
+            if let Some(cap) = RE_CONDITIONAL.captures(text) {
+                match &cap[1] {
+                    "if" | "ifdef" | "ifndef" => conditionals.push((here, false)),
+                    "endif" => if conditionals.pop().is_none() {
+                        errors.push_str(&error(&here, "#endif without a matching #if"));
+                    },
+                    directive => match conditionals.last_mut() {
+                        Some(conditional) => conditional.1 = true,
+                        None => errors.push_str(&error(&here, &format!("#{} without a matching #if", directive))),
+                    },
+                }
+                continue;
+            }
+            if text.trim_start().starts_with('#') {
+                continue;
+            }
+        }
+
+        let in_alternative = conditionals.iter().any(|conditional| conditional.1);
+
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if block_comment.is_some() {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    block_comment = None;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    block_comment = Some(here.clone());
+                },
+                _ if in_alternative => {},
+                '(' | '[' | '{' => brackets.push((c, here.clone())),
+                ')' | ']' | '}' => {
+                    let opening = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        _ => '{',
+                    };
+                    match brackets.last() {
+                        Some((open, _)) if *open == opening => {
+                            brackets.pop();
+                        },
+                        Some((open, _)) => errors.push_str(&error(&here, &format!("unexpected '{}' before the '{}' was closed", c, open))),
+                        None => errors.push_str(&error(&here, &format!("unexpected '{}'", c))),
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(location) = block_comment {
+        errors.push_str(&error(&location, "unterminated comment"));
+    }
+    for (open, location) in brackets {
+        errors.push_str(&error(&location, &format!("unclosed '{}'", open)));
+    }
+    for (location, _) in conditionals {
+        errors.push_str(&error(&location, "#if without a matching #endif"));
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(errors)
+    }
+}
+
+impl ShaderValidator for StaticValidator {
+    fn validate(&self, _: TreeType, source: String) -> Option<String> {
+        check(&source)
+    }
+
+    // checking is a single pass over the source, so it isn't worth interrupting
+    fn validate_with_timeout(&self, tree_type: TreeType, source: String, _: Duration) -> Result<Option<String>, ValidationError> {
+        Ok(self.validate(tree_type, source))
+    }
+
+    fn info(&self) -> ValidatorInfo {
+        ValidatorInfo {
+            vendor: "mcshader-lsp".into(),
+            renderer: "static checks".into(),
+            version: String::new(),
+            glsl_version: String::new(),
+        }
+    }
+
+    fn describe_invocation(&self, tree_type: TreeType) -> String {
+        format!("static checks of the brackets, comments and #if nesting of the {:?} shader, without compiling it", tree_type)
+    }
+
+    // the arguments only define macros and enable extensions, neither of which are checked
+    fn set_stage_args(&mut self, _: HashMap<TreeType, Vec<String>>) {}
+}
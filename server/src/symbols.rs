@@ -0,0 +1,220 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+
+use rust_lsp::lsp_types::{Position, Range};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RE_IDENTIFIER: Regex = Regex::new(r#"\b[A-Za-z_][A-Za-z0-9_]*\b"#).unwrap();
+    static ref RE_DEFINE: Regex = Regex::new(r#"^\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap();
+    static ref RE_DEFINE_VALUE: Regex = Regex::new(r#"^\s*#\s*define\s+([A-Za-z_][A-Za-z0-9_]*)(\(?)(.*)$"#).unwrap();
+    static ref RE_DECLARATION: Regex = Regex::new(
+        r#"\b(?:void|bool|int|uint|float|double|[biud]?vec[234]|d?mat[234](?:x[234])?|[iu]?sampler[A-Za-z0-9]*|struct)\s+([A-Za-z_][A-Za-z0-9_]*)"#
+    ).unwrap();
+    static ref RE_FUNCTION: Regex = Regex::new(
+        r#"^\s*(?:(?:highp|mediump|lowp|const)\s+)*([A-Za-z_][A-Za-z0-9_]*)\s+([A-Za-z_][A-Za-z0-9_]*)\s*\(([^)]*)\)"#
+    ).unwrap();
+}
+
+/// GLSL keywords and reserved names that must never be treated as user-defined symbols.
+static RESERVED: &[&str] = &[
+    "attribute", "const", "uniform", "varying", "buffer", "shared", "layout", "centroid", "flat", "smooth",
+    "noperspective", "patch", "sample", "break", "continue", "do", "for", "while", "switch", "case", "default",
+    "if", "else", "subroutine", "in", "out", "inout", "true", "false", "invariant", "precise", "discard",
+    "return", "struct", "void", "bool", "int", "uint", "float", "double", "lowp", "mediump", "highp",
+    "precision", "main", "define", "undef", "ifdef", "ifndef", "elif", "endif", "include", "version",
+    "extension", "pragma", "line", "error",
+];
+
+/// An identifier token in a source file. `start` and `end` are the byte
+/// offsets of the token within its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A function definition or prototype found in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub return_type: String,
+    pub parameters: Vec<String>,
+}
+
+impl FunctionSignature {
+    pub fn label(&self) -> String {
+        format!("{} {}({})", self.return_type, self.name, self.parameters.join(", "))
+    }
+}
+
+impl Token {
+    pub fn range(&self) -> Range {
+        Range::new(
+            Position::new(u32::try_from(self.line).unwrap(), u32::try_from(self.start).unwrap()),
+            Position::new(u32::try_from(self.line).unwrap(), u32::try_from(self.end).unwrap()),
+        )
+    }
+}
+
+pub fn is_identifier(text: &str) -> bool {
+    match RE_IDENTIFIER.find(text) {
+        Some(m) => m.start() == 0 && m.end() == text.len(),
+        None => false,
+    }
+}
+
+/// Returns whether `text` is a GLSL keyword, built-in type or otherwise reserved name.
+pub fn is_reserved(text: &str) -> bool {
+    RESERVED.contains(&text) || text.starts_with("gl_") || RE_DECLARATION.is_match(&format!("{} x", text))
+}
+
+/// Returns the identifier token that touches `character` on line `line`, if any.
+pub fn token_at_position(source: &str, line: usize, character: usize) -> Option<Token> {
+    let text = source.lines().nth(line)?;
+
+    RE_IDENTIFIER.find_iter(text)
+        .find(|m| m.start() <= character && character <= m.end())
+        .map(|m| Token {
+            text: m.as_str().to_string(),
+            line,
+            start: m.start(),
+            end: m.end(),
+        })
+}
+
+/// Returns every whole-token occurrence of `name` in `source`, so that `FOO`
+/// does not match inside `FOOBAR`.
+pub fn find_token_occurrences(source: &str, name: &str) -> Vec<Token> {
+    source.lines()
+        .enumerate()
+        .flat_map(move |(n, line)| {
+            RE_IDENTIFIER.find_iter(line)
+                .filter(move |m| m.as_str() == name)
+                .map(move |m| Token {
+                    text: m.as_str().to_string(),
+                    line: n,
+                    start: m.start(),
+                    end: m.end(),
+                })
+        })
+        .collect()
+}
+
+/// Returns the line numbers of every `#define` of `name` in `source`.
+pub fn find_defines(source: &str, name: &str) -> Vec<usize> {
+    source.lines()
+        .enumerate()
+        .filter(|(_, line)| match RE_DEFINE.captures(line) {
+            Some(cap) => &cap[1] == name,
+            None => false,
+        })
+        .map(|(n, _)| n)
+        .collect()
+}
+
+/// Returns what the first `#define` of `name` in `source` is replaced with, without any trailing
+/// comment. Macros taking arguments aren't substituted on their own, so they have no value.
+pub fn define_value(source: &str, name: &str) -> Option<String> {
+    source.lines()
+        .filter_map(|line| RE_DEFINE_VALUE.captures(line))
+        .find(|cap| &cap[1] == name)
+        .filter(|cap| cap[2].is_empty())
+        .map(|cap| {
+            let value = &cap[3];
+            let value = value.find("//").map_or(value, |comment| &value[..comment]);
+            value.trim().to_string()
+        })
+}
+
+/// Returns the name of every macro `#define`d in `source`.
+pub fn defined_names(source: &str) -> Vec<String> {
+    source.lines()
+        .filter_map(|line| RE_DEFINE.captures(line).map(|cap| cap[1].to_string()))
+        .collect()
+}
+
+/// Returns the name of every function, variable and struct declared in `source`, with the
+/// same heuristic as `find_declarations`.
+pub fn declared_names(source: &str) -> Vec<String> {
+    source.lines()
+        .flat_map(|line| RE_DECLARATION.captures_iter(line).map(|cap| cap[1].to_string()).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Returns the line numbers of every declaration of `name` in `source`, such as
+/// `float name(...)`, `uniform vec3 name;` or `struct name`. This is a heuristic and
+/// only considers the first name in a declaration.
+pub fn find_declarations(source: &str, name: &str) -> Vec<usize> {
+    source.lines()
+        .enumerate()
+        .filter(|(_, line)| RE_DECLARATION.captures_iter(line).any(|cap| &cap[1] == name))
+        .map(|(n, _)| n)
+        .collect()
+}
+
+/// Returns every function definition or prototype of `name` in `source`.
+pub fn find_function_signatures(source: &str, name: &str) -> Vec<FunctionSignature> {
+    source.lines()
+        .filter_map(|line| RE_FUNCTION.captures(line))
+        .filter(|cap| &cap[2] == name)
+        // rules out statements such as `return foo(x)`
+        .filter(|cap| !matches!(&cap[1], "return" | "else" | "case" | "define"))
+        .map(|cap| FunctionSignature {
+            name: cap[2].to_string(),
+            return_type: cap[1].to_string(),
+            parameters: cap[3].split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty() && p != "void")
+                .collect(),
+        })
+        .collect()
+}
+
+/// Finds the innermost function call that the cursor at `line`/`character` is inside the
+/// argument list of, returning the function name and the index of the argument under the cursor.
+pub fn call_at_position(source: &str, line: usize, character: usize) -> Option<(String, usize)> {
+    let mut offset = 0;
+    for (n, text) in source.lines().enumerate() {
+        if n == line {
+            offset += std::cmp::min(character, text.len());
+            break;
+        }
+        offset += text.len() + 1;
+    }
+    let mut offset = std::cmp::min(offset, source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let preceding = &source[..offset];
+
+    let mut depth = 0;
+    let mut commas = 0;
+    for (i, c) in preceding.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth > 0 => depth -= 1,
+            '(' => {
+                let name_end = preceding[..i].trim_end().len();
+                let name_start = preceding[..name_end].char_indices()
+                    .rev()
+                    .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                    .last()
+                    .map_or(name_end, |(i, _)| i);
+                let name = &preceding[name_start..name_end];
+                if !is_identifier(name) {
+                    return None;
+                }
+                return Some((name.to_string(), commas));
+            },
+            ',' if depth == 0 => commas += 1,
+            ';' | '{' | '}' => return None,
+            _ => {}
+        }
+    }
+    None
+}
@@ -12,6 +12,8 @@ use petgraph::algo::is_cyclic_directed;
 
 use fs_extra::{copy_items, dir};
 
+use commands::Invokeable;
+
 use jsonrpc_common::*;
 use jsonrpc_response::*;
 
@@ -39,14 +41,7 @@ fn new_temp_server() -> MinecraftShaderLanguageServer {
         s: Box::new(io::sink()),
     });
 
-    MinecraftShaderLanguageServer {
-        endpoint,
-        graph: Rc::new(RefCell::new(graph::CachedStableGraph::new())),
-        wait: WaitGroup::new(),
-        root: "".into(),
-        command_provider: None,
-        opengl_context: Rc::new(opengl::MockShaderValidator::new()),
-    }
+    MinecraftShaderLanguageServer::with_validator(endpoint, Rc::new(opengl::MockShaderValidator::new()))
 }
 
 fn copy_files(files: &str, dest: &TempDir) {
@@ -61,7 +56,7 @@ fn copy_files(files: &str, dest: &TempDir) {
 fn copy_to_and_set_root(test_path: &str, server: &mut MinecraftShaderLanguageServer,) -> (Rc<TempDir>, PathBuf) {
     let (_tmp_dir, tmp_path) = copy_to_tmp_dir(test_path);
 
-    server.root = tmp_path.clone();//format!("{}{}", "file://", tmp_path);
+    server.roots = vec![tmp_path.clone()];
 
     (_tmp_dir, tmp_path)
 }
@@ -78,6 +73,42 @@ fn copy_to_tmp_dir(test_path: &str) -> (Rc<TempDir>, PathBuf) {
     (tmp_dir, tmp_path.into())
 }
 
+// Files held in memory, for tests of include resolution and merging that don't touch the disk.
+struct MemoryFiles {
+    files: HashMap<PathBuf, String>,
+}
+
+impl files::FileProvider for MemoryFiles {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.files.get(path) {
+            Some(source) => Ok(source.clone().into_bytes()),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} isn't in memory", path))),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file.starts_with(path))
+    }
+
+    fn entries(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut entries: Vec<String> = self.files.keys()
+            .filter_map(|file| file.strip_prefix(path).ok())
+            .filter_map(|relative| relative.components().next())
+            .map(|name| name.as_os_str().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+fn set_memory_files(server: &mut MinecraftShaderLanguageServer, root: &Path, files: &[(&str, &str)]) {
+    server.roots = vec![root.to_path_buf()];
+    server.files = Rc::new(MemoryFiles {
+        files: files.iter().map(|(path, source)| (root.join(PathBuf::from_slash(path)), source.to_string())).collect(),
+    });
+}
+
 #[allow(deprecated)]
 #[test]
 fn test_empty_initialize() {
@@ -121,7 +152,7 @@ fn test_empty_initialize() {
     ));
     server.initialize(initialize_params, completable);
 
-    assert_eq!(server.root, tmp_path);
+    assert_eq!(server.roots, vec![tmp_path.to_path_buf()]);
 
     assert_eq!(server.graph.borrow().graph.edge_count(), 0);
     assert_eq!(server.graph.borrow().graph.node_count(), 0);
@@ -931,7 +962,7 @@ fn test_generate_merge_list_01() {
     let sources = server.load_sources(&nodes).unwrap();
 
     let graph_borrow = server.graph.borrow();
-    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow);
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, false);
 
     let merge_file = tmp_path.clone().join( "shaders").join("final.fsh.merge");
 
@@ -994,7 +1025,7 @@ fn test_generate_merge_list_02() {
     let sources = server.load_sources(&nodes).unwrap();
 
     let graph_borrow = server.graph.borrow();
-    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow);
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, false);
 
     let merge_file = tmp_path.clone().join("shaders").join("final.fsh.merge");
 
@@ -1067,7 +1098,7 @@ fn test_generate_merge_list_03() {
     let sources = server.load_sources(&nodes).unwrap();
 
     let graph_borrow = server.graph.borrow();
-    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow);
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, false);
 
     let merge_file = tmp_path.clone().join("shaders").join("final.fsh.merge");
 
@@ -1152,7 +1183,7 @@ fn test_generate_merge_list_04() {
     let sources = server.load_sources(&nodes).unwrap();
 
     let graph_borrow = server.graph.borrow();
-    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow);
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, false);
 
     let merge_file = tmp_path.clone().join("shaders").join("final.fsh.merge");
 
@@ -1175,3 +1206,3918 @@ fn test_generate_merge_list_04() {
 
     assert_eq!(result, truth);
 }
+
+#[test]
+fn test_get_shader_version() {
+    assert_eq!(get_shader_version("#version 120\n\nvoid main() {}"), Some(120));
+    assert_eq!(get_shader_version("// comment\n#version 450\n"), Some(450));
+    assert_eq!(get_shader_version("void main() {}"), None);
+}
+
+#[test]
+fn test_lint_version_directive() {
+    {
+        let mut server = new_temp_server();
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+        server.endpoint.request_shutdown();
+
+        server.gen_initial_graph();
+
+        let mut validator = opengl::MockShaderValidator::new();
+        validator.expect_validate()
+            .withf(|_, source| get_shader_version(source) == Some(120))
+            .times(1)
+            .returning(|_, _| None);
+        server.opengl_context = Rc::new(validator);
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+    }
+    {
+        let mut server = new_temp_server();
+
+        let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+        server.endpoint.request_shutdown();
+
+        let final_path = tmp_path.join("shaders").join("final.fsh");
+        fs::write(&final_path, "#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n\nvoid main() {}\n").unwrap();
+
+        server.gen_initial_graph();
+
+        let mut validator = opengl::MockShaderValidator::new();
+        validator.expect_validate()
+            .withf(|_, source| get_shader_version(source).is_none())
+            .times(1)
+            .returning(|_, _| None);
+        server.opengl_context = Rc::new(validator);
+
+        let diagnostics = server.lint(&final_path).unwrap();
+
+        let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+        assert_eq!(final_diagnostics[0].range.start.line, 0);
+    }
+}
+
+#[test]
+fn test_unsupported_version_error() {
+    assert_eq!(opengl::supported_glsl_version("4.60 NVIDIA"), Some(460));
+    assert_eq!(opengl::supported_glsl_version("1.30"), Some(130));
+    assert_eq!(opengl::supported_glsl_version(""), None);
+
+    let info = opengl::ValidatorInfo {
+        vendor: "Mesa".into(),
+        renderer: "llvmpipe".into(),
+        version: "3.1 Mesa 20.3.4".into(),
+        glsl_version: "1.40".into(),
+    };
+    assert_eq!(opengl::unsupported_version_error("// a comment\n#version 330\nvoid main() {}\n", &info),
+        Some("0(3) : error: GLSL 330 is not supported by llvmpipe, which supports up to GLSL 140\n".into()));
+    assert_eq!(opengl::unsupported_version_error("#version 140\nvoid main() {}\n", &info), None);
+    assert_eq!(opengl::unsupported_version_error("void main() {}\n", &info), None);
+}
+
+#[test]
+fn test_lint_unsupported_version() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(&final_path, "// a comment\n#version 330\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let info = opengl::ValidatorInfo {
+        vendor: "Mesa".into(),
+        renderer: "llvmpipe".into(),
+        version: "3.1 Mesa 20.3.4".into(),
+        glsl_version: "1.40".into(),
+    };
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(move |_, source| opengl::unsupported_version_error(&source, &info));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].range.start.line, 1);
+    assert!(final_diagnostics[0].message.starts_with("GLSL 330 is not supported"));
+}
+
+#[test]
+fn test_lint_unrecognized_root_fallback() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let root_path = tmp_path.join("shaders").join("final.xyz");
+    fs::write(&root_path, "#version 120\n\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+    server.add_file_and_includes_to_graph(&root_path);
+
+    server.config.unrecognized_roots.insert("xyz".into(), configuration::UnrecognizedRootBehaviour::Fragment);
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .withf(|tree_type, _| matches!(tree_type, TreeType::Fragment))
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&root_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&root_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_symbols_token_matching() {
+    let source = "#define FOO 1\nint FOOBAR = FOO + 2;\n";
+
+    let token = symbols::token_at_position(source, 1, 14).unwrap();
+    assert_eq!(token.text, "FOO");
+    assert_eq!((token.line, token.start, token.end), (1, 13, 16));
+
+    assert!(symbols::token_at_position(source, 1, 11).is_none());
+
+    let occurrences = symbols::find_token_occurrences(source, "FOO");
+    assert_eq!(occurrences.len(), 2);
+    assert_eq!((occurrences[0].line, occurrences[0].start), (0, 8));
+    assert_eq!((occurrences[1].line, occurrences[1].start), (1, 13));
+
+    assert_eq!(symbols::find_defines(source, "FOO"), vec![0]);
+    assert!(symbols::find_defines(source, "FOOBAR").is_empty());
+}
+
+#[test]
+fn test_rename_symbol() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&common_path, "#define FOO 0.5\n#define FOOBAR 1.0\nfloat test() {\n\treturn FOO;\n}").unwrap();
+    fs::write(&final_path, "#version 120\n\n#include \"/common.glsl\"\n\nvoid main() {\n\tgl_FragColor[0] = vec4(FOO * FOOBAR);\n}").unwrap();
+
+    server.gen_initial_graph();
+
+    let edit = server.rename_symbol(&final_path, Position::new(5, 25), "BAR").unwrap();
+    let changes = edit.changes.unwrap();
+
+    let final_edits = changes.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_edits.len(), 1);
+    assert_eq!(final_edits[0].range, Range::new(Position::new(5, 24), Position::new(5, 27)));
+    assert_eq!(final_edits[0].new_text, "BAR");
+
+    let common_edits = changes.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_edits.len(), 2);
+    assert_eq!(common_edits[0].range.start, Position::new(0, 8));
+    assert_eq!(common_edits[1].range.start, Position::new(3, 8));
+
+    // `main` is reserved
+    assert!(server.rename_symbol(&final_path, Position::new(4, 6), "BAR").is_err());
+    // whitespace isn't an identifier
+    assert!(server.rename_symbol(&final_path, Position::new(1, 0), "BAR").is_err());
+}
+
+#[test]
+fn test_rename_rejects_unrenameable_positions() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(&final_path, "#version 120\n#define FOO 1.0\n#include \"/common.glsl\"\n\nvoid main() {\n\tgl_FragColor[0] = vec4(test() * FOO);\n}").unwrap();
+
+    server.gen_initial_graph();
+
+    let final_url = Url::from_file_path(&final_path).unwrap();
+    let renamed_ranges = |position| -> Vec<Range> {
+        let edit = server.rename_symbol(&final_path, position, "BAR").unwrap();
+        edit.changes.unwrap()[&final_url].iter().map(|edit| edit.range).collect()
+    };
+
+    // user-defined function from common.glsl
+    assert!(renamed_ranges(Position::new(5, 26)).contains(&Range::new(Position::new(5, 24), Position::new(5, 28))));
+    // #define
+    assert!(renamed_ranges(Position::new(5, 34)).contains(&Range::new(Position::new(5, 33), Position::new(5, 36))));
+    // keyword, built-in type and built-in variable
+    assert!(server.rename_symbol(&final_path, Position::new(4, 1), "BAR").is_err());
+    assert!(server.rename_symbol(&final_path, Position::new(5, 20), "BAR").is_err());
+    assert!(server.rename_symbol(&final_path, Position::new(5, 3), "BAR").is_err());
+    // literal
+    assert!(server.rename_symbol(&final_path, Position::new(1, 13), "BAR").is_err());
+}
+
+#[test]
+fn test_prepare_rename_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(&final_path, "#version 120\n#define FOO 1.0\n#include \"/common.glsl\"\n\nvoid main() {\n\tgl_FragColor[0] = vec4(test() * FOO);\n}").unwrap();
+
+    server.gen_initial_graph();
+
+    let prepare_rename = |server: &mut MinecraftShaderLanguageServer, position: Position| {
+        server.run_command("prepareRename", vec![
+            serde_json::json!(final_path.to_str().unwrap()),
+            serde_json::to_value(position).unwrap(),
+        ])
+    };
+
+    let response = prepare_rename(&mut server, Position::new(5, 34)).unwrap();
+    assert_eq!(response, serde_json::json!({
+        "range": Range::new(Position::new(5, 33), Position::new(5, 36)),
+        "placeholder": "FOO",
+    }));
+
+    // keyword and built-in variable
+    assert!(prepare_rename(&mut server, Position::new(4, 1)).is_err());
+    assert!(prepare_rename(&mut server, Position::new(5, 3)).is_err());
+}
+
+#[test]
+fn test_why_included_command() {
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let composite_idx = graph.add_node(&composite_path);
+        let common_idx = graph.add_node(&common_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 3, start: 0, end: 0 });
+        graph.add_edge(composite_idx, common_idx, IncludePosition { line: 7, start: 0, end: 0 });
+    }
+
+    let command = commands::WhyIncludedCommand { graph: graph.clone() };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    let result = command.run_command(&tmp_path, path_arg(&common_path)).unwrap();
+    assert_eq!(result, serde_json::json!([
+        { "file": composite_path, "line": 7 },
+        { "file": final_path, "line": 3 },
+    ]));
+
+    let result = command.run_command(&tmp_path, path_arg(&final_path)).unwrap();
+    assert_eq!(result, serde_json::json!([]));
+
+    assert!(command.run_command(&tmp_path, path_arg(&tmp_path.join("shaders").join("missing.glsl"))).is_err());
+}
+
+#[test]
+fn test_preprocessor_active_lines() {
+    let source = "#ifdef FOO\na\n#else\nb\n#endif\n#ifndef FOO\nc\n#if defined(BAR)\nd\n#elif BAZ > 1\ne\n#endif\n#endif\n";
+
+    let defines: HashSet<String> = HashSet::new();
+    assert_eq!(
+        preprocessor::active_lines(source, &defines),
+        vec![true, false, true, true, true, true, true, true, false, true, true, true, true]
+    );
+
+    let defines: HashSet<String> = vec!["FOO".to_string()].into_iter().collect();
+    assert_eq!(
+        preprocessor::active_lines(source, &defines),
+        vec![true, true, true, false, true, true, false, false, false, false, false, false, true]
+    );
+}
+
+#[test]
+fn test_preprocessor_inject_defines() {
+    let source = "#version 120\n\nvoid main() {}\n";
+    assert_eq!(
+        preprocessor::inject_defines(source, &["FOO".into(), "BAR=2".into()]),
+        "#version 120\n#define FOO\n#define BAR 2\n#line 2\n\nvoid main() {}\n"
+    );
+
+    assert_eq!(
+        preprocessor::inject_defines("void main() {}", &["FOO".into()]),
+        "#define FOO\n#line 1\nvoid main() {}"
+    );
+
+    assert_eq!(preprocessor::inject_defines(source, &[]), source);
+}
+
+#[test]
+fn test_lint_with_defines() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#ifdef FEATURE\n#include \"/common.glsl\"\n#endif\n\nvoid main() {}\n").unwrap();
+    fs::write(tmp_path.join("shaders").join("common.glsl"), "float broken() {\n\treturn 0.5\n}").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .returning(|_, source| {
+            if source.contains("broken") && source.contains("#define FEATURE") {
+                Some("0(5) : error C0000: syntax error, unexpected '}'\n".into())
+            } else {
+                None
+            }
+        });
+    server.opengl_context = Rc::new(validator);
+
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    let diagnostics = server.lint_with_defines(&final_path, &[]).unwrap();
+    assert!(diagnostics.get(&final_url).unwrap().is_empty());
+
+    let diagnostics = server.lint_with_defines(&final_path, &["FEATURE".into()]).unwrap();
+    let final_diagnostics = diagnostics.get(&final_url).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Error));
+
+    // with an unrelated define, the include is in a provably inactive branch and is left out
+    let diagnostics = server.lint_with_defines(&final_path, &["OTHER".into()]).unwrap();
+    assert!(diagnostics.get(&final_url).unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_validator_stdout_windows_paths() {
+    let server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    // escaped backslashes as echoed back from our #line directives, with a stray forward slash
+    let mut windows_path = common_path.to_str().unwrap().replace('/', "\\").replace('\\', "\\\\");
+    windows_path = windows_path.replacen("\\\\", "/", 1);
+
+    let stdout = format!(
+        "{}(3) : error C0000: syntax error\nnot a path(2) : error C0000: skipped\n0(4) : warning C7022: unrecognized profile specifier\n",
+        windows_path
+    );
+
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    assert_eq!(diagnostics.len(), 2);
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].message, "syntax error");
+
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+}
+
+#[test]
+fn test_sort_diagnostics_stable_across_lints() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let outputs = vec![
+        "0(6) : error C0000: b\n0(4) : warning C0000: a\n0(6) : warning C0000: c\n0(6) : error C0000: a\n",
+        "0(6) : error C0000: a\n0(6) : warning C0000: c\n0(4) : warning C0000: a\n0(6) : error C0000: b\n",
+    ];
+    let mut calls = 0;
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(move |_, _| {
+            calls += 1;
+            Some(outputs[calls - 1].to_string())
+        });
+    server.opengl_context = Rc::new(validator);
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    let mut first = server.lint(&final_path).unwrap().remove(&final_url).unwrap();
+    let mut second = server.lint(&final_path).unwrap().remove(&final_url).unwrap();
+    assert_ne!(first, second);
+
+    sort_diagnostics(&mut first);
+    sort_diagnostics(&mut second);
+    assert_eq!(first, second);
+
+    let order: Vec<(u32, Option<DiagnosticSeverity>, &str)> = first.iter()
+        .map(|d| (d.range.start.line, d.severity, d.message.as_str()))
+        .collect();
+    assert_eq!(order, vec![
+        (2, Some(DiagnosticSeverity::Warning), "a"),
+        (4, Some(DiagnosticSeverity::Error), "a"),
+        (4, Some(DiagnosticSeverity::Error), "b"),
+        (4, Some(DiagnosticSeverity::Warning), "c"),
+    ]);
+}
+
+#[test]
+fn test_diagnostics_to_publish_sorted_per_file() {
+    let server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let (final_path, common_path) = if cfg!(windows) {
+        (PathBuf::from("C:\\shaders\\final.fsh"), PathBuf::from("C:\\shaders\\common.glsl"))
+    } else {
+        (PathBuf::from("/shaders/final.fsh"), PathBuf::from("/shaders/common.glsl"))
+    };
+    let common = common_path.to_str().unwrap().replace("\\", "\\\\");
+
+    // the validator reports in the order it found the errors, going back and forth between the files
+    let stdout = format!("0(9) : error C0000: c\n{common}(7) : error C0000: b\n0(3) : error C0000: a\n{common}(4) : error C0000: a\n", common = common);
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+
+    let mut published = server.diagnostics_to_publish(diagnostics, None);
+    published.sort_by(|a, b| a.uri.cmp(&b.uri));
+    let order: Vec<(Url, Vec<u32>)> = published.into_iter()
+        .map(|params| (params.uri, params.diagnostics.iter().map(|d| d.range.start.line).collect()))
+        .collect();
+    assert_eq!(order, vec![
+        (Url::from_file_path(&common_path).unwrap(), vec![2, 5]),
+        (Url::from_file_path(&final_path).unwrap(), vec![1, 7]),
+    ]);
+}
+
+#[test]
+fn test_find_includes_missing_file() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let missing = tmp_path.join("shaders").join("missing.fsh");
+    assert!(server.find_includes(&missing).is_err());
+
+    // neither panics nor adds a node for a file that can't be read
+    server.add_file_and_includes_to_graph(&missing);
+    assert!(server.graph.borrow_mut().find_node(&missing).is_none());
+
+    let includes = server.find_includes(&tmp_path.join("shaders").join("final.fsh")).unwrap();
+    assert_eq!(includes.len(), 1);
+    assert_eq!(includes[0].0, tmp_path.join("shaders").join("common.glsl"));
+}
+
+#[test]
+fn test_symbols_call_at_position() {
+    let source = "void main() {\n\tfloat x = mix(a, clamp(b, 0.0, 1.0), \n\t\tc);\n}";
+
+    assert_eq!(symbols::call_at_position(source, 1, 15), Some(("mix".to_string(), 0)));
+    assert_eq!(symbols::call_at_position(source, 1, 24), Some(("clamp".to_string(), 0)));
+    assert_eq!(symbols::call_at_position(source, 1, 28), Some(("clamp".to_string(), 1)));
+    assert_eq!(symbols::call_at_position(source, 2, 2), Some(("mix".to_string(), 2)));
+    assert_eq!(symbols::call_at_position(source, 1, 6), None);
+
+    let signatures = symbols::find_function_signatures("float test(vec2 uv, float t) {\n\treturn test(uv, t);\n}", "test");
+    assert_eq!(signatures, vec![symbols::FunctionSignature {
+        name: "test".into(),
+        return_type: "float".into(),
+        parameters: vec!["vec2 uv".into(), "float t".into()],
+    }]);
+    assert_eq!(signatures[0].label(), "float test(vec2 uv, float t)");
+}
+
+#[test]
+fn test_signature_help() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(tmp_path.join("shaders").join("common.glsl"), "float test(vec2 uv, float t) {\n\treturn t;\n}").unwrap();
+    fs::write(&final_path, "#version 120\n\n#include \"/common.glsl\"\n\nvoid main() {\n\tgl_FragColor = vec4(test(vec2(0.0), 1.0) * dot(a, b));\n}").unwrap();
+
+    server.gen_initial_graph();
+
+    // user-defined function from an include
+    let help = server.signature_help_at(&final_path, Position::new(5, 37)).unwrap().unwrap();
+    assert_eq!(help.signatures.len(), 1);
+    assert_eq!(help.signatures[0].label, "float test(vec2 uv, float t)");
+    assert_eq!(help.active_parameter, Some(1));
+
+    // built-in function
+    let help = server.signature_help_at(&final_path, Position::new(5, 51)).unwrap().unwrap();
+    assert_eq!(help.signatures[0].label, "float dot(genType x, genType y)");
+    assert_eq!(help.active_parameter, Some(1));
+
+    // not inside a call
+    assert!(server.signature_help_at(&final_path, Position::new(4, 4)).unwrap().is_none());
+}
+
+#[test]
+fn test_lint_dedups_diagnostics_across_trees() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::copy(tmp_path.join("shaders").join("final.fsh"), tmp_path.join("shaders").join("composite.fsh")).unwrap();
+
+    server.gen_initial_graph();
+
+    let output = format!("{}(4) : error C0000: syntax error\n", common_path.to_str().unwrap().replace("\\", "\\\\"));
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(move |_, _| Some(output.clone()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range.start.line, 2);
+}
+
+#[test]
+fn test_lint_all() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::copy(&final_path, &composite_path).unwrap();
+
+    server.gen_initial_graph();
+
+    let output = format!("{}(4) : error C0000: syntax error\n", common_path.to_str().unwrap().replace("\\", "\\\\"));
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(move |_, _| Some(output.clone()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint_all();
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range.start.line, 2);
+    assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+    assert!(diagnostics.get(&Url::from_file_path(&composite_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_update_published_files_drops_removed_includes() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    assert!(server.update_published_files(&final_path).is_empty());
+
+    fs::write(&final_path, "#version 120\n\nvoid main() {\n\tgl_FragColor[0] = vec4(0.0);\n}").unwrap();
+    server.update_includes(&final_path);
+
+    assert_eq!(server.update_published_files(&final_path), vec![Url::from_file_path(&common_path).unwrap()]);
+    assert!(server.update_published_files(&final_path).is_empty());
+}
+
+#[test]
+fn test_rebuild_graph() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let stale_path = tmp_path.join("shaders").join("stale.glsl");
+    server.graph.borrow_mut().add_node(&stale_path);
+    assert_eq!(server.graph.borrow().graph.node_count(), 3);
+
+    server.rebuild_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    assert_eq!(server.graph.borrow().graph.node_count(), 2);
+    assert_eq!(server.graph.borrow_mut().find_node(&stale_path), None);
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    assert_eq!(server.graph.borrow().child_node_names(final_idx), vec![common_path]);
+}
+
+#[test]
+fn test_find_includes_multiple_packs() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let tmp_path = tmp_dir.path().to_path_buf();
+    server.roots = vec![tmp_path.clone()];
+
+    for pack in &["PackA", "PackB"] {
+        let shaders = tmp_path.join(pack).join("shaders");
+        fs::create_dir_all(shaders.join("world0")).unwrap();
+        fs::write(shaders.join("common.glsl"), "float x;\n").unwrap();
+        fs::write(shaders.join("final.fsh"), "#version 120\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+        fs::write(shaders.join("world0").join("composite.fsh"), "#version 120\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+    }
+
+    server.gen_initial_graph();
+
+    let mut pack_roots = server.pack_roots.clone();
+    pack_roots.sort();
+    assert_eq!(pack_roots, vec![tmp_path.join("PackA").join("shaders"), tmp_path.join("PackB").join("shaders")]);
+
+    for pack in &["PackA", "PackB"] {
+        let shaders = tmp_path.join(pack).join("shaders");
+
+        let includes = server.find_includes(&shaders.join("final.fsh")).unwrap();
+        assert_eq!(includes.iter().map(|i| i.0.clone()).collect::<Vec<_>>(), vec![shaders.join("common.glsl")]);
+
+        let includes = server.find_includes(&shaders.join("world0").join("composite.fsh")).unwrap();
+        assert_eq!(includes.iter().map(|i| i.0.clone()).collect::<Vec<_>>(), vec![shaders.join("common.glsl")]);
+    }
+}
+
+#[test]
+fn test_parse_validator_stdout_error_codes() {
+    let server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let final_path = PathBuf::from_str(if cfg!(windows) { "C:\\shaders\\final.fsh" } else { "/shaders/final.fsh" }).unwrap();
+
+    let stdout = "0(3) : error C0000: syntax error\n0(4) : warning B1234: unknown class\n".to_string();
+
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let mut final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().clone();
+    sort_diagnostics(&mut final_diagnostics);
+    assert_eq!(final_diagnostics.len(), 2);
+
+    assert_eq!(final_diagnostics[0].code, Some(NumberOrString::String("C0000".into())));
+    assert_eq!(
+        final_diagnostics[0].code_description.as_ref().map(|d| d.href.as_str()),
+        Some("https://www.khronos.org/registry/OpenGL/specs/gl/GLSLangSpec.4.60.html")
+    );
+
+    assert_eq!(final_diagnostics[1].code, Some(NumberOrString::String("B1234".into())));
+    assert_eq!(final_diagnostics[1].code_description, None);
+}
+
+// Reports an error on every line containing ERROR_HERE, numbering lines the way the
+// validator does: `#line N` makes the line following it N + 1.
+fn emulate_validator_line_numbers(source: &str) -> String {
+    let re_line = Regex::new(r#"^#line (\d+)(?: "(.*)")?"#).unwrap();
+    let mut file = "0".to_string();
+    let mut line = 1;
+    let mut output = String::new();
+    for text in source.lines() {
+        if let Some(cap) = re_line.captures(text) {
+            line = cap[1].parse::<usize>().unwrap() + 1;
+            if let Some(path) = cap.get(2) {
+                file = path.as_str().to_string();
+            }
+            continue;
+        }
+        if text.contains("ERROR_HERE") {
+            output.push_str(&format!("{}({}) : error C0000: syntax error\n", file, line));
+        }
+        line += 1;
+    }
+    output
+}
+
+#[test]
+fn test_lint_existing_line_directives() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&common_path, "float a;\n#line 100\nfloat b;\nERROR_HERE\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, source| Some(emulate_validator_line_numbers(&source)));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range.start.line, 3);
+}
+
+#[test]
+fn test_include_tooltip() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    assert_eq!(server.include_tooltip(&final_path, &common_path), "/common.glsl");
+
+    let lib_path = tmp_path.join("shaders").join("lib").join("util.glsl");
+    assert_eq!(server.include_tooltip(&final_path, &lib_path), "/lib/util.glsl");
+
+    // paths escaping the shaderpack are shown in full rather than panicking
+    let outside_path = tmp_path.join("outside.glsl");
+    assert_eq!(server.include_tooltip(&final_path, &outside_path), outside_path.to_slash_lossy());
+}
+
+#[test]
+fn test_merged_view_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let command = commands::MergedViewCommand { server: &server, files: &files::DiskFileProvider };
+    let result = command.run_command(&tmp_path, vec![Value::String(Url::from_file_path(&final_path).unwrap().path().into())]).unwrap();
+
+    let source = result.get("source").unwrap().as_str().unwrap();
+    let lines = result.get("lines").unwrap().as_array().unwrap();
+    assert_eq!(lines.len(), source.lines().count());
+
+    let expected = vec![
+        serde_json::json!({ "file": final_path, "line": 0 }),
+        serde_json::json!({ "file": final_path, "line": 1 }),
+        Value::Null,
+        serde_json::json!({ "file": common_path, "line": 0 }),
+        serde_json::json!({ "file": common_path, "line": 1 }),
+        serde_json::json!({ "file": common_path, "line": 2 }),
+        Value::Null,
+        serde_json::json!({ "file": final_path, "line": 3 }),
+        serde_json::json!({ "file": final_path, "line": 4 }),
+    ];
+    assert_eq!(lines[..expected.len()].to_vec(), expected);
+
+    let composite_arg = vec![Value::String(Url::from_file_path(&common_path).unwrap().path().into())];
+    assert!(command.run_command(&tmp_path, composite_arg).is_err());
+}
+
+#[test]
+fn test_ignore_list_patterns() {
+    let ignore_list = ignore::IgnoreList::new(&["backup".into(), "**/vendor/*.glsl".into(), "/shaders/old?".into()]);
+
+    assert!(ignore_list.is_ignored(&PathBuf::from_slash("backup/shaders/final.fsh")));
+    assert!(ignore_list.is_ignored(&PathBuf::from_slash("packs/backup/shaders/final.fsh")));
+    assert!(!ignore_list.is_ignored(&PathBuf::from_slash("backups/shaders/final.fsh")));
+
+    assert!(ignore_list.is_ignored(&PathBuf::from_slash("shaders/lib/vendor/noise.glsl")));
+    assert!(!ignore_list.is_ignored(&PathBuf::from_slash("shaders/lib/vendor/noise.fsh")));
+
+    assert!(ignore_list.is_ignored(&PathBuf::from_slash("shaders/old1/final.fsh")));
+    assert!(!ignore_list.is_ignored(&PathBuf::from_slash("shaders/old12/final.fsh")));
+    assert!(!ignore_list.is_ignored(&PathBuf::from_slash("packs/shaders/old1/final.fsh")));
+}
+
+#[test]
+fn test_gen_initial_graph_ignore() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    fs::create_dir_all(tmp_path.join("backup")).unwrap();
+    fs::write(tmp_path.join("backup").join("final.fsh"), "#version 120\n").unwrap();
+    fs::create_dir_all(tmp_path.join("build")).unwrap();
+    fs::write(tmp_path.join("build").join("final.fsh"), "#version 120\n").unwrap();
+    fs::write(tmp_path.join(".gitignore"), "# build output\nbuild/\n").unwrap();
+
+    server.config.ignore = vec!["backup".into()];
+    server.gen_initial_graph();
+
+    assert_eq!(server.graph.borrow_mut().find_node(&tmp_path.join("backup").join("final.fsh")), None);
+    assert_ne!(server.graph.borrow_mut().find_node(&tmp_path.join("build").join("final.fsh")), None);
+    assert_ne!(server.graph.borrow_mut().find_node(&tmp_path.join("shaders").join("final.fsh")), None);
+
+    server.config.use_gitignore = true;
+    server.rebuild_graph();
+
+    assert_eq!(server.graph.borrow_mut().find_node(&tmp_path.join("build").join("final.fsh")), None);
+    assert_ne!(server.graph.borrow_mut().find_node(&tmp_path.join("shaders").join("final.fsh")), None);
+}
+
+#[test]
+fn test_gen_initial_graph_extensions() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    for name in &["gbuffers_basic.gsh", "deferred.csh", "final.fsh.glsl", "composite.frag", "notes.txt"] {
+        fs::write(shaders.join(name), "#version 430\n").unwrap();
+    }
+
+    server.config.unrecognized_roots.insert("frag".into(), configuration::UnrecognizedRootBehaviour::Fragment);
+    server.gen_initial_graph();
+
+    for name in &["gbuffers_basic.gsh", "deferred.csh", "final.fsh.glsl", "composite.frag"] {
+        assert_ne!(server.graph.borrow_mut().find_node(&shaders.join(name)), None, "{} is not indexed", name);
+    }
+    assert_eq!(server.graph.borrow_mut().find_node(&shaders.join("notes.txt")), None);
+}
+
+#[test]
+fn test_parse_validator_stdout_without_error_code() {
+    let server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let final_path = PathBuf::from_str(if cfg!(windows) { "C:\\shaders\\final.fsh" } else { "/shaders/final.fsh" }).unwrap();
+
+    let stdout = "0(3) : error: 'x' : undeclared identifier\n".to_string();
+
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].code, None);
+    assert_eq!(final_diagnostics[0].message, "'x' : undeclared identifier");
+}
+
+#[test]
+fn test_diagnostics_summary() {
+    let diagnostic = |severity| Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+        code: None,
+        severity: Some(severity),
+        source: None,
+        message: "".into(),
+        related_information: None,
+        tags: None,
+        code_description: Option::None,
+        data: Option::None,
+    };
+    let final_url = Url::parse("file:///shaders/final.fsh").unwrap();
+    let common_url = Url::parse("file:///shaders/common.glsl").unwrap();
+
+    let mut diagnostics: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    diagnostics.insert(final_url.clone(), vec![]);
+    assert_eq!(diagnostics_summary(&diagnostics), ("0 errors, 0 warnings".into(), "$(check)"));
+
+    diagnostics.insert(common_url, vec![diagnostic(DiagnosticSeverity::Warning), diagnostic(DiagnosticSeverity::Information)]);
+    assert_eq!(diagnostics_summary(&diagnostics), ("0 errors, 1 warning".into(), "$(warning)"));
+
+    diagnostics.insert(final_url, vec![diagnostic(DiagnosticSeverity::Error); 3]);
+    assert_eq!(diagnostics_summary(&diagnostics), ("3 errors, 1 warning".into(), "$(error)"));
+}
+
+#[test]
+fn test_find_includes_crlf() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    let lib_path = tmp_path.join("shaders").join("lib.glsl");
+    fs::write(&final_path, "#version 120\r\n\r\n#include \"/common.glsl\"\r\n\r\n#include \"/lib.glsl\r\"\r\nvoid main() {}\r\n").unwrap();
+    fs::write(&lib_path, "float lib() {\r\n\treturn 1.0;\r\n}\r\n").unwrap();
+
+    let includes = server.find_includes(&final_path).unwrap();
+    assert_eq!(includes, vec![
+        (common_path.clone(), IncludePosition { line: 2, start: 10, end: 22 }),
+        (lib_path.clone(), IncludePosition { line: 4, start: 10, end: 19 }),
+    ]);
+
+    server.gen_initial_graph();
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tree = server.get_dfs_for_node(final_idx).unwrap();
+    let sources = server.load_sources(&tree).unwrap();
+    assert!(sources.contains_key(&common_path));
+    assert!(sources.contains_key(&lib_path));
+}
+
+#[test]
+fn test_load_sources_bom_and_invalid_utf8() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    fs::write(&final_path, b"\xef\xbb\xbf#version 120\n\n#include \"/common.glsl\"\n\nvoid main() {}\n").unwrap();
+    fs::write(&common_path, b"// caf\xe9\nfloat test() {\n\treturn 0.5;\n}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tree = server.get_dfs_for_node(final_idx).unwrap();
+
+    let mut diagnostics = HashMap::new();
+    let sources = server.load_sources_with_diagnostics(&tree, &mut diagnostics).unwrap();
+
+    assert!(sources.get(&final_path).unwrap().starts_with("#version 120"));
+    assert_eq!(get_shader_version(sources.get(&final_path).unwrap()), Some(120));
+    assert!(sources.get(&common_path).unwrap().starts_with("// caf\u{fffd}\n"));
+
+    assert_eq!(diagnostics.len(), 1);
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+}
+
+#[test]
+fn test_dfs_dump_command() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let shaders = tmp_dir.path().join("shaders");
+
+    let final_path = shaders.join("final.fsh");
+    let common_path = shaders.join("common.glsl");
+    let lib_path = shaders.join("lib.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    let (common_idx, lib_idx) = {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let common_idx = graph.add_node(&common_path);
+        let lib_idx = graph.add_node(&lib_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(common_idx, lib_idx, IncludePosition { line: 1, start: 0, end: 0 });
+        (common_idx, lib_idx)
+    };
+
+    let command = commands::DfsDumpCommand { graph: graph.clone() };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    let result = command.run_command(&shaders, path_arg(&final_path)).unwrap();
+    assert_eq!(result, serde_json::json!({
+        "order": [
+            { "file": final_path, "parent": null },
+            { "file": common_path, "parent": final_path },
+            { "file": lib_path, "parent": common_path },
+        ],
+        "cycle": null,
+    }));
+
+    graph.borrow_mut().add_edge(lib_idx, common_idx, IncludePosition { line: 0, start: 0, end: 0 });
+
+    let result = command.run_command(&shaders, path_arg(&final_path)).unwrap();
+    assert_eq!(result.get("order").unwrap().as_array().unwrap().len(), 2);
+    assert_eq!(result.get("cycle").unwrap().get("files").unwrap(), &serde_json::json!([final_path, common_path, lib_path, common_path]));
+
+    assert!(command.run_command(&shaders, path_arg(&shaders.join("missing.glsl"))).is_err());
+}
+
+#[test]
+fn test_tree_type_from_extension() {
+    assert_eq!(TreeType::from_extension(OsStr::new("fsh")), Some(TreeType::Fragment));
+    assert_eq!(TreeType::from_extension(OsStr::new("vsh")), Some(TreeType::Vertex));
+    assert_eq!(TreeType::from_extension(OsStr::new("gsh")), Some(TreeType::Geometry));
+    assert_eq!(TreeType::from_extension(OsStr::new("csh")), Some(TreeType::Compute));
+
+    assert_eq!(TreeType::from_extension(OsStr::new("glsl")), None);
+    assert_eq!(TreeType::from_extension(OsStr::new("FSH")), None);
+    assert_eq!(TreeType::from_extension(OsStr::new("")), None);
+}
+
+#[test]
+fn test_lint_tessellation_roots() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let tcs_path = shaders.join("gbuffers_terrain.tcs");
+    let tes_path = shaders.join("gbuffers_terrain.tes");
+    let common_path = shaders.join("common.glsl");
+    for path in &[&tcs_path, &tes_path] {
+        fs::write(path, "#version 400\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+    }
+
+    server.gen_initial_graph();
+
+    let tcs_idx = server.graph.borrow_mut().find_node(&tcs_path).unwrap();
+    assert_eq!(server.graph.borrow().child_node_names(tcs_idx), vec![common_path]);
+    assert_ne!(server.graph.borrow_mut().find_node(&tes_path), None);
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .withf(|tree_type, _| *tree_type == TreeType::TessControl)
+        .times(1)
+        .returning(|_, _| None);
+    validator.expect_validate()
+        .withf(|tree_type, _| *tree_type == TreeType::TessEval)
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    server.lint(&tcs_path).unwrap();
+    server.lint(&tes_path).unwrap();
+}
+
+#[test]
+fn test_semantic_tokens() {
+    let source = "#version 120\n\n#include \"/common.glsl\"\n  #define FOO 1\nfloat x = FOO;\n#ifdef FOO\n#endif\n";
+
+    let tokens: Vec<(u32, u32, u32, u32)> = semantic_tokens::tokens(source).iter()
+        .map(|t| (t.delta_line, t.delta_start, t.length, t.token_type))
+        .collect();
+
+    assert_eq!(tokens, vec![
+        (0, 0, 8, 0),  // #version
+        (0, 9, 3, 3),  // 120
+        (2, 0, 8, 0),  // #include
+        (0, 9, 14, 1), // "/common.glsl"
+        (1, 2, 7, 0),  // #define
+        (0, 8, 3, 2),  // FOO
+        (2, 0, 6, 0),  // #ifdef
+        (0, 7, 3, 2),  // FOO
+        (1, 0, 6, 0),  // #endif
+    ]);
+
+    assert_eq!(semantic_tokens::legend().token_types.len(), 4);
+}
+
+#[test]
+fn test_folding_ranges() {
+    let source = "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\n#include \"/c.glsl\"\n\nvoid main() {\n\tif (true) {\n\t\tfloat x = 1.0; // }\n\t}\n\t/* {\n\t*/\n}\n#include \"/d.glsl\"\n";
+
+    let ranges: Vec<(u32, u32, Option<FoldingRangeKind>)> = folding::folding_ranges(source).into_iter()
+        .map(|r| (r.start_line, r.end_line, r.kind))
+        .collect();
+
+    assert_eq!(ranges, vec![
+        (1, 3, Some(FoldingRangeKind::Imports)),
+        (5, 10, None),
+        (6, 7, None),
+    ]);
+}
+
+#[test]
+fn test_document_colors() {
+    let source = "const vec3 sky = vec3(0.5, .25, 1.0);\nvec4 fog = vec4(1., 0.0f, 0, 0.5) * vec3(2.0, 0.0, 0.0);\nvec3 bad = vec3(0.5, 0.5, 0.5, 1.0);\nvec3 x = vec3(a, 0.0, 0.0);\n";
+
+    let colors: Vec<(Range, (f32, f32, f32, f32))> = colors::document_colors(source).into_iter()
+        .map(|c| (c.range, (c.color.red, c.color.green, c.color.blue, c.color.alpha)))
+        .collect();
+
+    assert_eq!(colors, vec![
+        (Range::new(Position::new(0, 17), Position::new(0, 36)), (0.5, 0.25, 1.0, 1.0)),
+        (Range::new(Position::new(1, 11), Position::new(1, 33)), (1.0, 0.0, 0.0, 0.5)),
+    ]);
+
+    let range = Range::new(Position::new(0, 17), Position::new(0, 36));
+    let presentations = colors::color_presentations(source, Color { red: 0.2, green: 1.0, blue: 0.125, alpha: 1.0 }, range);
+    assert_eq!(presentations[0].label, "vec3(0.2, 1.0, 0.125)");
+    assert_eq!(presentations[0].text_edit, Some(TextEdit::new(range, "vec3(0.2, 1.0, 0.125)".into())));
+
+    // a translucent color can't be a vec3
+    let presentations = colors::color_presentations(source, Color { red: 0.2, green: 1.0, blue: 0.125, alpha: 0.5 }, range);
+    assert_eq!(presentations[0].label, "vec4(0.2, 1.0, 0.125, 0.5)");
+}
+
+#[test]
+fn test_lint_include_diagnostics_source() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/missing.glsl\"\n\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| Some("0(3) : error C0000: syntax error\n".into()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 2);
+
+    let missing = final_diagnostics.iter().find(|d| d.source == Some(consts::INCLUDES_SOURCE.into())).unwrap();
+    assert_eq!(missing.severity, Some(DiagnosticSeverity::Error));
+    assert_eq!(missing.range, Range::new(Position::new(2, 10), Position::new(2, 23)));
+    assert!(final_diagnostics.iter().any(|d| d.source == Some(consts::SOURCE.into())));
+
+    // include cycles are reported by the server too
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n").unwrap();
+    fs::write(&common_path, "#include \"/final.fsh\"\n").unwrap();
+    server.rebuild_graph();
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].source, Some(consts::INCLUDES_SOURCE.into()));
+}
+
+#[test]
+fn test_lint_include_case_mismatch() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/Common.glsl\"\n\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+
+    // on case-sensitive file systems the include doesn't resolve at all and is reported as missing too
+    let mismatch = final_diagnostics.iter().find(|d| d.severity == Some(DiagnosticSeverity::Warning)).unwrap();
+    assert_eq!(mismatch.source, Some(consts::INCLUDES_SOURCE.into()));
+    assert_eq!(mismatch.range, Range::new(Position::new(2, 10), Position::new(2, 22)));
+    assert!(mismatch.message.contains(&format!("{:?}", common_path)));
+
+    // matching case isn't reported
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n\nvoid main() {}\n").unwrap();
+    server.rebuild_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_is_program_name() {
+    assert!(is_program_name("final", consts::PROGRAM_NAMES));
+    assert!(is_program_name("gbuffers_terrain", consts::PROGRAM_NAMES));
+    assert!(is_program_name("composite", consts::PROGRAM_NAMES));
+    assert!(is_program_name("composite15", consts::PROGRAM_NAMES));
+    assert!(is_program_name("shadowcomp1", consts::PROGRAM_NAMES));
+
+    assert!(!is_program_name("gbuffers_terain", consts::PROGRAM_NAMES));
+    assert!(!is_program_name("compositex", consts::PROGRAM_NAMES));
+    assert!(!is_program_name("final1", consts::PROGRAM_NAMES));
+}
+
+#[test]
+fn test_lint_unknown_program_name() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let typo_path = tmp_path.join("shaders").join("gbuffers_terain.fsh");
+    fs::copy(tmp_path.join("shaders").join("final.fsh"), &typo_path).unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&typo_path).unwrap();
+    let typo_diagnostics = diagnostics.get(&Url::from_file_path(&typo_path).unwrap()).unwrap();
+    assert_eq!(typo_diagnostics.len(), 1);
+    assert_eq!(typo_diagnostics[0].severity, Some(DiagnosticSeverity::Information));
+
+    server.config.program_names = Some(vec!["gbuffers_terain".into()]);
+    let diagnostics = server.lint(&typo_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&typo_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_lint_with_canned_validator() {
+    let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| StdoutNewline {
+        s: Box::new(io::sink()),
+    });
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    // line 3 of common.glsl once the #line directive offsets are taken into account
+    let output = format!("{}(4) : error C0000: syntax error\n", common_path.to_str().unwrap().replace("\\", "\\\\"));
+
+    let mut server = MinecraftShaderLanguageServer::with_validator(endpoint, Rc::new(opengl::CannedValidator {
+        output: Some(output),
+    }));
+    server.endpoint.request_shutdown();
+    server.roots = vec![tmp_path.clone()];
+
+    server.gen_initial_graph();
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range.start.line, 2);
+    assert_eq!(common_diagnostics[0].message, "syntax error");
+}
+
+#[test]
+fn test_lint_clamps_diagnostic_ranges() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    let common = common_path.to_str().unwrap().replace("\\", "\\\\");
+
+    let output = format!("0(0) : error C0000: underflow\n0(1) : error C0000: underflow\n{}(99) : error C0000: past the end\n", common);
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(move |_, _| Some(output.clone()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 2);
+    for diagnostic in final_diagnostics {
+        // "#version 120"
+        assert_eq!(diagnostic.range, Range::new(Position::new(0, 0), Position::new(0, 12)));
+    }
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    // "}"
+    assert_eq!(common_diagnostics[0].range, Range::new(Position::new(2, 0), Position::new(2, 1)));
+}
+
+#[test]
+fn test_apply_stage_args() {
+    let args = vec!["-DSHADOWS=2".to_string(), "-UFOG".to_string(), "--extension=GL_ARB_compute_shader".to_string(), "--bogus".to_string()];
+
+    let source = "#version 430\nvoid main() {}\n";
+    assert_eq!(
+        opengl::apply_stage_args(source, &args),
+        "#version 430\n#define SHADOWS 2\n#undef FOG\n#extension GL_ARB_compute_shader : enable\n#line 1\nvoid main() {}\n"
+    );
+
+    let source = "// header\n#version 120\nvoid main() {}\n";
+    assert_eq!(
+        opengl::apply_stage_args(source, &["-DLOW".to_string()]),
+        "// header\n#version 120\n#define LOW\n#line 2\nvoid main() {}\n"
+    );
+
+    assert_eq!(opengl::apply_stage_args("void main() {}\n", &["--extension=GL_EXT_gpu_shader4:require".to_string()]),
+        "#extension GL_EXT_gpu_shader4 : require\n#line 0\nvoid main() {}\n");
+
+    assert_eq!(opengl::apply_stage_args(source, &["--bogus".to_string()]), source);
+}
+
+#[test]
+fn test_validator_args_are_passed_to_validator() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({
+        "validatorArgs": {
+            "compute": ["-DCOMPUTE"],
+            "tessEval": ["-UFOG"]
+        }
+    })).unwrap();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_set_stage_args()
+        .withf(|args| args.len() == 2 && args[&TreeType::Compute] == vec!["-DCOMPUTE"] && args[&TreeType::TessEval] == vec!["-UFOG"])
+        .times(1)
+        .returning(|_| ());
+    server.opengl_context = Rc::new(validator);
+
+    server.set_validator_args(config.validator_args);
+}
+
+#[test]
+fn test_list_roots_command() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let shaders = tmp_dir.path().join("shaders");
+
+    let final_path = shaders.join("final.fsh");
+    let composite_path = shaders.join("composite.fsh");
+    let gbuffers_path = shaders.join("gbuffers_basic.vsh");
+    let common_path = shaders.join("common.glsl");
+    let unused_path = shaders.join("unused.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let composite_idx = graph.add_node(&composite_path);
+        let gbuffers_idx = graph.add_node(&gbuffers_path);
+        let common_idx = graph.add_node(&common_path);
+        graph.add_node(&unused_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(composite_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(gbuffers_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+    }
+
+    let command = commands::ListRootsCommand { graph };
+
+    let result = command.run_command(&shaders, vec![]).unwrap();
+    assert_eq!(result, serde_json::json!({
+        "fragment": [composite_path, final_path],
+        "vertex": [gbuffers_path],
+        "unrecognized": [unused_path],
+    }));
+}
+
+#[test]
+fn test_generate_merge_list_dedup_diamond() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let a_path = shaders.join("a.glsl");
+    let b_path = shaders.join("b.glsl");
+    let common_path = shaders.join("common.glsl");
+
+    fs::write(&final_path, "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&a_path, "#include \"/common.glsl\"\nfloat a;\n").unwrap();
+    fs::write(&b_path, "#include \"/common.glsl\"\nfloat b;\n").unwrap();
+    fs::write(&common_path, "float common;\n").unwrap();
+
+    let final_idx = server.graph.borrow_mut().add_node(&final_path);
+    let a_idx = server.graph.borrow_mut().add_node(&a_path);
+    let b_idx = server.graph.borrow_mut().add_node(&b_path);
+    let common_idx = server.graph.borrow_mut().add_node(&common_path);
+
+    server.graph.borrow_mut().add_edge(final_idx, a_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(final_idx, b_idx, IncludePosition { line: 2, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(a_idx, common_idx, IncludePosition { line: 0, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(b_idx, common_idx, IncludePosition { line: 0, start: 0, end: 0 });
+
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+
+    let graph_borrow = server.graph.borrow();
+
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, false);
+    assert_eq!(result.matches("float common;").count(), 2);
+
+    let escaped = |path: &PathBuf| path.to_str().unwrap().replace("\\", "\\\\");
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, true);
+    assert_eq!(result, format!(r#"#version 120
+#line 1 "{a}"
+#line 1 "{common}"
+float common;
+#line 2 "{a}"
+float a;
+#line 3 "{root}"
+#line 1 "{b}"
+#line 2 "{b}"
+float b;
+#line 4 "{root}"
+void main() {{}}
+"#, a = escaped(&a_path), b = escaped(&b_path), common = escaped(&common_path), root = escaped(&final_path)));
+}
+
+#[test]
+fn test_generate_merge_list_dedup_diamond_with_nested_includes() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let a_path = shaders.join("a.glsl");
+    let b_path = shaders.join("b.glsl");
+    let common_path = shaders.join("common.glsl");
+    let lib_path = shaders.join("lib.glsl");
+
+    fs::write(&final_path, "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&a_path, "float a;\n#include \"/common.glsl\"\n").unwrap();
+    fs::write(&b_path, "float b;\n#include \"/common.glsl\"\nfloat b2;\n").unwrap();
+    fs::write(&common_path, "#include \"/lib.glsl\"\nfloat common;\n").unwrap();
+    fs::write(&lib_path, "float lib;\n").unwrap();
+
+    let final_idx = server.graph.borrow_mut().add_node(&final_path);
+    let a_idx = server.graph.borrow_mut().add_node(&a_path);
+    let b_idx = server.graph.borrow_mut().add_node(&b_path);
+    let common_idx = server.graph.borrow_mut().add_node(&common_path);
+    let lib_idx = server.graph.borrow_mut().add_node(&lib_path);
+
+    server.graph.borrow_mut().add_edge(final_idx, a_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(final_idx, b_idx, IncludePosition { line: 2, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(a_idx, common_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(b_idx, common_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(common_idx, lib_idx, IncludePosition { line: 0, start: 0, end: 0 });
+
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+
+    let graph_borrow = server.graph.borrow();
+    let result = merge_views::generate_merge_list(&nodes, &sources, &graph_borrow, true);
+
+    assert_eq!(result.matches("float lib;").count(), 1);
+    assert_eq!(result.matches("float common;").count(), 1);
+    assert!(!result.contains("#include"));
+
+    // every line left over still maps back to where it came from
+    let lines = merge_views::line_map(&result, &final_path);
+    let position_of = |text: &str| {
+        let n = result.lines().position(|line| line == text).unwrap();
+        lines[n].clone().unwrap()
+    };
+    assert_eq!(position_of("float b;"), (b_path.clone(), 0));
+    assert_eq!(position_of("float b2;"), (b_path.clone(), 2));
+    assert_eq!(position_of("void main() {}"), (final_path.clone(), 3));
+}
+
+#[test]
+fn test_lint_missing_include_extension() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/01", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&composite_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(3)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+    assert_eq!(final_diagnostics[0].range.start.line, 0);
+
+    // reported for every top-level file when linting an include, but only where it is missing
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert_eq!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().len(), 1);
+    assert!(diagnostics.get(&Url::from_file_path(&composite_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_merged_definition_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n\nvoid main() {\n\tgl_FragColor[0] = vec4(test());\n}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let command = commands::MergedDefinitionCommand { server: &server, files: &files::DiskFileProvider };
+    let args = |path: &PathBuf, line: u32, character: u32| vec![
+        Value::String(Url::from_file_path(path).unwrap().path().into()),
+        serde_json::json!({ "line": line, "character": character }),
+    ];
+
+    // after the #version, #extension and the opening #line directive of common.glsl
+    let result = command.run_command(&tmp_path, args(&final_path, 5, 26)).unwrap();
+    assert_eq!(result, serde_json::json!({ "root": final_path, "line": 3 }));
+
+    let result = command.run_command(&tmp_path, args(&common_path, 0, 8)).unwrap();
+    assert_eq!(result, serde_json::json!({ "root": final_path, "line": 3 }));
+
+    // gl_FragColor isn't defined anywhere in the tree
+    let result = command.run_command(&tmp_path, args(&final_path, 5, 5)).unwrap();
+    assert_eq!(result, Value::Null);
+
+    assert!(command.run_command(&tmp_path, vec![Value::String(Url::from_file_path(&final_path).unwrap().path().into())]).is_err());
+}
+
+#[test]
+fn test_indent_levels() {
+    let source = "#version 120\nvoid main() {\nif (x) {\n#ifdef FOO\ny();\n#endif\n} else {\n/* a {\n  b }\n*/ z();\n}\n}\n";
+    assert_eq!(formatting::indent_levels(source), vec![
+        Some(0), Some(0), Some(1), Some(0), Some(2), Some(0), Some(1), Some(2), None, None, Some(1), Some(0), Some(0),
+    ]);
+}
+
+#[test]
+fn test_indent_line() {
+    let tabs = FormattingOptions {
+        tab_size: 4,
+        insert_spaces: false,
+        ..Default::default()
+    };
+    let spaces = FormattingOptions {
+        tab_size: 2,
+        insert_spaces: true,
+        ..Default::default()
+    };
+
+    // after typing a closing brace
+    let source = "void main() {\n\tif (x) {\n\t\ty();\n\t\t}\n}";
+    assert_eq!(formatting::indent_line(source, 3, &tabs), Some(TextEdit::new(Range::new(Position::new(3, 0), Position::new(3, 2)), "\t".into())));
+    assert_eq!(formatting::indent_line(source, 2, &tabs), None);
+    assert_eq!(formatting::indent_line(source, 2, &spaces), Some(TextEdit::new(Range::new(Position::new(2, 0), Position::new(2, 2)), "    ".into())));
+
+    // after typing a newline at the end of the document
+    let source = "void main() {\n";
+    assert_eq!(formatting::indent_line(source, 1, &tabs), Some(TextEdit::new(Range::new(Position::new(1, 0), Position::new(1, 0)), "\t".into())));
+
+    assert_eq!(formatting::indent_line(source, 5, &tabs), None);
+}
+
+#[test]
+fn test_document_text_prefers_open_buffer() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    assert_eq!(server.document_text(&common_path).unwrap(), fs::read_to_string(&common_path).unwrap());
+
+    server.documents.insert(common_path.clone(), "float unsaved;\n".into());
+    assert_eq!(server.document_text(&common_path).unwrap(), "float unsaved;\n");
+}
+
+#[test]
+fn test_format_document() {
+    let options = FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..Default::default()
+    };
+
+    let source = "  #version 120\n\n\n#include \"/b.glsl\"\n   #include \"/a.glsl\"\nvoid main() {\nif (x) {\n  for (int i = 0; i < 4; i++) {\ny(\"  {spaced}  \");\n\t\t}\n\n\n\n    } else {\n  #ifdef FOO\n        z();\n   #endif\n}\n/*\n      kept\n*/\n}\n";
+    let formatted = "#version 120\n\n#include \"/b.glsl\"\n#include \"/a.glsl\"\nvoid main() {\n    if (x) {\n        for (int i = 0; i < 4; i++) {\n            y(\"  {spaced}  \");\n        }\n\n    } else {\n#ifdef FOO\n        z();\n#endif\n    }\n    /*\n      kept\n*/\n}\n";
+    assert_eq!(formatting::format_document(source, &options), formatted);
+
+    // formatting is stable
+    assert_eq!(formatting::format_document(formatted, &options), formatted);
+    assert_eq!(formatting::format_document_edit(formatted, &options), None);
+
+    let edit = formatting::format_document_edit(source, &options).unwrap();
+    assert_eq!(edit.range, Range::new(Position::new(0, 0), Position::new(22, 0)));
+    assert_eq!(edit.new_text, formatted);
+
+    // line endings are kept
+    assert_eq!(formatting::format_document("void main() {\r\nx();\r\n}\r\n", &options), "void main() {\r\n    x();\r\n}\r\n");
+}
+
+#[test]
+fn test_format_range() {
+    let options = FormattingOptions {
+        tab_size: 4,
+        insert_spaces: false,
+        ..Default::default()
+    };
+
+    let source = "void a() {\nx();\n}\nvoid b() {\nif (y) {\nz();\n  \n}\n}\n";
+
+    // only the selected lines are touched, indented by the blocks they are in
+    assert_eq!(formatting::format_range(source, 4, 6, &options), vec![
+        TextEdit::new(Range::new(Position::new(4, 0), Position::new(4, 0)), "\t".into()),
+        TextEdit::new(Range::new(Position::new(5, 0), Position::new(5, 0)), "\t\t".into()),
+        TextEdit::new(Range::new(Position::new(6, 0), Position::new(6, 2)), "".into()),
+    ]);
+
+    assert_eq!(formatting::format_range(source, 0, 0, &options), vec![]);
+    assert_eq!(formatting::format_range(source, 1, 1, &options), vec![
+        TextEdit::new(Range::new(Position::new(1, 0), Position::new(1, 0)), "\t".into()),
+    ]);
+    assert_eq!(formatting::format_range(source, 20, 30, &options), vec![]);
+}
+
+#[test]
+fn test_merge_cache() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+    let mut sources = server.load_sources(&nodes).unwrap();
+
+    let graph = server.graph.borrow();
+    let mut cache = merge_views::MergeCache::default();
+
+    let first = cache.get_or_merge(&nodes, &sources, &graph, false);
+    assert_eq!(first.source, merge_views::generate_merge_list(&nodes, &sources, &graph, false));
+    assert_eq!(first.line_map, merge_views::line_map(&first.source, &final_path));
+
+    let second = cache.get_or_merge(&nodes, &sources, &graph, false);
+    assert!(Rc::ptr_eq(&first, &second));
+
+    // different options or contents are merged again
+    assert!(!Rc::ptr_eq(&first, &cache.get_or_merge(&nodes, &sources, &graph, true)));
+    sources.insert(common_path.clone(), "float changed;\n".into());
+    let changed = cache.get_or_merge(&nodes, &sources, &graph, false);
+    assert!(!Rc::ptr_eq(&first, &changed));
+    assert!(changed.source.contains("float changed;"));
+
+    cache.invalidate(&common_path);
+    assert!(!Rc::ptr_eq(&changed, &cache.get_or_merge(&nodes, &sources, &graph, false)));
+}
+
+#[test]
+fn test_lint_include_chain_related_information() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let a_path = shaders.join("a.glsl");
+    let b_path = shaders.join("b.glsl");
+    let c_path = shaders.join("c.glsl");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/a.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&a_path, "float a;\n#include \"/b.glsl\"\n").unwrap();
+    fs::write(&b_path, "#include \"/c.glsl\"\n").unwrap();
+    fs::write(&c_path, "float c;\nfloat c2;\nERROR\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let output = format!("{}(4) : error C0000: syntax error\n", c_path.to_str().unwrap().replace("\\", "\\\\"));
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(move |_, _| Some(output.clone()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&c_path).unwrap();
+    let c_diagnostics = diagnostics.get(&Url::from_file_path(&c_path).unwrap()).unwrap();
+    assert_eq!(c_diagnostics.len(), 1);
+
+    let include = |path: &PathBuf, line: u32, name: &str| DiagnosticRelatedInformation {
+        location: Location::new(Url::from_file_path(path).unwrap(), Range::new(Position::new(line, 10), Position::new(line, 17))),
+        message: format!("{} is included here", name),
+    };
+    assert_eq!(c_diagnostics[0].related_information, Some(vec![
+        include(&final_path, 2, "a.glsl"),
+        include(&a_path, 1, "b.glsl"),
+        include(&b_path, 0, "c.glsl"),
+    ]));
+
+    // nothing to trace for errors in the top-level file itself
+    let stdout = "0(3) : error C0000: syntax error\n".to_string();
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tree = server.get_dfs_for_node(final_idx).unwrap();
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &tree);
+    assert_eq!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap()[0].related_information, None);
+}
+
+#[test]
+fn test_parse_validator_stdout_warnings_as_errors() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let final_path = PathBuf::from_str(if cfg!(windows) { "C:\\shaders\\final.fsh" } else { "/shaders/final.fsh" }).unwrap();
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    let stdout = "0(3) : warning C7050: 'x' might be used before being initialized\n0(4) : error C0000: syntax error\n";
+
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let severities: Vec<_> = diagnostics.get(&final_url).unwrap().iter().map(|d| d.severity).collect();
+    assert_eq!(severities, vec![Some(DiagnosticSeverity::Warning), Some(DiagnosticSeverity::Error)]);
+
+    server.config = serde_json::from_value(serde_json::json!({ "warningsAsErrors": true })).unwrap();
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let severities: Vec<_> = diagnostics.get(&final_url).unwrap().iter().map(|d| d.severity).collect();
+    assert_eq!(severities, vec![Some(DiagnosticSeverity::Error), Some(DiagnosticSeverity::Error)]);
+}
+
+#[test]
+fn test_log_level_config() {
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({})).unwrap();
+    assert_eq!(config.log_level.level_filter(), log::LevelFilter::Info);
+
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({
+        "logLevel": "trace"
+    })).unwrap();
+    assert_eq!(config.log_level.level_filter(), log::LevelFilter::Trace);
+
+    assert!(serde_json::from_value::<configuration::Configuration>(serde_json::json!({
+        "logLevel": "verbose"
+    })).is_err());
+}
+
+#[test]
+fn test_document_links_skip_deleted_targets() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let links = server.document_links(&final_path);
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].target, Some(Url::from_file_path(&common_path).unwrap()));
+    assert_eq!(links[0].range, Range::new(Position::new(2, 10), Position::new(2, 22)));
+    assert_eq!(links[1].target, Some(Url::parse(consts::INCLUDE_EXTENSION_DOCUMENTATION).unwrap()));
+    assert_eq!(links[1].range, Range::new(Position::new(1, 11), Position::new(1, 38)));
+
+    // the edge outlives the file until final.fsh is saved again
+    fs::remove_file(&common_path).unwrap();
+    let links = server.document_links(&final_path);
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].target, Some(Url::parse(consts::INCLUDE_EXTENSION_DOCUMENTATION).unwrap()));
+}
+
+#[test]
+fn test_find_includes_parent_traversal() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let tmp_path = tmp_dir.path().to_path_buf();
+    server.roots = vec![tmp_path.clone()];
+
+    let shaders = tmp_path.join("shaders");
+    let header_path = shaders.join("lib").join("common.glsl");
+    let final_path = shaders.join("final.fsh");
+    let composite_path = shaders.join("program").join("composite.fsh");
+    fs::create_dir_all(shaders.join("lib")).unwrap();
+    fs::create_dir_all(shaders.join("program")).unwrap();
+    fs::write(&header_path, "float x;\n").unwrap();
+    fs::write(&final_path, "#version 120\n#include \"./lib/common.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&composite_path, "#version 120\n#include \"../program/../lib/common.glsl\"\nvoid main() {}\n").unwrap();
+
+    let includes = server.find_includes(&composite_path).unwrap();
+    assert_eq!(includes.iter().map(|i| i.0.clone()).collect::<Vec<_>>(), vec![header_path.clone()]);
+
+    server.gen_initial_graph();
+
+    let header_nodes = server.graph.borrow().graph.node_indices()
+        .filter(|n| server.graph.borrow().get_node(*n).ends_with("common.glsl"))
+        .count();
+    assert_eq!(header_nodes, 1);
+
+    let header_idx = server.graph.borrow_mut().find_node(&header_path).unwrap();
+    let mut parents: Vec<PathBuf> = server.graph.borrow().parent_node_indexes(header_idx).into_iter()
+        .map(|n| server.graph.borrow().get_node(n))
+        .collect();
+    parents.sort();
+    assert_eq!(parents, vec![final_path, composite_path]);
+
+    assert_eq!(normalize_path(Path::new("/a/./b/../../../c")), PathBuf::from("/c"));
+    assert_eq!(normalize_path(Path::new("../a/../b")), PathBuf::from("../b"));
+}
+
+#[test]
+fn test_graph_dedups_equivalent_paths() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let shaders = tmp_dir.path().join("shaders");
+    fs::create_dir_all(&shaders).unwrap();
+    let common_path = shaders.join("common.glsl");
+    fs::write(&common_path, "float x;\n").unwrap();
+
+    let mut graph = graph::CachedStableGraph::new();
+    let idx = graph.add_node(&common_path);
+
+    assert_eq!(graph.add_node(&shaders.join(".").join("common.glsl")), idx);
+    assert_eq!(graph.find_node(&shaders.join("lib").join("..").join("common.glsl")), Some(idx));
+    // files that don't exist yet are deduped too
+    let missing_idx = graph.add_node(&shaders.join("missing.glsl"));
+    assert_eq!(graph.find_node(&shaders.join(".").join("missing.glsl")), Some(missing_idx));
+
+    #[cfg(target_family = "unix")]
+    {
+        let link_path = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(&shaders, &link_path).unwrap();
+        assert_eq!(graph.add_node(&link_path.join("common.glsl")), idx);
+    }
+
+    assert_eq!(graph.graph.node_count(), 2);
+    // the node keeps the path it was first added with
+    assert_eq!(graph.get_node(idx), common_path);
+}
+
+#[test]
+fn test_graph_canonicalizes_each_path_once() {
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let shaders = tmp_dir.path().join("shaders");
+    fs::create_dir_all(&shaders).unwrap();
+    let common_path = shaders.join("common.glsl");
+    fs::write(&common_path, "float x;\n").unwrap();
+
+    let mut graph = graph::CachedStableGraph::new();
+
+    // nodes that aren't cached are searched for by key too
+    let idx = graph.graph.add_node(common_path.to_str().unwrap().to_string());
+    assert_eq!(graph.find_node(&shaders.join(".").join("common.glsl")), Some(idx));
+
+    #[cfg(target_family = "unix")]
+    {
+        let link_path = tmp_dir.path().join("link");
+        std::os::unix::fs::symlink(&shaders, &link_path).unwrap();
+        let linked_common = link_path.join("common.glsl");
+        assert_eq!(graph.key(&linked_common), graph.key(&common_path));
+
+        // the key of a path that has been seen isn't looked up on disk again until the graph is cleared
+        fs::remove_file(&link_path).unwrap();
+        assert_eq!(graph.key(&linked_common), graph.key(&common_path));
+        graph.clear();
+        assert_eq!(graph.key(&linked_common), linked_common);
+    }
+}
+
+#[test]
+fn test_lint_validate_timeout() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.config.validate_timeout_ms = Some(250);
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate_with_timeout()
+        .withf(|_, _, timeout| *timeout == std::time::Duration::from_millis(250))
+        .times(1)
+        .returning(|_, _, timeout| Err(opengl::ValidationError::TimedOut(timeout)));
+    server.opengl_context = Rc::new(validator);
+
+    // a timed out validation doesn't fail the lint, it just has no validator output
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_opengl_rejects_validate_timeout() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    server.workspace_change_configuration(DidChangeConfigurationParams {
+        settings: serde_json::json!({ "mcglsl": { "validator": "opengl", "validateTimeoutMs": 250 } }),
+    });
+    assert_eq!(server.config.validate_timeout_ms, None);
+
+    server.workspace_change_configuration(DidChangeConfigurationParams {
+        settings: serde_json::json!({ "mcglsl": { "validator": "static", "validateTimeoutMs": 250 } }),
+    });
+    assert_eq!(server.config.validate_timeout_ms, Some(250));
+}
+
+#[test]
+fn test_gl_info_command() {
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_info()
+        .times(1)
+        .returning(|| opengl::ValidatorInfo {
+            vendor: "NVIDIA Corporation".into(),
+            renderer: "GeForce GTX 1080/PCIe/SSE2".into(),
+            version: "4.6.0 NVIDIA 460.39".into(),
+            glsl_version: "4.60 NVIDIA".into(),
+        });
+
+    let command = commands::GlInfoCommand { validator: &validator };
+
+    let result = command.run_command(&PathBuf::new(), vec![]).unwrap();
+    assert_eq!(result, serde_json::json!({
+        "vendor": "NVIDIA Corporation",
+        "renderer": "GeForce GTX 1080/PCIe/SSE2",
+        "version": "4.6.0 NVIDIA 460.39",
+        "glslVersion": "4.60 NVIDIA",
+    }));
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_path_from_url_spaces_and_unicode() {
+    let path = PathBuf::from_url(Url::parse("file:///home/user/My%20Shaders/final.fsh").unwrap());
+    assert_eq!(path, PathBuf::from("/home/user/My Shaders/final.fsh"));
+
+    let path = PathBuf::from_url(Url::parse("file:///home/user/%C3%A9t%C3%A9/%E5%BD%B1.fsh").unwrap());
+    assert_eq!(path, PathBuf::from("/home/user/été/影.fsh"));
+
+    let path = PathBuf::from("/home/user/My Shaders/été/final.fsh");
+    assert_eq!(PathBuf::from_url(Url::from_file_path(&path).unwrap()), path);
+
+    let path = PathBuf::from_json(&serde_json::json!("/home/user/My Shaders/\"quoted\".fsh")).unwrap();
+    assert_eq!(path, PathBuf::from("/home/user/My Shaders/\"quoted\".fsh"));
+
+    let path = PathBuf::from_json(&serde_json::json!("/home/user/My%20Shaders/final.fsh")).unwrap();
+    assert_eq!(path, PathBuf::from("/home/user/My Shaders/final.fsh"));
+}
+
+#[test]
+#[cfg(target_family = "windows")]
+fn test_path_from_url_spaces_and_unicode() {
+    let path = PathBuf::from_url(Url::parse("file:///c%3A/Users/user/My%20Shaders/%C3%A9t%C3%A9.fsh").unwrap());
+    assert_eq!(path, PathBuf::from("c:\\Users\\user\\My Shaders\\été.fsh"));
+
+    let path = PathBuf::from_json(&serde_json::json!("/c:/Users/user/My Shaders/final.fsh")).unwrap();
+    assert_eq!(path, PathBuf::from("c:\\Users\\user\\My Shaders\\final.fsh"));
+}
+
+#[test]
+fn test_workspace_folders() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let (_tmp_dir_a, tmp_path_a) = copy_to_tmp_dir("./testdata/06");
+    let (_tmp_dir_b, tmp_path_b) = copy_to_tmp_dir("./testdata/06");
+    server.roots = vec![tmp_path_a.clone()];
+
+    server.gen_initial_graph();
+    assert_eq!(server.graph.borrow().graph.node_count(), 2);
+
+    server.did_change_workspace_folders(DidChangeWorkspaceFoldersParams {
+        event: WorkspaceFoldersChangeEvent {
+            added: vec![WorkspaceFolder { uri: Url::from_directory_path(&tmp_path_b).unwrap(), name: "b".into() }],
+            removed: vec![],
+        },
+    });
+    assert_eq!(server.graph.borrow().graph.node_count(), 4);
+    assert_eq!(server.pack_roots.len(), 2);
+
+    // includes resolve against the pack of the folder they are in
+    let final_b = tmp_path_b.join("shaders").join("final.fsh");
+    let includes = server.find_includes(&final_b).unwrap();
+    assert_eq!(includes[0].0, tmp_path_b.join("shaders").join("common.glsl"));
+
+    server.did_change_workspace_folders(DidChangeWorkspaceFoldersParams {
+        event: WorkspaceFoldersChangeEvent {
+            added: vec![],
+            removed: vec![WorkspaceFolder { uri: Url::from_directory_path(&tmp_path_a).unwrap(), name: "a".into() }],
+        },
+    });
+    assert_eq!(server.roots, vec![tmp_path_b.clone()]);
+    assert_eq!(server.graph.borrow().graph.node_count(), 2);
+    assert!(server.graph.borrow_mut().find_node(&tmp_path_a.join("shaders").join("final.fsh")).is_none());
+}
+
+#[test]
+fn test_lint_if_changed_skips_unchanged_trees() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let output = format!("{}(4) : error C0000: syntax error\n", common_path.to_str().unwrap().replace("\\", "\\\\"));
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(move |_, _| Some(output.clone()));
+    server.opengl_context = Rc::new(validator);
+
+    let first = server.lint_if_changed(&final_path).unwrap();
+    assert_eq!(first.get(&Url::from_file_path(&common_path).unwrap()).unwrap().len(), 1);
+
+    // nothing changed, so the validator isn't run again
+    assert_eq!(server.lint_if_changed(&final_path).unwrap(), first);
+
+    // a change to an included file invalidates the tree
+    fs::write(&common_path, "sample text\n\nfloat x;\n").unwrap();
+    server.lint_if_changed(&final_path).unwrap();
+}
+
+#[test]
+fn test_lint_exclude() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::copy(&final_path, &composite_path).unwrap();
+
+    server.gen_initial_graph();
+    server.config.exclude_lint = vec!["shaders/composite.fsh".into()];
+
+    // only final.fsh's tree is validated when linting the file they both include
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(&final_path).unwrap()));
+    assert!(!diagnostics.contains_key(&Url::from_file_path(&composite_path).unwrap()));
+
+    // linting an excluded file only clears its diagnostics, while it stays in the graph
+    let diagnostics = server.lint(&composite_path).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics.get(&Url::from_file_path(&composite_path).unwrap()).unwrap().is_empty());
+    assert!(server.graph.borrow_mut().find_node(&composite_path).is_some());
+}
+
+#[test]
+fn test_find_includes_without_extension() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    fs::create_dir_all(shaders.join("lib")).unwrap();
+    fs::write(shaders.join("lib").join("noise.inc"), "float noise;\n").unwrap();
+    fs::write(shaders.join("lib").join("both.inc"), "float a;\n").unwrap();
+    fs::write(shaders.join("lib").join("both.glsl"), "float b;\n").unwrap();
+    fs::write(&final_path, "#version 120\n#include \"/common\"\n#include \"lib/noise\"\n#include \"lib/both\"\n#include \"lib/missing\"\nvoid main() {}\n").unwrap();
+
+    let includes: Vec<PathBuf> = server.find_includes(&final_path).unwrap().into_iter().map(|i| i.0).collect();
+    assert_eq!(includes, vec![
+        shaders.join("common.glsl"),
+        shaders.join("lib").join("noise.inc"),
+        // .glsl is tried first
+        shaders.join("lib").join("both.glsl"),
+        // left as written, to be reported missing
+        shaders.join("lib").join("missing"),
+    ]);
+}
+
+#[test]
+fn test_version_hover_describes_invocation() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_describe_invocation()
+        .withf(|tree_type| *tree_type == TreeType::Fragment)
+        .times(1)
+        .returning(|_| "glslangValidator -S frag".into());
+    server.opengl_context = Rc::new(validator);
+
+    let hover = server.version_hover(&final_path, Position::new(0, 3)).unwrap().unwrap();
+    assert_eq!(hover.range, Some(Range::new(Position::new(0, 0), Position::new(0, 12))));
+    match hover.contents {
+        HoverContents::Markup(markup) => assert!(markup.value.contains("Fragment") && markup.value.contains("glslangValidator -S frag")),
+        contents => panic!("expected markup, got {:?}", contents),
+    }
+
+    // only the #version line of a top-level file
+    assert!(server.version_hover(&final_path, Position::new(2, 3)).unwrap().is_none());
+    fs::write(&common_path, "#version 120\nfloat x;\n").unwrap();
+    assert!(server.version_hover(&common_path, Position::new(0, 3)).unwrap().is_none());
+}
+
+#[test]
+fn test_lint_empty_file() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let empty_path = tmp_path.join("shaders").join("composite.fsh");
+    fs::write(&empty_path, "").unwrap();
+
+    server.gen_initial_graph();
+
+    // an empty file is neither merged into anything nor validated
+    let node = server.graph.borrow_mut().find_node(&empty_path).unwrap();
+    let nodes = server.get_dfs_for_node(node).unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+    assert_eq!(merge_views::generate_merge_list(&nodes, &sources, &server.graph.borrow(), false), "");
+    assert_eq!(merge_views::generate_merge_list(&[], &sources, &server.graph.borrow(), false), "");
+
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+
+    let diagnostics = server.lint(&empty_path).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics.get(&Url::from_file_path(&empty_path).unwrap()).unwrap().is_empty());
+
+    fs::write(&empty_path, "\n  \n").unwrap();
+    let diagnostics = server.lint(&empty_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&empty_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_filter_diagnostics_by_severity() {
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({})).unwrap();
+    assert_eq!(config.min_severity, None);
+
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({
+        "minSeverity": "warning"
+    })).unwrap();
+    assert_eq!(config.min_severity, Some(configuration::MinSeverity::Warning));
+
+    let diagnostic = |severity| Diagnostic {
+        range: Range::new(Position::new(0, 0), Position::new(0, 1)),
+        code: Option::None,
+        severity,
+        source: Some(consts::SOURCE.into()),
+        message: "message".into(),
+        related_information: Option::None,
+        tags: Option::None,
+        code_description: Option::None,
+        data: Option::None,
+    };
+
+    let url = Url::parse("file:///shaders/final.fsh").unwrap();
+    let mut diagnostics = HashMap::new();
+    diagnostics.insert(url.clone(), vec![
+        diagnostic(Some(DiagnosticSeverity::Error)),
+        diagnostic(Some(DiagnosticSeverity::Warning)),
+        diagnostic(Some(DiagnosticSeverity::Information)),
+        diagnostic(None),
+    ]);
+
+    filter_diagnostics_by_severity(&mut diagnostics, configuration::MinSeverity::Warning.severity());
+    let severities: Vec<Option<DiagnosticSeverity>> = diagnostics[&url].iter().map(|d| d.severity).collect();
+    assert_eq!(severities, vec![Some(DiagnosticSeverity::Error), Some(DiagnosticSeverity::Warning)]);
+
+    filter_diagnostics_by_severity(&mut diagnostics, configuration::MinSeverity::Error.severity());
+    assert_eq!(diagnostics[&url].len(), 1);
+}
+
+#[test]
+fn test_graph_stats_command() {
+    let shaders = PathBuf::from("/shaders");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&shaders.join("final.fsh"));
+        let composite_idx = graph.add_node(&shaders.join("composite.fsh"));
+        let common_idx = graph.add_node(&shaders.join("common.glsl"));
+        let lighting_idx = graph.add_node(&shaders.join("lighting.glsl"));
+        let noise_idx = graph.add_node(&shaders.join("noise.glsl"));
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(composite_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(composite_idx, noise_idx, IncludePosition { line: 3, start: 0, end: 0 });
+        graph.add_edge(common_idx, lighting_idx, IncludePosition { line: 1, start: 0, end: 0 });
+        graph.add_edge(lighting_idx, noise_idx, IncludePosition { line: 1, start: 0, end: 0 });
+        // a cycle isn't followed round
+        graph.add_edge(noise_idx, common_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    }
+
+    let command = commands::GraphStatsCommand { graph };
+
+    let result = command.run_command(&shaders, vec![]).unwrap();
+    assert_eq!(result, serde_json::json!({
+        "nodes": 5,
+        "edges": 6,
+        "roots": 2,
+        "maxDepth": 3,
+    }));
+}
+
+#[test]
+fn test_shaders_properties_disabled_programs() {
+    let properties = properties::ShadersProperties::parse(
+        "# comment\nprogram.composite1.enabled = false\nprogram.world-1/gbuffers_water.enabled=false\nprogram.deferred.enabled = SSAO\nprogram.final.enabled = \\\n    false\n"
+    );
+    let pack_root = PathBuf::from("/shaders");
+    assert!(properties.is_disabled(&pack_root, &pack_root.join("composite1.fsh")));
+    assert!(properties.is_disabled(&pack_root, &pack_root.join("composite1.vsh")));
+    assert!(properties.is_disabled(&pack_root, &pack_root.join("world-1").join("gbuffers_water.fsh")));
+    assert!(properties.is_disabled(&pack_root, &pack_root.join("final.fsh")));
+    // conditions on options can't be evaluated, so the program is assumed to be enabled
+    assert!(!properties.is_disabled(&pack_root, &pack_root.join("deferred.fsh")));
+    assert!(!properties.is_disabled(&pack_root, &pack_root.join("gbuffers_water.fsh")));
+
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::copy(&final_path, &composite_path).unwrap();
+    fs::write(tmp_path.join("shaders").join("shaders.properties"), "program.final.enabled = false\n").unwrap();
+
+    server.gen_initial_graph();
+
+    // only composite.fsh's tree is validated
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(&composite_path).unwrap()));
+    assert!(!diagnostics.contains_key(&Url::from_file_path(&final_path).unwrap()));
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Information));
+    assert!(final_diagnostics[0].message.contains("shaders.properties"));
+}
+
+#[test]
+fn test_ancestors_command() {
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let composite_path = shaders.join("composite.fsh");
+    let common_path = shaders.join("common.glsl");
+    let lighting_path = shaders.join("lighting.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let composite_idx = graph.add_node(&composite_path);
+        let common_idx = graph.add_node(&common_path);
+        let lighting_idx = graph.add_node(&lighting_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(composite_idx, common_idx, IncludePosition { line: 3, start: 0, end: 0 });
+        graph.add_edge(common_idx, lighting_idx, IncludePosition { line: 1, start: 0, end: 0 });
+        graph.add_edge(final_idx, lighting_idx, IncludePosition { line: 4, start: 0, end: 0 });
+    }
+
+    let command = commands::AncestorsCommand { graph };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    let result = command.run_command(&tmp_path, path_arg(&lighting_path)).unwrap();
+    assert_eq!(result, serde_json::json!([
+        { "file": common_path, "includes": lighting_path, "line": 1 },
+        { "file": final_path, "includes": lighting_path, "line": 4 },
+        { "file": composite_path, "includes": common_path, "line": 3 },
+        { "file": final_path, "includes": common_path, "line": 2 },
+    ]));
+
+    let result = command.run_command(&tmp_path, path_arg(&final_path)).unwrap();
+    assert_eq!(result, serde_json::json!([]));
+}
+
+#[test]
+fn test_lint_include_outside_pack() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let outside_path = tmp_path.join("outside.glsl");
+    fs::write(&outside_path, "float outside;\n").unwrap();
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n#include \"../outside.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+    assert_eq!(final_diagnostics[0].source, Some(consts::INCLUDES_SOURCE.into()));
+    assert_eq!(final_diagnostics[0].range, Range::new(Position::new(3, 10), Position::new(3, 25)));
+    assert!(final_diagnostics[0].message.contains("outside of the shaderpack"));
+}
+
+#[test]
+fn test_static_validator() {
+    assert_eq!(static_validator::check("#version 120\nvoid main() {\n\tvec4(0.0);\n}\n"), None);
+    // the #else branch repeats the opening line of the first one
+    assert_eq!(static_validator::check("#version 120\n#ifdef FOO\nvoid main() {\n#else\nvoid main() {\n#endif\n}\n"), None);
+    assert_eq!(
+        static_validator::check("#version 120\nvoid main() {\n\tvec4(0.0];\n/* {\n#line 1 \"/common.glsl\"\n)\n#endif\n#ifdef FOO\n"),
+        Some([
+            "0(4) : error: unexpected ']' before the '(' was closed",
+            "0(5) : error: unterminated comment",
+            "0(3) : error: unclosed '{'",
+            "0(4) : error: unclosed '('",
+        ].iter().map(|e| format!("{}\n", e)).collect())
+    );
+    assert_eq!(
+        static_validator::check("#version 120\n#line 1 \"/common.glsl\"\n)\n#endif\n#ifdef FOO\n"),
+        Some("/common.glsl(2) : error: unexpected ')'\n/common.glsl(3) : error: #endif without a matching #if\n/common.glsl(4) : error: #if without a matching #endif\n".into())
+    );
+
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&common_path, "float test() {\n\treturn 0.5;\n").unwrap();
+
+    server.gen_initial_graph();
+    server.opengl_context = Rc::new(static_validator::StaticValidator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().is_empty());
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range.start.line, 0);
+    assert_eq!(common_diagnostics[0].message, "unclosed '{'");
+}
+
+#[test]
+fn test_lint_tags_diagnostics_in_disabled_blocks() {
+    let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| StdoutNewline {
+        s: Box::new(io::sink()),
+    });
+
+    let output = "0(7) : error C0000: never\n0(10) : error C0000: option\n0(13) : error C0000: defined\n".to_string();
+    let mut server = MinecraftShaderLanguageServer::with_validator(endpoint, Rc::new(opengl::CannedValidator {
+        output: Some(output),
+    }));
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&common_path, "#define COMMON\n").unwrap();
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n//#define OPTION\n\
+        #ifdef NEVER\nfloat a;\n#endif\n#ifdef OPTION\nfloat b;\n#endif\n#ifdef COMMON\nfloat c;\n#endif\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 3);
+
+    let never = final_diagnostics.iter().find(|d| d.range.start.line == 5).unwrap();
+    assert_eq!(never.tags, Some(vec![DiagnosticTag::Unnecessary]));
+    assert_eq!(never.message, "never (in a preprocessor block that is never enabled)");
+
+    // users can switch the option on, and COMMON is defined by an include
+    for line in &[8, 11] {
+        let diagnostic = final_diagnostics.iter().find(|d| d.range.start.line == *line).unwrap();
+        assert_eq!(diagnostic.tags, None);
+    }
+}
+
+#[test]
+fn test_tree_outline_command() {
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let common_path = shaders.join("common.glsl");
+    let lighting_path = shaders.join("lib").join("lighting.glsl");
+    let noise_path = shaders.join("noise.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let common_idx = graph.add_node(&common_path);
+        let lighting_idx = graph.add_node(&lighting_path);
+        let noise_idx = graph.add_node(&noise_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(common_idx, lighting_idx, IncludePosition { line: 0, start: 0, end: 0 });
+        graph.add_edge(final_idx, noise_idx, IncludePosition { line: 3, start: 0, end: 0 });
+    }
+
+    let command = commands::TreeOutlineCommand { graph: graph.clone() };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    let result = command.run_command(&tmp_path, path_arg(&final_path)).unwrap();
+    let outline = result.as_str().unwrap();
+    let lines: Vec<&str> = outline.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "shaders/final.fsh");
+    // siblings come out in no particular order, but each file is indented under its includer
+    let common = lines.iter().position(|l| *l == "  shaders/common.glsl (line 3)").unwrap();
+    assert_eq!(lines[common + 1], "    shaders/lib/lighting.glsl (line 1)");
+    assert!(lines.contains(&"  shaders/noise.glsl (line 4)"));
+
+    let result = command.run_command(&tmp_path, path_arg(&lighting_path)).unwrap();
+    assert_eq!(result, Value::String("shaders/lib/lighting.glsl\n".into()));
+}
+
+#[test]
+fn test_lint_file_not_yet_in_graph() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    // created after the graph was built
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    fs::copy(tmp_path.join("shaders").join("final.fsh"), &composite_path).unwrap();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&composite_path).unwrap();
+    assert!(diagnostics.get(&Url::from_file_path(&composite_path).unwrap()).unwrap().is_empty());
+    assert!(diagnostics.contains_key(&Url::from_file_path(tmp_path.join("shaders").join("common.glsl")).unwrap()));
+    assert!(server.graph.borrow_mut().find_node(&composite_path).is_some());
+
+    // one that is gone again before it is linted has nothing to report
+    let deleted_path = tmp_path.join("shaders").join("deferred.fsh");
+    let diagnostics = server.lint(&deleted_path).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics.get(&Url::from_file_path(&deleted_path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_document_links_config() {
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({})).unwrap();
+    assert!(config.document_links);
+
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    assert_eq!(server.document_links(&final_path).len(), 2);
+
+    server.config = serde_json::from_value(serde_json::json!({
+        "documentLinks": false
+    })).unwrap();
+    assert!(server.document_links(&final_path).is_empty());
+}
+
+#[test]
+fn test_lint_duplicate_include() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n#include \"common.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let check = |diagnostics: HashMap<Url, Vec<Diagnostic>>| {
+        let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+        assert_eq!(final_diagnostics.len(), 1);
+        assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Information));
+        assert_eq!(final_diagnostics[0].range, Range::new(Position::new(3, 10), Position::new(3, 21)));
+        assert_eq!(final_diagnostics[0].message, format!("{:?} is already included on line 3", common_path));
+    };
+    check(server.lint(&final_path).unwrap());
+
+    // saving again leaves both includes as they were
+    server.update_includes(&final_path);
+    server.update_includes(&final_path);
+    assert_eq!(server.graph.borrow().graph.edge_count(), 2);
+    check(server.lint(&final_path).unwrap());
+}
+
+#[test]
+fn test_graph_edges() {
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    let unused_path = tmp_path.join("shaders").join("unused.glsl");
+
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let common_idx = graph.add_node(&common_path);
+        graph.add_node(&unused_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 10, end: 22 });
+    }
+
+    let edges: Vec<(PathBuf, PathBuf, IncludePosition)> = graph.borrow().edges().collect();
+    assert_eq!(edges, vec![(final_path.clone(), common_path.clone(), IncludePosition { line: 2, start: 10, end: 22 })]);
+    assert_eq!(graph.borrow().nodes().count(), 3);
+
+    let command = commands::GraphDotCommand { graph };
+    command.run_command(&tmp_path, vec![]).unwrap();
+
+    let dot = fs::read_to_string(tmp_path.join("graph.dot")).unwrap();
+    assert!(dot.starts_with("digraph {\n"));
+    assert!(dot.contains(&format!("    {:?}\n", unused_path.to_string_lossy())));
+    assert!(dot.contains(&format!("    {:?} -> {:?} [ label = \"line 3\" ]\n", final_path.to_string_lossy(), common_path.to_string_lossy())));
+}
+
+#[test]
+fn test_lint_scope_changed() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let composite_path = tmp_path.join("shaders").join("composite.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::copy(&final_path, &composite_path).unwrap();
+
+    server.gen_initial_graph();
+    server.config.lint_scope = configuration::LintScope::Changed;
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(2)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    // without any opened, the first by path is validated
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(&composite_path).unwrap()));
+    assert!(!diagnostics.contains_key(&Url::from_file_path(&final_path).unwrap()));
+
+    server.note_recent_root(&final_path);
+    server.note_recent_root(&common_path);
+    assert_eq!(server.recent_roots, vec![final_path.clone()]);
+
+    let diagnostics = server.lint(&common_path).unwrap();
+    assert!(diagnostics.contains_key(&Url::from_file_path(&final_path).unwrap()));
+    assert!(!diagnostics.contains_key(&Url::from_file_path(&composite_path).unwrap()));
+}
+
+#[test]
+fn test_lint_misplaced_version() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "float x;\n  #version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&common_path, "// comment\n#version 120\nfloat test() {\n\treturn 0.5;\n}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Error));
+    assert_eq!(final_diagnostics[0].range, Range::new(Position::new(1, 2), Position::new(1, 14)));
+
+    // the #version of an included file comes after the code of the file including it
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range, Range::new(Position::new(1, 0), Position::new(1, 12)));
+
+    // comments may come before it
+    let root = PathBuf::from("/shaders/final.fsh");
+    let merged = "// header\n/* block\n*/\n#version 120\nvoid main() {}\n";
+    assert!(misplaced_versions(merged, &merge_views::line_map(merged, &root)).is_empty());
+}
+
+#[test]
+fn test_rename_symbol_uses_open_buffers() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    server.gen_initial_graph();
+
+    // neither edit has been saved
+    server.documents.insert(final_path.clone(), "#version 120\n#include \"/common.glsl\"\nvoid main() {\n\tfloat y = unsaved;\n}".into());
+    server.documents.insert(common_path.clone(), "float unsaved;\n".into());
+
+    let edit = server.rename_symbol(&final_path, Position::new(3, 13), "saved").unwrap();
+    let changes = edit.changes.unwrap();
+
+    let final_edits = changes.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_edits.len(), 1);
+    assert_eq!(final_edits[0].range, Range::new(Position::new(3, 11), Position::new(3, 18)));
+
+    let common_edits = changes.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_edits.len(), 1);
+    assert_eq!(common_edits[0].range, Range::new(Position::new(0, 6), Position::new(0, 13)));
+}
+
+#[test]
+fn test_validate_snippet_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .withf(|tree_type, source| *tree_type == TreeType::Vertex && source == "#version 330\nvoid main() {}\n")
+        .times(1)
+        .returning(|_, _| Some("0(3) : error C0000: syntax error, unexpected '}'\n".into()));
+    server.opengl_context = Rc::new(validator);
+
+    let command = commands::ValidateSnippetCommand { server: &server };
+    let diagnostics = command.run_command(&tmp_path, vec![serde_json::json!({
+        "source": "#version 330\nvoid main() {}\n",
+        "stage": "vertex",
+    })]).unwrap();
+    let diagnostics: Vec<Diagnostic> = serde_json::from_value(diagnostics).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range.start.line, 1);
+    assert_eq!(diagnostics[0].message, "syntax error, unexpected '}'");
+
+    // nothing is added to the graph
+    assert!(server.graph.borrow_mut().find_node(&std::env::temp_dir().join("snippet")).is_none());
+
+    assert!(command.run_command(&tmp_path, vec![serde_json::json!({ "source": "", "stage": "pixel" })]).is_err());
+}
+
+#[test]
+fn test_validate_snippet_command_without_roots() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+    assert!(server.roots.is_empty());
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| Some("0(3) : error C0000: syntax error, unexpected '}'\n".into()));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.run_command("validateSnippet", vec![serde_json::json!({
+        "source": "#version 330\nvoid main() {}\n",
+        "stage": "fragment",
+    })]).unwrap();
+    let diagnostics: Vec<Diagnostic> = serde_json::from_value(diagnostics).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range.start.line, 1);
+}
+
+#[test]
+fn test_max_include_depth() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    fs::write(shaders.join("common.glsl"), "#include \"/a.glsl\"\n").unwrap();
+    fs::write(shaders.join("a.glsl"), "#include \"/b.glsl\"\n").unwrap();
+    fs::write(shaders.join("b.glsl"), "float b;\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let final_idx = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    // b.glsl is 3 deep
+    server.config.max_include_depth = 3;
+    assert_eq!(server.get_dfs_for_node(final_idx).unwrap().len(), 4);
+
+    server.config.max_include_depth = 2;
+    let err = server.get_dfs_for_node(final_idx).unwrap_err();
+    assert!(matches!(err, dfs::error::DfsError::TooDeep(_)));
+    assert_eq!(err.files(), &[final_path.clone(), shaders.join("common.glsl"), shaders.join("a.glsl"), shaders.join("b.glsl")]);
+
+    // nothing is validated, the depth is reported instead
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert!(final_diagnostics[0].message.starts_with("Includes are nested more than 2 deep"));
+}
+
+#[test]
+fn test_affected_programs_command() {
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let composite_path = shaders.join("composite.fsh");
+    let common_path = shaders.join("common.glsl");
+    let lighting_path = shaders.join("lighting.glsl");
+    let noise_path = shaders.join("noise.glsl");
+
+    //     final    composite
+    //     /   \     /
+    // common  lighting
+    //     \   /
+    //     noise
+    let graph = Rc::new(RefCell::new(graph::CachedStableGraph::new()));
+    {
+        let mut graph = graph.borrow_mut();
+        let final_idx = graph.add_node(&final_path);
+        let composite_idx = graph.add_node(&composite_path);
+        let common_idx = graph.add_node(&common_path);
+        let lighting_idx = graph.add_node(&lighting_path);
+        let noise_idx = graph.add_node(&noise_path);
+
+        graph.add_edge(final_idx, common_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(final_idx, lighting_idx, IncludePosition { line: 3, start: 0, end: 0 });
+        graph.add_edge(composite_idx, lighting_idx, IncludePosition { line: 2, start: 0, end: 0 });
+        graph.add_edge(common_idx, noise_idx, IncludePosition { line: 1, start: 0, end: 0 });
+        graph.add_edge(lighting_idx, noise_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    }
+
+    let command = commands::AffectedProgramsCommand { graph };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    // final.fsh reaches noise.glsl both ways round the diamond, but is only listed once
+    let result = command.run_command(&tmp_path, path_arg(&noise_path)).unwrap();
+    assert_eq!(result, serde_json::json!([composite_path, final_path]));
+
+    let result = command.run_command(&tmp_path, path_arg(&common_path)).unwrap();
+    assert_eq!(result, serde_json::json!([final_path]));
+
+    let result = command.run_command(&tmp_path, path_arg(&final_path)).unwrap();
+    assert_eq!(result, serde_json::json!([final_path]));
+
+    assert!(command.run_command(&tmp_path, path_arg(&shaders.join("missing.glsl"))).is_err());
+}
+
+#[test]
+fn test_lint_macro_include() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\n  #include SHADOW_PATH // set by the build\nvoid main() {}\n").unwrap();
+
+    assert_eq!(find_macro_includes("#include \"/common.glsl\"\n#include SHADOW_PATH\n"), vec![
+        ("SHADOW_PATH".to_string(), IncludePosition { line: 1, start: 9, end: 20 }),
+    ]);
+
+    // only the quoted include is followed
+    let includes = server.find_includes(&final_path).unwrap();
+    assert_eq!(includes.len(), 1);
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Information));
+    assert_eq!(final_diagnostics[0].range, Range::new(Position::new(3, 11), Position::new(3, 22)));
+    assert!(final_diagnostics[0].message.contains("SHADOW_PATH"));
+}
+
+#[test]
+fn test_debounce_save_lint() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    // every save is linted without a window
+    assert!(!server.debounce_save_lint(&final_path));
+    assert!(!server.debounce_save_lint(&final_path));
+    assert!(server.pending_save_lints.is_empty());
+
+    server.config.validate_debounce_ms = Some(60_000);
+    server.last_save_lint.clear();
+    assert!(!server.debounce_save_lint(&final_path));
+    // the saves after the first are coalesced until the window has passed
+    assert!(server.debounce_save_lint(&final_path));
+    assert!(server.debounce_save_lint(&final_path));
+    assert_eq!(server.pending_save_lints.len(), 1);
+    // each file has a window of its own
+    assert!(!server.debounce_save_lint(&common_path));
+
+    // nothing is validated while the window is open
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    server.lint_pending_saves();
+    assert!(server.pending_save_lints.contains(&final_path));
+}
+
+#[test]
+fn test_custom_diagnostic_regex() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    assert!(compile_diagnostic_regex(r#"^(?P<linenum>\d+"#).is_err());
+    assert!(compile_diagnostic_regex(r#"^(?P<linenum>\d+): (?P<message>.+)"#).is_err());
+
+    let stdout = format!("[error] {}@4 syntax error\n[warning] 0@3 unused variable\n0(3) : error C0000: driver format\n", common_path.to_str().unwrap());
+
+    server.set_diagnostic_regex(Some(r#"^\[(?P<severity>error|warning)\] (?P<filepath>.+)@(?P<linenum>\d+) (?P<output>.+)"#));
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    assert_eq!(diagnostics.len(), 2);
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].message, "syntax error");
+    assert_eq!(common_diagnostics[0].range.start.line, 2);
+
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].message, "unused variable");
+    assert_eq!(final_diagnostics[0].severity, Some(DiagnosticSeverity::Warning));
+
+    // an invalid pattern falls back to the driver's format
+    server.set_diagnostic_regex(Some("(?P<output>"));
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].message, "driver format");
+}
+
+#[test]
+fn test_generate_merge_list_pragma_once_diamond() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let a_path = shaders.join("a.glsl");
+    let b_path = shaders.join("b.glsl");
+    let common_path = shaders.join("common.glsl");
+
+    fs::write(&final_path, "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(&a_path, "#include \"/common.glsl\"\nfloat a;\n").unwrap();
+    fs::write(&b_path, "#include \"/common.glsl\"\nfloat b;\n").unwrap();
+
+    let final_idx = server.graph.borrow_mut().add_node(&final_path);
+    let a_idx = server.graph.borrow_mut().add_node(&a_path);
+    let b_idx = server.graph.borrow_mut().add_node(&b_path);
+    let common_idx = server.graph.borrow_mut().add_node(&common_path);
+
+    server.graph.borrow_mut().add_edge(final_idx, a_idx, IncludePosition { line: 1, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(final_idx, b_idx, IncludePosition { line: 2, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(a_idx, common_idx, IncludePosition { line: 0, start: 0, end: 0 });
+    server.graph.borrow_mut().add_edge(b_idx, common_idx, IncludePosition { line: 0, start: 0, end: 0 });
+
+    let nodes = server.get_dfs_for_node(final_idx).unwrap();
+
+    // without the pragma, common.glsl is merged in through both a.glsl and b.glsl
+    fs::write(&common_path, "float common;\n").unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+    let result = merge_views::generate_merge_list(&nodes, &sources, &server.graph.borrow(), false);
+    assert_eq!(result.matches("float common;").count(), 2);
+
+    fs::write(&common_path, "#pragma once\nfloat common;\n").unwrap();
+    let sources = server.load_sources(&nodes).unwrap();
+    let result = merge_views::generate_merge_list(&nodes, &sources, &server.graph.borrow(), false);
+
+    let escaped = |path: &PathBuf| path.to_str().unwrap().replace("\\", "\\\\");
+    assert_eq!(result, format!(r#"#version 120
+#line 1 "{a}"
+#line 1 "{common}"
+#pragma once
+float common;
+#line 2 "{a}"
+float a;
+#line 3 "{root}"
+#line 1 "{b}"
+#line 2 "{b}"
+float b;
+#line 4 "{root}"
+void main() {{}}
+"#, a = escaped(&a_path), b = escaped(&b_path), common = escaped(&common_path), root = escaped(&final_path)));
+}
+
+#[test]
+fn test_nothing_validated_after_shutdown() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+
+    server.gen_initial_graph();
+    server.shutting_down = true;
+
+    // the mock panics if it is asked to validate anything
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    server.lint_saved(&final_path);
+
+    let mut diagnostics = HashMap::new();
+    diagnostics.insert(Url::from_file_path(&final_path).unwrap(), vec![missing_version_diagnostic()]);
+    server.publish_diagnostic(diagnostics, None);
+}
+
+#[test]
+fn test_unused_includes_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    let common_path = shaders.join("common.glsl");
+    let lighting_path = shaders.join("lighting.glsl");
+    let noise_path = shaders.join("noise.glsl");
+    let body_path = shaders.join("body.glsl");
+
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n#include \"/lighting.glsl\"\n#include \"/noise.glsl\"\nvoid main() {\n\tgl_FragColor = vec4(test() * SHADOW);\n#include \"/body.glsl\"\n}\n").unwrap();
+    // common.glsl is used through the file it includes
+    fs::write(&common_path, "#include \"/settings.glsl\"\nfloat unused_helper() {\n\treturn 1.0;\n}\n").unwrap();
+    fs::write(shaders.join("settings.glsl"), "#define SHADOW 0.5\n").unwrap();
+    fs::write(&lighting_path, "float test() {\n\treturn 0.5;\n}\n").unwrap();
+    fs::write(&noise_path, "uniform sampler2D noisetex;\nfloat noise(vec2 uv) {\n\treturn 0.0;\n}\n").unwrap();
+    fs::write(&body_path, "gl_FragColor.a = 1.0;\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let command = commands::UnusedIncludesCommand { graph: Rc::clone(&server.graph), files: &files::DiskFileProvider };
+    let path_arg = |path: &PathBuf| vec![Value::String(Url::from_file_path(path).unwrap().path().into())];
+
+    let result = command.run_command(&tmp_path, path_arg(&final_path)).unwrap();
+    assert_eq!(result, serde_json::json!([
+        { "file": noise_path, "line": 3 },
+    ]));
+}
+
+#[test]
+fn test_index_dry_run() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    server.config.index_dry_run = true;
+    server.config.ignore = vec!["common.glsl".into()];
+
+    server.gen_initial_graph();
+    assert_eq!(server.graph.borrow().graph.node_count(), 0);
+    // the shaderpacks are still found, for resolving the includes of files opened later
+    assert_eq!(server.pack_roots, vec![shaders.clone()]);
+
+    assert_eq!(server.find_index_files(), vec![shaders.join("final.fsh")]);
+}
+
+#[test]
+fn test_define_hover_chain() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::write(&final_path, "#version 120\n#define A B\n#include \"/common.glsl\"\n#define LOOP_A LOOP_B\nvoid main() {\n\tfloat x = A + C + LOOP_A + test();\n}\n").unwrap();
+    fs::write(&common_path, "#define B 3 // the answer\n#define C\n#define LOOP_B LOOP_A\n#define F(x) (x)\nfloat test() {\n\treturn 0.5;\n}\n").unwrap();
+
+    assert_eq!(symbols::define_value("#define B 3 // the answer\n", "B"), Some("3".into()));
+    assert_eq!(symbols::define_value("#define F(x) (x)\n", "F"), None);
+
+    server.gen_initial_graph();
+
+    let markdown = |hover: Hover| match hover.contents {
+        HoverContents::Markup(markup) => markup.value,
+        contents => panic!("expected markup, got {:?}", contents),
+    };
+
+    // A goes through B, which is defined in common.glsl
+    let hover = server.define_hover(&final_path, Position::new(5, 11)).unwrap().unwrap();
+    assert_eq!(hover.range, Some(Range::new(Position::new(5, 11), Position::new(5, 12))));
+    assert_eq!(markdown(hover), "```glsl\n#define A B\n#define B 3\n```\n\nExpands to `3`");
+
+    let hover = server.define_hover(&final_path, Position::new(5, 15)).unwrap().unwrap();
+    assert_eq!(markdown(hover), "```glsl\n#define C\n```");
+
+    let hover = server.define_hover(&final_path, Position::new(5, 20)).unwrap().unwrap();
+    assert_eq!(markdown(hover), "```glsl\n#define LOOP_A LOOP_B\n#define LOOP_B LOOP_A\n```\n\n`LOOP_A` is defined in terms of itself, so it never expands to a value");
+
+    // neither a macro nor anything at all
+    assert!(server.define_hover(&final_path, Position::new(5, 30)).unwrap().is_none());
+    assert!(server.define_hover(&final_path, Position::new(6, 1)).unwrap().is_none());
+}
+
+#[test]
+fn test_files_outside_shaderpack_are_not_linted() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    fs::create_dir(tmp_path.join("tools")).unwrap();
+    let outside_path = tmp_path.join("tools").join("preview.fsh");
+    fs::write(&outside_path, "void main() {\n\tgl_FragColor = vec4(1.0);\n}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    assert!(server.in_shaderpack(&tmp_path.join("shaders").join("final.fsh")));
+    assert!(!server.in_shaderpack(&outside_path));
+
+    // the mock panics if it is asked to validate anything
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    server.did_open_text_document(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: Url::from_file_path(&outside_path).unwrap(),
+            language_id: "glsl".into(),
+            version: 1,
+            text: fs::read_to_string(&outside_path).unwrap(),
+        },
+    });
+
+    // it is still indexed, so that includes of it resolve
+    assert!(server.graph.borrow_mut().find_node(&outside_path).is_some());
+}
+
+#[test]
+fn test_parse_validator_lines() {
+    let server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let final_path = PathBuf::from_str(if cfg!(windows) { "C:\\shaders\\final.fsh" } else { "/shaders/final.fsh" }).unwrap();
+    let final_url = Url::from_file_path(&final_path).unwrap();
+
+    // lines are parsed as they are produced, without the whole output being built up first
+    let lines = (0..10_000).map(|n| format!("0({}) : error C0000: error {}", n + 2, n));
+    let diagnostics = server.parse_validator_lines(&final_path, lines, &[]);
+    let final_diagnostics = diagnostics.get(&final_url).unwrap();
+    assert_eq!(final_diagnostics.len(), 10_000);
+    assert_eq!(final_diagnostics[9_999].range.start.line, 9_999);
+    assert_eq!(final_diagnostics[9_999].message, "error 9999");
+
+    // the line endings of the output don't end up in the messages
+    let stdout = "0(3) : error C0000: syntax error\nnot a diagnostic\r\n0(4) : warning B1234: unknown class\r\n";
+    let diagnostics = server.parse_validator_lines(&final_path, stdout.lines(), &[]);
+    let messages: Vec<&str> = diagnostics.get(&final_url).unwrap().iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+    assert_eq!(messages, vec!["syntax error", "unknown class"]);
+}
+
+#[test]
+fn test_create_missing_include_action() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let missing_path = tmp_path.join("shaders").join("lib").join("missing.glsl");
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n#include \"/lib/missing.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let include = IncludePosition { line: 2, start: 10, end: 27 };
+    let diagnostics = vec![missing_include_diagnostic(&missing_path, &include)];
+
+    let actions = server.create_file_actions(&final_path, Range::new(Position::new(2, 12), Position::new(2, 12)), &diagnostics);
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].title, "Create file /lib/missing.glsl");
+    assert_eq!(actions[0].command, "createFile");
+
+    // nothing is offered away from the include, or without the diagnostic
+    assert!(server.create_file_actions(&final_path, Range::new(Position::new(3, 0), Position::new(3, 4)), &diagnostics).is_empty());
+    assert!(server.create_file_actions(&final_path, Range::new(Position::new(2, 12), Position::new(2, 12)), &[]).is_empty());
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate().returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    server.create_file_command(actions[0].arguments.clone().unwrap()).unwrap();
+    assert_eq!(fs::read_to_string(&missing_path).unwrap(), "");
+
+    // the include isn't missing anymore, so the action is no longer offered
+    assert!(server.create_file_actions(&final_path, Range::new(Position::new(2, 12), Position::new(2, 12)), &diagnostics).is_empty());
+}
+
+#[test]
+fn test_include_extension_quick_fix() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+    fs::write(&final_path, "// a comment\r\n#version 120\r\n#include \"/common.glsl\"\r\nvoid main() {}\r\n").unwrap();
+
+    server.gen_initial_graph();
+
+    assert!(server.include_extension_actions(&final_path, &[]).is_empty());
+    let actions = server.include_extension_actions(&final_path, &[missing_include_extension_diagnostic()]);
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].command, "mcglsl.insertIncludeExtension");
+
+    let edit = server.include_extension_edit_command(actions[0].arguments.clone().unwrap()).unwrap();
+    let edit: WorkspaceEdit = serde_json::from_value(edit).unwrap();
+    let edits = edit.changes.unwrap().remove(&final_url).unwrap();
+    assert_eq!(edits, vec![TextEdit::new(
+        Range::new(Position::new(1, 12), Position::new(1, 12)),
+        "\r\n#extension GL_GOOGLE_include_directive : require".into(),
+    )]);
+
+    // nothing to insert once it is enabled
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/common.glsl\"\nvoid main() {}\n").unwrap();
+    assert_eq!(server.include_extension_edit_command(actions[0].arguments.clone().unwrap()).unwrap(), Value::Null);
+}
+
+#[test]
+fn test_include_paths() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::create_dir_all(tmp_path.join("vendor").join("first")).unwrap();
+    fs::create_dir_all(tmp_path.join("vendor").join("second")).unwrap();
+    fs::write(tmp_path.join("vendor").join("first").join("noise.glsl"), "float noise() { return 0.0; }\n").unwrap();
+    fs::write(tmp_path.join("vendor").join("second").join("noise.glsl"), "float noise() { return 1.0; }\n").unwrap();
+    fs::write(tmp_path.join("vendor").join("second").join("blur.glsl"), "float blur() { return 1.0; }\n").unwrap();
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n#include \"/noise.glsl\"\n#include \"blur\"\n#include \"/missing.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.config.include_paths = vec!["vendor/first".into(), "vendor/second".into()];
+
+    let includes: Vec<PathBuf> = server.find_includes(&final_path).unwrap().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(includes, vec![
+        // found where it always was, so the include paths aren't looked at
+        tmp_path.join("shaders").join("common.glsl"),
+        // the first include path that has it wins
+        tmp_path.join("vendor").join("first").join("noise.glsl"),
+        // relative includes and default extensions are resolved too
+        tmp_path.join("vendor").join("second").join("blur.glsl"),
+        // still reported as missing where it was included from
+        tmp_path.join("shaders").join("missing.glsl"),
+    ]);
+}
+
+#[test]
+fn test_memory_files() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let root = PathBuf::from_str(if cfg!(windows) { "C:\\memory" } else { "/memory" }).unwrap();
+    set_memory_files(&mut server, &root, &[
+        ("shaders/final.fsh", "#version 120\n#include \"/lib/common\"\n#include \"missing.glsl\"\nvoid main() {}\n"),
+        ("shaders/lib/common.glsl", "float x;\n"),
+    ]);
+
+    let final_path = root.join("shaders").join("final.fsh");
+    let common_path = root.join("shaders").join("lib").join("common.glsl");
+    let missing_path = root.join("shaders").join("missing.glsl");
+
+    let includes = server.find_includes(&final_path).unwrap();
+    assert_eq!(includes, vec![
+        (common_path.clone(), IncludePosition { line: 1, start: 10, end: 21 }),
+        (missing_path.clone(), IncludePosition { line: 2, start: 10, end: 22 }),
+    ]);
+
+    server.add_file_and_includes_to_graph(&final_path);
+    let node = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tree = server.get_dfs_for_node(node).unwrap();
+
+    let mut diagnostics = HashMap::new();
+    let sources = server.load_sources_with_diagnostics(&tree, &mut diagnostics).unwrap();
+    assert_eq!(sources.get(&common_path).map(String::as_str), Some("float x;\n"));
+    assert_eq!(
+        diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap(),
+        &vec![missing_include_diagnostic(&missing_path, &IncludePosition { line: 2, start: 10, end: 22 })]
+    );
+}
+
+#[test]
+fn test_document_versions() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let final_url = Url::from_file_path(&final_path).unwrap();
+    let common_url = Url::from_file_path(tmp_path.join("shaders").join("common.glsl")).unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate().returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    let text = fs::read_to_string(&final_path).unwrap();
+    server.did_open_text_document(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: final_url.clone(),
+            language_id: "glsl".into(),
+            version: 1,
+            text: text.clone(),
+        },
+    });
+    assert_eq!(server.document_version(&final_url), Some(1));
+
+    server.did_change_text_document(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+            uri: final_url.clone(),
+            version: 4,
+        },
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: format!("{}\n", text),
+        }],
+    });
+    // the diagnostics are still of what is on disk until the change is saved
+    assert_eq!(server.document_version(&final_url), None);
+    fs::write(&final_path, format!("{}\n", text)).unwrap();
+    assert_eq!(server.document_version(&final_url), None);
+    server.did_save_text_document(DidSaveTextDocumentParams {
+        text_document: TextDocumentIdentifier {
+            uri: final_url.clone(),
+        },
+        text: Some(format!("{}\n", text)),
+    });
+    assert_eq!(server.document_version(&final_url), Some(4));
+    // files that aren't open have no version to echo back
+    assert_eq!(server.document_version(&common_url), None);
+
+    server.did_close_text_document(DidCloseTextDocumentParams {
+        text_document: TextDocumentIdentifier {
+            uri: final_url.clone(),
+        },
+    });
+    assert_eq!(server.document_version(&final_url), None);
+
+    // reopened with changes that were never saved
+    server.did_open_text_document(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: final_url.clone(),
+            language_id: "glsl".into(),
+            version: 5,
+            text: text.clone(),
+        },
+    });
+    assert_eq!(server.document_version(&final_url), None);
+}
+
+#[test]
+fn test_lint_on_open() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+
+    server.gen_initial_graph();
+
+    assert!(server.lints_on_open(&final_path));
+    assert!(server.lints_on_open(&common_path));
+
+    server.config.lint_on_open = configuration::LintOnOpen::RootsOnly;
+    assert!(server.lints_on_open(&final_path));
+    assert!(!server.lints_on_open(&common_path));
+
+    server.config.lint_on_open = configuration::LintOnOpen::Never;
+    assert!(!server.lints_on_open(&final_path));
+    assert!(!server.lints_on_open(&common_path));
+
+    let config: configuration::Configuration = serde_json::from_value(serde_json::json!({ "lintOnOpen": "rootsOnly" })).unwrap();
+    assert_eq!(config.lint_on_open, configuration::LintOnOpen::RootsOnly);
+}
+
+#[test]
+fn test_dimension_folder_includes() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+    let tmp_path = tmp_dir.path().to_path_buf();
+    server.roots = vec![tmp_path.clone()];
+
+    let shaders = tmp_path.join("shaders");
+    for dir in &["lib", "world0/lib", "world-1"] {
+        fs::create_dir_all(shaders.join(PathBuf::from_slash(dir))).unwrap();
+    }
+    fs::write(shaders.join("lib").join("common.glsl"), "float x;\n").unwrap();
+    // absolute includes from a dimension folder still resolve against shaders/, never against the folder itself
+    fs::write(shaders.join("world0").join("lib").join("common.glsl"), "float y;\n").unwrap();
+    fs::write(shaders.join("world0").join("sky.glsl"), "float sky;\n").unwrap();
+    fs::write(shaders.join("gbuffers_terrain.fsh"), "#version 120\n#include \"lib/common.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(shaders.join("world0").join("gbuffers_terrain.fsh"), "#version 120\n#include \"/lib/common.glsl\"\n#include \"sky.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(shaders.join("world-1").join("gbuffers_terrain.fsh"), "#version 120\n#include \"../lib/common.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    assert_eq!(server.pack_roots, vec![shaders.clone()]);
+
+    let includes = server.find_includes(&shaders.join("world0").join("gbuffers_terrain.fsh")).unwrap();
+    assert_eq!(includes.into_iter().map(|(path, _)| path).collect::<Vec<_>>(), vec![
+        shaders.join("lib").join("common.glsl"),
+        // relative includes resolve against the dimension folder
+        shaders.join("world0").join("sky.glsl"),
+    ]);
+
+    let common = server.graph.borrow_mut().find_node(&shaders.join("lib").join("common.glsl")).unwrap();
+    assert_eq!(server.graph.borrow().affected_programs(common), vec![
+        shaders.join("gbuffers_terrain.fsh"),
+        shaders.join("world-1").join("gbuffers_terrain.fsh"),
+        shaders.join("world0").join("gbuffers_terrain.fsh"),
+    ]);
+
+    let decoy = server.graph.borrow_mut().find_node(&shaders.join("world0").join("lib").join("common.glsl")).unwrap();
+    assert!(server.graph.borrow().parent_node_indexes(decoy).is_empty());
+
+    // the programs of a dimension folder are checked against the same names as those of shaders/
+    let mut diagnostics = HashMap::new();
+    server.check_program_name(&shaders.join("world0").join("gbuffers_terrain.fsh"), &mut diagnostics);
+    assert!(diagnostics.is_empty());
+}
+
+#[allow(deprecated)]
+#[test]
+fn test_initialize_reports_validator_error() {
+    let mut server = new_temp_server();
+    server.validator_error = Some("no display or windowing system is available".into());
+
+    let tmp_dir = TempDir::new("mcshader").unwrap();
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(tmp_dir.path()).unwrap()),
+        client_info: None,
+        initialization_options: None,
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    // the server still initializes without OpenGL
+    let on_response = |resp: Option<Response>| match resp.unwrap().result_or_error {
+        ResponseResult::Result(_) => {}
+        ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+    };
+    let completable = MethodCompletable::new(ResponseCompletable::new(
+        Some(Id::Number(1)),
+        Box::new(on_response),
+    ));
+    server.initialize(initialize_params, completable);
+
+    // the error is only shown once
+    assert_eq!(server.validator_error, None);
+
+    server.endpoint.request_shutdown();
+}
+
+#[allow(deprecated)]
+#[test]
+fn test_project_config_file() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+    fs::write(tmp_path.join("shaders").join(".mcglslrc"), r#"{ "warningsAsErrors": true, "includePaths": ["vendor"], "maxIncludeDepth": 8 }"#).unwrap();
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(&tmp_path).unwrap()),
+        client_info: None,
+        initialization_options: None,
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+    let completable = MethodCompletable::new(ResponseCompletable::new(
+        Some(Id::Number(1)),
+        Box::new(|resp: Option<Response>| assert!(resp.is_some())),
+    ));
+    server.initialize(initialize_params, completable);
+
+    assert!(server.config.warnings_as_errors);
+    assert_eq!(server.config.include_paths, vec!["vendor".to_string()]);
+    assert_eq!(server.config.max_include_depth, 8);
+
+    // the client sends every setting, so those left at their defaults keep the values of the file
+    server.workspace_change_configuration(DidChangeConfigurationParams {
+        settings: serde_json::json!({ "mcglsl": { "warningsAsErrors": false, "includePaths": [], "maxIncludeDepth": 4, "lintOnOpen": "never" } }),
+    });
+    assert!(server.config.warnings_as_errors);
+    assert_eq!(server.config.include_paths, vec!["vendor".to_string()]);
+    assert_eq!(server.config.max_include_depth, 4);
+    assert_eq!(server.config.lint_on_open, configuration::LintOnOpen::Never);
+
+    server.endpoint.request_shutdown();
+}
+
+#[test]
+fn test_parse_project_config() {
+    let json = parse_project_config(r#"{ "warningsAsErrors": true, "includePaths": ["vendor"] }"#).unwrap();
+    let from_toml = parse_project_config("# settings for this pack\nwarningsAsErrors = true\nincludePaths = [\"vendor\"]\n").unwrap();
+    assert_eq!(json, serde_json::json!({ "warningsAsErrors": true, "includePaths": ["vendor"] }));
+    assert_eq!(from_toml, json);
+
+    assert!(parse_project_config("warningsAsErrors = ").is_err());
+    assert!(parse_project_config("{ \"warningsAsErrors\": ").is_err());
+}
+
+#[test]
+fn test_lint_crlf_and_cr_positions() {
+    assert_eq!(LineEnding::detect("#version 120\r\nvoid main() {}\r\n"), LineEnding::Crlf);
+    assert_eq!(LineEnding::detect("#version 120\rvoid main() {}\r"), LineEnding::Cr);
+    assert_eq!(LineEnding::detect("#version 120\nvoid main() {}\r\n"), LineEnding::Lf);
+    assert_eq!(LineEnding::detect("void main() {}"), LineEnding::Lf);
+    assert_eq!(normalize_line_endings("a\r\nb\rc\n\r\n"), "a\nb\nc\n\n");
+
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    let missing_path = tmp_path.join("shaders").join("missing.glsl");
+    fs::write(&final_path, "#version 120\r\n#include \"/common.glsl\"\r\n#include \"/missing.glsl\"\r\nvoid main() {\r\n\tERROR_HERE\r\n}\r\n").unwrap();
+    // old Mac OS line endings, which editors and the GLSL preprocessor both treat as line breaks
+    fs::write(&common_path, "float a;\rfloat b;\rERROR_HERE\r").unwrap();
+
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, source| Some(emulate_validator_line_numbers(&source)));
+    server.opengl_context = Rc::new(validator);
+
+    let diagnostics = server.lint(&final_path).unwrap();
+
+    let mut final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap().clone();
+    sort_diagnostics(&mut final_diagnostics);
+    let missing = final_diagnostics.iter().find(|d| d.source.as_deref() == Some(consts::INCLUDES_SOURCE)).unwrap();
+    assert_eq!(missing, &missing_include_diagnostic(&missing_path, &IncludePosition { line: 2, start: 10, end: 23 }));
+    let error = final_diagnostics.iter().find(|d| d.message == "syntax error").unwrap();
+    assert_eq!(error.range, Range::new(Position::new(4, 0), Position::new(4, 11)));
+
+    let common_diagnostics = diagnostics.get(&Url::from_file_path(&common_path).unwrap()).unwrap();
+    assert_eq!(common_diagnostics.len(), 1);
+    assert_eq!(common_diagnostics[0].range, Range::new(Position::new(2, 0), Position::new(2, 10)));
+}
+
+#[allow(deprecated)]
+#[test]
+fn test_semantic_tokens_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_tmp_dir("./testdata/06");
+
+    let initialize_params = InitializeParams {
+        process_id: None,
+        root_path: None,
+        root_uri: Some(Url::from_directory_path(tmp_path.clone()).unwrap()),
+        client_info: None,
+        initialization_options: None,
+        capabilities: ClientCapabilities {
+            workspace: None,
+            text_document: None,
+            experimental: None,
+            window: None,
+            general: Option::None,
+        },
+        trace: None,
+        workspace_folders: None,
+        locale: Option::None,
+    };
+
+    let on_response = |resp: Option<Response>| {
+        match resp.unwrap().result_or_error {
+            ResponseResult::Result(result) => {
+                // the client's semantic tokens provider is registered with this legend
+                assert_eq!(result["capabilities"]["experimental"]["semanticTokensLegend"]["tokenTypes"],
+                    serde_json::json!(["keyword", "string", "macro", "number"]));
+            },
+            ResponseResult::Error(e) => panic!("expected ResponseResult::Result(..), got {:?}", e),
+        }
+    };
+
+    let completable = MethodCompletable::new(ResponseCompletable::new(
+        Some(Id::Number(1)),
+        Box::new(on_response),
+    ));
+    server.initialize(initialize_params, completable);
+    server.endpoint.request_shutdown();
+
+    // the tokens are of the unsaved contents
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.documents.insert(final_path.clone(), "#version 120\n#define FOO 1\n".into());
+
+    let tokens = server.run_command("semanticTokens", vec![serde_json::json!(final_path.to_str().unwrap())]).unwrap();
+    assert_eq!(tokens["data"], serde_json::json!([
+        0, 0, 8, 0, 0, // #version
+        0, 9, 3, 3, 0, // 120
+        1, 0, 7, 0, 0, // #define
+        0, 8, 3, 2, 0, // FOO
+    ]));
+
+    assert!(server.run_command("semanticTokens", vec![]).is_err());
+}
+
+#[test]
+fn test_folding_ranges_command() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    // the ranges are of the unsaved contents
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.documents.insert(final_path.clone(), "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\nvoid main() {\n\tfloat x;\n}\n".into());
+
+    let ranges = server.run_command("foldingRanges", vec![serde_json::json!(final_path.to_str().unwrap())]).unwrap();
+    assert_eq!(ranges, serde_json::json!([
+        { "startLine": 1, "endLine": 2, "kind": "imports" },
+        { "startLine": 3, "endLine": 4 },
+    ]));
+
+    assert!(server.run_command("foldingRanges", vec![]).is_err());
+}
+
+#[test]
+fn test_color_commands() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    // the colors are of the unsaved contents
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.documents.insert(final_path.clone(), "#version 120\nconst vec3 sky = vec3(0.5, 0.25, 1.0);\n".into());
+    let path = serde_json::json!(final_path.to_str().unwrap());
+
+    let colors = server.run_command("documentColors", vec![path.clone()]).unwrap();
+    assert_eq!(colors, serde_json::json!([{
+        "range": { "start": { "line": 1, "character": 17 }, "end": { "line": 1, "character": 37 } },
+        "color": { "red": 0.5, "green": 0.25, "blue": 1.0, "alpha": 1.0 },
+    }]));
+
+    let presentations = server.run_command("colorPresentations", vec![
+        path,
+        serde_json::json!({ "red": 1.0, "green": 0.0, "blue": 0.5, "alpha": 0.5 }),
+        colors[0]["range"].clone(),
+    ]).unwrap();
+    assert_eq!(presentations[0]["label"], "vec4(1.0, 0.0, 0.5, 0.5)");
+    assert_eq!(presentations[0]["textEdit"]["range"], colors[0]["range"]);
+
+    assert!(server.run_command("colorPresentations", vec![serde_json::json!(final_path.to_str().unwrap())]).is_err());
+}
+
+#[test]
+fn test_debounce_lints_last_save() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let window = Duration::from_secs(60);
+    server.config.validate_debounce_ms = Some(window.as_millis() as u64);
+
+    // a burst whose last save is inside the window of the first
+    assert!(!server.debounce_save_lint(&final_path));
+    assert!(server.debounce_save_lint(&final_path));
+
+    // the timers of the saves go off before the window has passed since the last one
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    server.run_command("lintDueSave", vec![serde_json::json!(final_path.to_str().unwrap())]).unwrap();
+    assert!(server.pending_save_lints.contains(&final_path));
+
+    // and the last save is linted once it has
+    let last_save = Instant::now().checked_sub(window).unwrap();
+    server.last_save_lint.insert(final_path.clone(), last_save);
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+    server.run_command("lintDueSave", vec![serde_json::json!(final_path.to_str().unwrap())]).unwrap();
+    assert!(server.pending_save_lints.is_empty());
+
+    // a timer going off after the lint does nothing
+    server.opengl_context = Rc::new(opengl::MockShaderValidator::new());
+    server.run_command("lintDueSave", vec![serde_json::json!(final_path.to_str().unwrap())]).unwrap();
+}
+
+#[test]
+fn test_shutdown_stops_save_lint_timers() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    server.config.validate_debounce_ms = Some(60_000);
+
+    assert!(!server.debounce_save_lint(&final_path));
+    assert!(server.debounce_save_lint(&final_path));
+    assert_eq!(server.save_lint_timers.len(), 2);
+
+    // the timers give up on their window rather than being waited out
+    let stopping = Instant::now();
+    server.stop_save_lint_timers();
+    assert!(server.save_lint_timers.is_empty());
+    assert!(stopping.elapsed() < Duration::from_secs(10));
+
+    // and any started after shutting down don't wait at all
+    assert!(server.debounce_save_lint(&final_path));
+    server.stop_save_lint_timers();
+    assert!(server.save_lint_timers.is_empty());
+}
+
+#[test]
+fn test_workspace_folders_changed_command() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let (_tmp_dir_a, tmp_path_a) = copy_to_tmp_dir("./testdata/06");
+    let (_tmp_dir_b, tmp_path_b) = copy_to_tmp_dir("./testdata/06");
+    server.roots = vec![tmp_path_a.clone()];
+
+    server.gen_initial_graph();
+    assert_eq!(server.graph.borrow().graph.node_count(), 2);
+
+    // the client sends the event of `vscode.workspace.onDidChangeWorkspaceFolders` as is
+    server.run_command("workspaceFoldersChanged", vec![serde_json::json!({
+        "event": {
+            "added": [{ "uri": Url::from_directory_path(&tmp_path_b).unwrap(), "name": "b" }],
+            "removed": [{ "uri": Url::from_directory_path(&tmp_path_a).unwrap(), "name": "a" }],
+        },
+    })]).unwrap();
+    assert_eq!(server.roots, vec![tmp_path_b.clone()]);
+    assert_eq!(server.graph.borrow().graph.node_count(), 2);
+    assert!(server.graph.borrow_mut().find_node(&tmp_path_b.join("shaders").join("final.fsh")).is_some());
+
+    assert!(server.run_command("workspaceFoldersChanged", vec![]).is_err());
+}
+
+#[test]
+fn test_lint_include_paths_not_outside_pack() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    fs::create_dir_all(tmp_path.join("vendor")).unwrap();
+    fs::write(tmp_path.join("vendor").join("noise.glsl"), "float noise() { return 0.0; }\n").unwrap();
+    fs::write(tmp_path.join("outside.glsl"), "float outside;\n").unwrap();
+    fs::write(&final_path, "#version 120\n#extension GL_GOOGLE_include_directive : require\n#include \"/noise.glsl\"\n#include \"../outside.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.config.include_paths = vec!["vendor".into()];
+    server.gen_initial_graph();
+
+    let mut validator = opengl::MockShaderValidator::new();
+    validator.expect_validate()
+        .times(1)
+        .returning(|_, _| None);
+    server.opengl_context = Rc::new(validator);
+
+    // the include found in an include path is outside of the shaderpack on purpose, unlike the other
+    let diagnostics = server.lint(&final_path).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    assert_eq!(final_diagnostics.len(), 1);
+    assert_eq!(final_diagnostics[0].range, Range::new(Position::new(3, 10), Position::new(3, 25)));
+    assert!(final_diagnostics[0].message.contains("outside of the shaderpack"));
+}
+
+#[test]
+fn test_include_paths_recorded() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let noise_path = tmp_path.join("vendor").join("second").join("noise.glsl");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    fs::create_dir_all(tmp_path.join("vendor").join("second")).unwrap();
+    fs::write(&noise_path, "float noise() { return 1.0; }\n").unwrap();
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n#include \"/noise.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.config.include_paths = vec!["vendor/first".into(), "vendor/second".into()];
+    server.gen_initial_graph();
+
+    // the include path that resolved an include is kept in the graph
+    assert_eq!(server.graph.borrow().include_root(&noise_path), Some(&"vendor/second".to_string()));
+    assert_eq!(server.graph.borrow().include_root(&common_path), None);
+
+    // and shown in the include's link
+    assert_eq!(server.include_tooltip(&final_path, &noise_path), "/noise.glsl in the include path vendor/second");
+    assert_eq!(server.include_tooltip(&final_path, &common_path), "/common.glsl");
+}
+
+#[test]
+fn test_merge_commands_use_merge_dedup() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let shaders = tmp_path.join("shaders");
+    fs::write(shaders.join("a.glsl"), "#include \"/common.glsl\"\n").unwrap();
+    fs::write(shaders.join("b.glsl"), "#include \"/common.glsl\"\n").unwrap();
+    fs::write(shaders.join("common.glsl"), "float common;\n").unwrap();
+    fs::write(&final_path, "#version 120\n#include \"/a.glsl\"\n#include \"/b.glsl\"\nvoid main() {}\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let path = Value::String(Url::from_file_path(&final_path).unwrap().path().into());
+    let merged = |server: &mut MinecraftShaderLanguageServer, command: &str| {
+        let result = server.run_command(command, vec![path.clone()]).unwrap();
+        result.get("source").unwrap_or(&result).as_str().unwrap().to_string()
+    };
+
+    assert_eq!(merged(&mut server, "virtualMerge").matches("float common;").count(), 2);
+    assert_eq!(merged(&mut server, "mergedView").matches("float common;").count(), 2);
+
+    // the commands show the same merge as is validated
+    server.config.merge_dedup = true;
+    assert_eq!(merged(&mut server, "virtualMerge").matches("float common;").count(), 1);
+    assert_eq!(merged(&mut server, "mergedView").matches("float common;").count(), 1);
+}
+
+#[test]
+fn test_commands_read_open_documents() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    server.gen_initial_graph();
+
+    let final_path = tmp_path.join("shaders").join("final.fsh");
+    let common_path = tmp_path.join("shaders").join("common.glsl");
+    let path = Value::String(Url::from_file_path(&final_path).unwrap().path().into());
+
+    // neither edit has been saved
+    server.documents.insert(final_path.clone(), "#version 120\n#include \"/common.glsl\"\nvoid main() {\n\tfloat y = unsaved;\n}\n".into());
+    server.documents.insert(common_path.clone(), "float unsaved;\n".into());
+
+    let merged = server.run_command("virtualMerge", vec![path.clone()]).unwrap();
+    assert!(merged.as_str().unwrap().contains("float unsaved;"));
+    assert!(merged.as_str().unwrap().contains("float y = unsaved;"));
+
+    let definition = server.run_command("mergedDefinition", vec![path.clone(), serde_json::json!({ "line": 3, "character": 13 })]).unwrap();
+    assert_eq!(definition["root"], serde_json::json!(final_path));
+
+    // the include is used by the unsaved contents
+    assert_eq!(server.run_command("unusedIncludes", vec![path]).unwrap(), serde_json::json!([]));
+}
+
+#[test]
+fn test_merge_commands_merge_like_lint() {
+    let mut server = new_temp_server();
+
+    let (_tmp_dir, tmp_path) = copy_to_and_set_root("./testdata/06", &mut server);
+    server.endpoint.request_shutdown();
+
+    let shaders = tmp_path.join("shaders");
+    let final_path = shaders.join("final.fsh");
+    fs::write(&final_path, "#version 120\n#include \"/common.glsl\"\n#include \"/missing.glsl\"\nvoid main() {}\n").unwrap();
+    fs::write(shaders.join("common.glsl"), "#line 1\n#include \"/a.glsl\"\n").unwrap();
+    fs::write(shaders.join("a.glsl"), "float a;\n").unwrap();
+
+    server.gen_initial_graph();
+
+    let path = Value::String(Url::from_file_path(&final_path).unwrap().path().into());
+
+    // the missing include is merged in as empty, and the #line of common.glsl points back at it
+    let merged = server.run_command("virtualMerge", vec![path.clone()]).unwrap();
+    let merged = merged.as_str().unwrap();
+    assert!(merged.contains("float a;"));
+    assert!(merged.contains(&format!("#line 2 \"{}\"", shaders.join("common.glsl").to_str().unwrap())));
+
+    // a.glsl is 2 deep
+    server.config.max_include_depth = 1;
+    assert!(server.run_command("virtualMerge", vec![path]).is_err());
+}
+
+#[test]
+fn test_memory_files_case_mismatch() {
+    let mut server = new_temp_server();
+    server.endpoint.request_shutdown();
+
+    let root = PathBuf::from_str(if cfg!(windows) { "C:\\memory" } else { "/memory" }).unwrap();
+    set_memory_files(&mut server, &root, &[
+        ("shaders/final.fsh", "#version 120\n#include \"/Lib/common.glsl\"\nvoid main() {}\n"),
+        ("shaders/lib/common.glsl", "float x;\n"),
+    ]);
+
+    let final_path = root.join("shaders").join("final.fsh");
+    server.add_file_and_includes_to_graph(&final_path);
+    let node = server.graph.borrow_mut().find_node(&final_path).unwrap();
+    let tree = server.get_dfs_for_node(node).unwrap();
+
+    // the directories are listed from memory rather than disk, where they don't exist
+    let mut diagnostics = HashMap::new();
+    server.load_sources_with_diagnostics(&tree, &mut diagnostics).unwrap();
+    let final_diagnostics = diagnostics.get(&Url::from_file_path(&final_path).unwrap()).unwrap();
+    let mismatch = final_diagnostics.iter().find(|d| d.severity == Some(DiagnosticSeverity::Warning)).unwrap();
+    assert!(mismatch.message.contains(&format!("{:?}", root.join("shaders").join("lib").join("common.glsl"))));
+}
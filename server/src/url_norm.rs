@@ -13,17 +13,26 @@ pub trait FromJSON {
 }
 
 impl FromUrl for PathBuf {
-    #[cfg(target_family = "windows")]
+    // `to_file_path` decodes the percent escapes of the whole path as bytes, so `%20` and
+    // multi-byte UTF-8 characters decode correctly, and handles drive letters and UNC hosts.
+    // URLs without a file path, such as those of untitled documents, have their path decoded as is.
     fn from_url(u: Url) -> Self {
-        let path = percent_encoding::percent_decode_str(u.path().strip_prefix("/").unwrap()).decode_utf8().unwrap();
-        PathBuf::from_slash(path)
+        match u.to_file_path() {
+            Ok(path) => path,
+            Err(_) => decode_path(u.path()),
+        }
     }
+}
 
-    #[cfg(target_family = "unix")]
-    fn from_url(u: Url) -> Self {
-        let path = percent_encoding::percent_decode_str(u.path()).decode_utf8().unwrap();
-        PathBuf::from_slash(path)
-    }
+#[cfg(target_family = "windows")]
+fn decode_path(path: &str) -> PathBuf {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    PathBuf::from_slash(percent_encoding::percent_decode_str(path).decode_utf8_lossy())
+}
+
+#[cfg(target_family = "unix")]
+fn decode_path(path: &str) -> PathBuf {
+    PathBuf::from_slash(percent_encoding::percent_decode_str(path).decode_utf8_lossy())
 }
 
 impl FromJSON for PathBuf {
@@ -33,10 +42,8 @@ impl FromJSON for PathBuf {
         if !v.is_string() {
             return Err(anyhow::format_err!("cannot convert {:?} to PathBuf", v));
         }
-        let path = v.to_string();
-        let path = percent_encoding::percent_decode_str(
-            path.trim_start_matches('"').trim_end_matches('"').strip_prefix("/").unwrap()
-        ).decode_utf8()?;
+        let path = v.as_str().unwrap();
+        let path = percent_encoding::percent_decode_str(path.strip_prefix('/').unwrap_or(path)).decode_utf8()?;
         Ok(PathBuf::from_slash(path))
     }
 
@@ -46,10 +53,7 @@ impl FromJSON for PathBuf {
         if !v.is_string() {
             return Err(anyhow::format_err!("cannot convert {:?} to PathBuf", v));
         }
-        let path = v.to_string();
-        let path = percent_encoding::percent_decode_str(
-            path.trim_start_matches('"').trim_end_matches('"')
-        ).decode_utf8()?;
+        let path = percent_encoding::percent_decode_str(v.as_str().unwrap()).decode_utf8()?;
         Ok(PathBuf::from_slash(path))
     }
 }
\ No newline at end of file
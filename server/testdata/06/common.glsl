@@ -0,0 +1,3 @@
+float test() {
+	return 0.5;
+}
\ No newline at end of file